@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use json::JsonValue::{Object, Array};
 use json::JsonValue;
 use super::super::open_type_like::glyph::Glyph;
+use crate::error::{Error, Result};
 
 #[derive(Debug, Clone)]
 pub struct FontData {
@@ -31,37 +32,44 @@ fn get_array(value: &JsonValue) -> Option<&json::Array> {
 }
 
 impl FontData {
-    pub fn parse(source: &str) -> Option<FontData> {
-        let json_d = json::parse(source).unwrap();
-        let pack_data = get_array(&json_d)?;
+    pub fn parse(source: &str) -> Result<FontData> {
+        let json_d = json::parse(source).map_err(|e| Error::Syntax(e.to_string()))?;
+        let pack_data = get_array(&json_d).ok_or_else(|| Error::invalid("$", "应为数组"))?;
 
-        if pack_data.len() != 6 { return None; }
+        if pack_data.len() != 6 { return Err(Error::invalid("$", "应包含 6 项")); }
 
-        let name = pack_data.get(0)?.as_str()?.to_string();
-        let units_per_em = pack_data.get(1)?.as_i32()?;
-        let ascender = pack_data.get(2)?.as_i32()?;
-        let descender = pack_data.get(3)?.as_i32()?;
+        let get_i32 = |index: usize| pack_data[index].as_i32().ok_or_else(|| Error::invalid(&format!("$[{}]", index), "应为整数"));
+        let name = pack_data[0].as_str().ok_or_else(|| Error::invalid("$[0]", "应为字符串"))?.to_string();
+        let units_per_em = get_i32(1)?;
+        let ascender = get_i32(2)?;
+        let descender = get_i32(3)?;
 
-        let glyph_index_map_obj = get_object(pack_data.get(4)?)?;
+        let glyph_index_map_obj = get_object(&pack_data[4]).ok_or_else(|| Error::invalid("$[4]", "应为对象"))?;
 
         let mut glyph_index_map = HashMap::<String, usize>::new();
         for (str, value) in glyph_index_map_obj.iter() {
-            glyph_index_map.insert(str.to_string(), value.as_usize()?);
+            let index = value.as_usize().ok_or_else(|| Error::invalid(&format!("$[4].{}", str), "应为非负整数"))?;
+            glyph_index_map.insert(str.to_string(), index);
         }
 
         let mut glyphs_pack = Vec::<(i32, Box<Glyph>)>::new();
 
-        let glyphs_pack_arr = get_array(pack_data.get(5)?)?;
+        let glyphs_pack_arr = get_array(&pack_data[5]).ok_or_else(|| Error::invalid("$[5]", "应为数组"))?;
 
-        for item in glyphs_pack_arr {
-            let detail = get_array(item)?;
-            let advance_width = detail.get(0)?.as_i32()?;
-            let glyph_str = detail.get(1)?.as_str()?.to_string();
-            let glyph: Box<Glyph> = Box::new(Glyph::parse(&glyph_str, advance_width, units_per_em, ascender, descender, 0)?);
+        for (index, item) in glyphs_pack_arr.iter().enumerate() {
+            let path = format!("$[5][{}]", index);
+            let detail = get_array(item).ok_or_else(|| Error::invalid(&path, "应为数组"))?;
+            let advance_width = detail.get(0).and_then(|v| v.as_i32()).ok_or_else(|| Error::invalid(&format!("{}[0]", path), "应为整数"))?;
+            let glyph_str = detail.get(1).and_then(|v| v.as_str()).ok_or_else(|| Error::invalid(&format!("{}[1]", path), "应为字符串"))?.to_string();
+            let glyph = Glyph::parse(&glyph_str, advance_width, units_per_em, ascender, descender, 0)
+                .ok_or_else(|| Error::invalid(&format!("{}[1]", path), "字形路径格式不正确"))?;
+            let mut glyph: Box<Glyph> = Box::new(glyph);
+            glyph.glyph_id = index as u32;
+            glyph.font_name = name.clone();
             glyphs_pack.push((advance_width, glyph));
         }
 
-        Some(FontData {
+        Ok(FontData {
             name,
             units_per_em,
             ascender,
@@ -72,4 +80,3 @@ impl FontData {
         })
     }
 }
-
@@ -1,2 +1,3 @@
 pub mod text_data;
-pub mod font_data;
\ No newline at end of file
+pub mod font_data;
+pub mod validate;
\ No newline at end of file
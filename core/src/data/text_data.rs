@@ -1,5 +1,9 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use schemars::JsonSchema;
 use serde_json::Value;
-use crate::open_type_like::glyph::Glyph;
+use crate::open_type_like::glyph::{Glyph, ShapedGlyph, Variations};
+use crate::error::{self, Error};
+use super::validate;
 
 #[derive(Debug, Clone)]
 pub struct Shadow {
@@ -8,14 +12,20 @@ pub struct Shadow {
     pub color: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Gradient {
+    #[serde(rename = "type", default = "default_linear", deserialize_with = "deserialize_linear")]
     pub type_: String,
+    #[serde(default = "default_vector", deserialize_with = "deserialize_vector")]
     pub vector: (f32, f32),
+    #[serde(with = "color_stop")]
+    #[schemars(with = "std::collections::BTreeMap<String, String>")]
     pub stop: Vec<(String, (u8, u8, u8, f32))>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "ArtTextJson", into = "ArtTextJson")]
 pub struct ArtTextOption {
     pub fill: Option<Gradient>,
     pub texture: Option<String>,
@@ -30,42 +40,130 @@ pub enum ShadowOption {
     None,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum WritingMode {
+    #[serde(rename = "horizontal-tb")]
     HorizontalTB,
+    #[serde(rename = "vertical-rl")]
     VerticalRL,
+    #[serde(rename = "vertical-lr")]
     VerticalLR,
 }
 
-#[derive(Debug, Clone)]
+impl Default for WritingMode {
+    fn default() -> Self {
+        WritingMode::HorizontalTB
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(deny_unknown_fields)]
 pub struct ParagraphContent {
+    #[serde(default = "default_line_height", deserialize_with = "deserialize_line_height")]
+    #[schemars(range(min = 0))]
     pub line_height: f32,
+    #[serde(default, deserialize_with = "deserialize_number")]
     pub paragraph_indentation: f32,
+    #[serde(deserialize_with = "objects::deserialize")]
     pub blocks: Vec<TextBlock>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(deny_unknown_fields)]
 pub struct ParagraphData {
+    #[serde(default, deserialize_with = "deserialize_writing_mode")]
     pub writing_mode: WritingMode,
+    /// ltr | rtl | auto
+    #[serde(default = "default_direction", deserialize_with = "deserialize_direction")]
+    #[schemars(schema_with = "validate::direction_schema")]
+    pub direction: String,
+    /// 禁则处理的严格程度，strict | normal | loose
+    #[serde(default = "default_line_break", deserialize_with = "deserialize_line_break")]
+    #[schemars(schema_with = "validate::line_break_schema")]
+    pub line_break: String,
+    /// 断词方式，none | manual | auto
+    #[serde(default = "default_hyphens", deserialize_with = "deserialize_hyphens")]
+    #[schemars(schema_with = "validate::hyphens_schema")]
+    pub hyphens: String,
+    /// 断行算法，greedy 逐行折行，optimal 按 Knuth–Plass 整段求最优
+    #[serde(default = "default_line_breaking", deserialize_with = "deserialize_line_breaking")]
+    #[schemars(schema_with = "validate::line_breaking_schema")]
+    pub line_breaking: String,
+    #[serde(default = "default_text_align", deserialize_with = "deserialize_text_align")]
+    #[schemars(schema_with = "validate::text_align_schema")]
     pub text_align: String,
+    #[serde(default = "default_resizing", deserialize_with = "deserialize_resizing")]
+    #[schemars(schema_with = "validate::resizing_schema")]
     pub resizing: String,
+    #[serde(default = "default_align", deserialize_with = "deserialize_align")]
+    #[schemars(schema_with = "validate::align_schema")]
     pub align: String,
+    #[serde(default, deserialize_with = "deserialize_number")]
     pub paragraph_spacing: f32,
+    #[serde(rename = "contents", deserialize_with = "objects::deserialize")]
     pub paragraph_content: Vec<ParagraphContent>,
+    #[serde(rename = "advancedData", default, skip_serializing_if = "Option::is_none")]
     pub art_text: Option<ArtTextOption>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(deny_unknown_fields)]
 pub struct TextBlock {
+    #[serde(default, deserialize_with = "deserialize_string")]
     pub text: String,
+    /// 字体族，可以是数组或以逗号分隔，前面的字体缺字时依次使用后面的字体
+    #[serde(default = "default_font_family", deserialize_with = "deserialize_font_family")]
+    #[schemars(schema_with = "validate::font_family_schema")]
     pub font_family: String,
+    #[serde(default = "default_font_size", deserialize_with = "deserialize_font_size")]
+    #[schemars(range(min = 1))]
     pub font_size: f32,
+    #[serde(default, deserialize_with = "deserialize_number")]
     pub letter_spacing: f32,
+    #[serde(default = "default_color_string", deserialize_with = "deserialize_color_string")]
     pub fill: String,
+    /// 旧版斜体开关，等同于 `fontStyle: "italic"`
+    #[serde(default, deserialize_with = "deserialize_bool")]
     pub italic: bool,
+    /// 字重 1 到 1000，字体族中没有足够粗的字体时合成粗体
+    #[serde(default = "default_font_weight", deserialize_with = "deserialize_font_weight")]
+    #[schemars(range(min = 1, max = 1000))]
+    pub font_weight: u16,
+    /// normal | italic | oblique，没有斜体字体时倾斜绘制
+    #[serde(default = "default_font_style", deserialize_with = "deserialize_font_style")]
+    #[schemars(schema_with = "validate::font_style_schema")]
+    pub font_style: String,
+    /// 可变字体的轴取值，同 CSS 写法，如 `"wght" 700, "wdth" 80`，未指定 wght 时使用 fontWeight
+    #[serde(default, skip_serializing_if = "String::is_empty", deserialize_with = "deserialize_string")]
+    pub font_variation_settings: String,
+    #[serde(default = "default_color_string", deserialize_with = "deserialize_color_string")]
     pub stroke: String,
+    #[serde(default, deserialize_with = "deserialize_number")]
+    #[schemars(range(min = 0))]
     pub stroke_width: f32,
+    /// underline | overline | line-through，可用空格组合多种
+    #[serde(default, deserialize_with = "deserialize_string")]
+    #[schemars(schema_with = "validate::decoration_schema")]
     pub decoration: String,
+    /// solid | double | dotted | dashed | wavy
+    #[serde(default = "default_decoration_style", deserialize_with = "deserialize_decoration_style")]
+    #[schemars(schema_with = "validate::decoration_style_schema")]
+    pub decoration_style: String,
+    /// 为空时使用文字颜色
+    #[serde(default, skip_serializing_if = "String::is_empty", deserialize_with = "deserialize_string")]
+    pub decoration_color: String,
+    /// 下划线与上划线避开字形，none | auto | all，auto 不避开中日韩文字
+    #[serde(default = "default_decoration_skip_ink", deserialize_with = "deserialize_decoration_skip_ink")]
+    #[schemars(schema_with = "validate::decoration_skip_ink_schema")]
+    pub decoration_skip_ink: String,
+    #[serde(default = "default_true", deserialize_with = "deserialize_font_kerning")]
+    pub font_kerning: bool,
+    /// BCP 47 语言标签，用于断词与缺字时选择回退字体，如 `ja` 时汉字优先使用日文字体
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub lang: String,
 }
 
 impl Default for TextBlock {
@@ -77,24 +175,43 @@ impl Default for TextBlock {
             letter_spacing: 0.0,
             fill: "".to_string(),
             italic: false,
+            font_weight: 400,
+            font_style: "".to_string(),
+            font_variation_settings: "".to_string(),
             stroke: "".to_string(),
             stroke_width: 0.0,
             decoration: "".to_string(),
+            decoration_style: "".to_string(),
+            decoration_color: "".to_string(),
+            decoration_skip_ink: "".to_string(),
+            font_kerning: true,
+            lang: "".to_string(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct TextData {
+    #[serde(default = "default_size", deserialize_with = "deserialize_size")]
+    #[schemars(range(min = 0))]
     pub width: f32,
+    #[serde(default = "default_size", deserialize_with = "deserialize_size")]
+    #[schemars(range(min = 0))]
     pub height: f32,
     pub paragraph: ParagraphData,
+    /// 原始 JSON
+    #[serde(skip)]
     pub source: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct TextBlockDetail<'a> {
     pub glyph: &'a Glyph,
+    /// 字形簇经过 GSUB/GPOS 处理后的字形，为空时直接使用 `glyph`
+    pub cluster: Vec<(&'a Glyph, ShapedGlyph)>,
+    /// 与后一个字形之间的字偶距，字体单位
+    pub kerning: i32,
     pub writing_mode: WritingMode,
     pub paragraph_indentation: f32,
     pub line_height: f32,
@@ -106,12 +223,43 @@ pub struct TextBlockDetail<'a> {
     pub position: (f32, f32),
     pub base_line_to_top: f32,
     pub base_line_to_bottom: f32,
+    /// UAX #9 嵌入层级，奇数为从右到左
+    pub bidi_level: u8,
+    pub paragraph_level: u8,
+    /// 字形相对字框左侧的偏移，从右到左时字间距位于字形左侧
+    pub glyph_offset: f32,
+    /// 可以在此字后断词时插入的连字符字形
+    pub hyphen: Option<&'a Glyph>,
+    /// 选用的字体不够粗或不是斜体时，加粗轮廓或倾斜绘制
+    pub synthetic_bold: bool,
+    pub synthetic_oblique: bool,
+}
+
+impl TextBlock {
+    /// italic 与 oblique 都优先选用斜体字体
+    pub fn is_italic(&self) -> bool {
+        self.italic || self.font_style == "italic" || self.font_style == "oblique"
+    }
+
+    /// `fontFamily` 中依次使用的字体族
+    pub fn get_font_families(&self) -> Vec<String> {
+        split_font_family(&self.font_family)
+    }
+
+    /// 无法解析的取值忽略，由严格模式报告
+    pub fn get_variations(&self) -> Variations {
+        let mut values = vec![(*b"wght", self.font_weight as f32)];
+        values.extend(split_variation_settings(&self.font_variation_settings).unwrap_or_default());
+        Variations::new(&values)
+    }
 }
 
 impl<'a> TextBlockDetail<'a> {
     pub fn default(glyph: &'a Glyph) -> Self {
         TextBlockDetail {
             glyph,
+            cluster: vec![],
+            kerning: 0,
             writing_mode: WritingMode::HorizontalTB,
             paragraph_indentation: 0.0,
             line_height: 0.0,
@@ -123,18 +271,46 @@ impl<'a> TextBlockDetail<'a> {
             position: (0.0, 0.0),
             base_line_to_top: 0.0,
             base_line_to_bottom: 0.0,
+            bidi_level: 0,
+            paragraph_level: 0,
+            glyph_offset: 0.0,
+            hyphen: None,
+            synthetic_bold: false,
+            synthetic_oblique: false,
         }
     }
+
+    pub fn is_rtl(&self) -> bool {
+        self.bidi_level % 2 == 1
+    }
+
+    pub fn get_spacing(&self, font_size: f32) -> f32 {
+        let advance = match self.writing_mode {
+            WritingMode::HorizontalTB if !self.cluster.is_empty() => {
+                self.cluster.iter().fold(0f32, |p, (glyph, shaped)| {
+                    p + shaped.x_advance as f32 / glyph.units_per_em as f32 * font_size
+                })
+            }
+            _ => self.glyph.get_spacing(font_size, &self.writing_mode)
+        };
+        advance + self.get_kerning(font_size)
+    }
+
+    pub fn get_kerning(&self, font_size: f32) -> f32 {
+        if self.kerning == 0 { return 0f32; }
+        let glyph = self.cluster.last().map(|(g, _)| *g).unwrap_or(self.glyph);
+        self.kerning as f32 / glyph.units_per_em as f32 * font_size
+    }
 }
 
-fn split_color_string(s: &str) -> Option<(u8, u8, u8, f32)> {
+pub(crate) fn split_color_string(s: &str) -> Option<(u8, u8, u8, f32)> {
     let s = s.replace(" ", "");
     if !s.contains("rgb") {
         if !s.contains("#") {
             return None;
         }
         let without_prefix = s.trim_start_matches("#");
-        let color = i64::from_str_radix(without_prefix, 16u32).unwrap() as i64;
+        let color = i64::from_str_radix(without_prefix, 16u32).ok()?;
         let r = (color >> 16) as u8;
         let g = (color >> 8) as u8;
         let b = color as u8;
@@ -157,337 +333,474 @@ fn split_color_string(s: &str) -> Option<(u8, u8, u8, f32)> {
     }
 }
 
-impl TextData {
-    pub fn parse(source: &str) -> Option<TextData> {
-        let result = serde_json::from_str(source);
-        if result.is_err() { return None; }
-        let json: Value = result.unwrap();
-        let default_num = Value::Number(serde_json::Number::from_f64(200f64).unwrap());
-        let width = *&json.get("width").unwrap_or(&default_num).as_f64().unwrap_or_else(||
-            *&json.get("width").unwrap_or(&default_num).as_str().unwrap_or("200").parse::<f64>().unwrap_or(200f64)
-        ) as f32;
-        let height = *&json.get("height").unwrap_or(&default_num).as_f64().unwrap_or_else(||
-            *&json.get("height").unwrap_or(&default_num).as_str().unwrap_or("200").parse::<f64>().unwrap_or(200f64)
-        ) as f32;
-
-        if *&json["paragraph"].as_object().is_none() { return None; }
-
-        let paragraph_json = &json["paragraph"].as_object().unwrap();
-        let default_text_align = Value::String("center".to_string());
-        let text_align = paragraph_json.get("textAlign").unwrap_or(&default_text_align).as_str().unwrap_or("center").to_string();
-        let default_resizing = Value::String("grow-vertically".to_string());
-        let resizing = paragraph_json.get("resizing").unwrap_or(&default_resizing).as_str().unwrap_or("grow-vertically").to_string();
-        let default_align = Value::String("middle".to_string());
-        let align = paragraph_json.get("align").unwrap_or(&default_align).as_str().unwrap_or("middle").to_string();
-        let default_paragraph_spacing = Value::Number(serde_json::Number::from_f64(0.0).unwrap());
-        let paragraph_spacing = paragraph_json.get("paragraphSpacing").unwrap_or(&default_paragraph_spacing).as_f64().unwrap_or(0.0) as f32;
-        let mut art_text: Option<ArtTextOption> = None;
-
-        let writing_mode = paragraph_json.get("writingMode")
-            .and_then(|value| value.as_str())
-            .and_then(|s| match s {
-                "horizontal-tb" => Some(WritingMode::HorizontalTB),
-                "vertical-rl" => Some(WritingMode::VerticalRL),
-                "vertical-lr" => Some(WritingMode::VerticalLR),
-                _ => None
-            }).unwrap_or(WritingMode::HorizontalTB);
-
-        if paragraph_json.get("advancedData").unwrap_or_else(|| &Value::Null).as_object().is_some() {
-            let art_text_json = paragraph_json.get("advancedData").unwrap().as_object().unwrap();
-            let get_fill = || {
-                let type_ = "linear".to_string();
-                let default_stop = vec![("0".to_string(), (0u8, 0u8, 0u8, 1.0f32))];
-
-                if art_text_json.get("fill").is_some() {
-                    let default_fill = r#"
-                    {
-                        "stop": {
-                            "0": "rgba(0,0,0,1)"
-                        },
-                        "vector": [0, 1]
-                    }"#;
-                    let default_fill = serde_json::from_str(default_fill).unwrap();
-                    let fill_json = art_text_json.get("fill").unwrap().as_object().unwrap_or(&default_fill);
-                    let stop = {
-                        let mut stop = Vec::<(String, (u8, u8, u8, f32))>::new();
-                        let stop_json = fill_json.get("stop").unwrap().as_object().unwrap();
-                        for (key, value) in stop_json.iter() {
-                            let value = value.as_str().unwrap_or("rgba(0,0,0,1)");
-                            let value = split_color_string(value).unwrap_or((0u8, 0u8, 0u8, 1.0f32));
-                            stop.push((key.to_string(), value));
-                        }
-                        stop
-                    };
-                    let default_vec = vec![];
-                    let vector = fill_json.get("vector").unwrap().as_array().unwrap_or(&default_vec);
-                    let v0 = vector.get(0).unwrap_or(&Value::Number(serde_json::Number::from_f64(0.0).unwrap())).as_f64().unwrap_or(0.0) as f32;
-                    let v1 = vector.get(1).unwrap_or(&Value::Number(serde_json::Number::from_f64(0.0).unwrap())).as_f64().unwrap_or(0.0) as f32;
-                    Gradient {
-                        type_,
-                        vector: (v0, v1),
-                        stop,
-                    }
-                } else {
-                    Gradient {
-                        type_,
-                        vector: (0.0, 1.0),
-                        stop: default_stop,
-                    }
-                }
-            };
-            let get_stroke = || {
-                let default_vec = Value::Array(vec![]);
-                let default_vec1 = vec![];
-                let stroke = art_text_json.get("stroke").unwrap_or(&default_vec).as_array().unwrap_or(&default_vec1);
-                let stroke = {
-                    let mut v: Vec<((u8, u8, u8, f32), f32)> = vec![];
-                    for item in stroke.iter() {
-                        if item.as_object().is_none() {
-                            continue;
-                        }
-                        let item = item.as_object().unwrap();
-                        if let Some(hidden) = item.get("hidden") {
-                            if let Some(hidden) = hidden.as_bool() {
-                                if hidden { continue; }
-                            }
-                        }
-                        if item.get("width").is_none() {
-                            continue;
-                        }
-                        let color: (u8, u8, u8, f32) = {
-                            if item.get("color").is_none() {
-                                (0u8, 0u8, 0u8, 1.0f32)
-                            } else {
-                                let color_str = item.get("color").unwrap().as_str().unwrap_or("rgba(0,0,0,1)");
-                                split_color_string(color_str).unwrap_or((0u8, 0u8, 0u8, 1.0f32))
-                            }
-                        };
-                        let width = item.get("width").unwrap().as_f64().unwrap_or(0.0) as f32;
-                        v.push((color, width))
-                    }
-                    v
-                };
-                stroke
-            };
-            let get_shadow = || {
-                let default_vec = Value::Array(vec![]);
-                let default_vec1 = vec![];
-                let shadow = art_text_json.get("shadow").unwrap_or(&default_vec).as_array().unwrap_or(&default_vec1);
-                let shadow = {
-                    let mut v: Vec<((u8, u8, u8, f32), (f32, f32), f32)> = vec![];
-                    for item in shadow.iter() {
-                        if item.as_object().is_none() {
-                            continue;
-                        }
-                        let item = item.as_object().unwrap();
-                        if let Some(hidden) = item.get("hidden") {
-                            if let Some(hidden) = hidden.as_bool() {
-                                if hidden { continue; }
-                            }
-                        }
-                        let blur =
-                            if item.get("blur").is_none() {
-                                0f32
-                            } else {
-                                item.get("blur").unwrap().as_f64().unwrap_or(0f64) as f32
-                            };
-                        let color: (u8, u8, u8, f32) =
-                            if item.get("color").is_none() {
-                                (0u8, 0u8, 0u8, 1.0f32)
-                            } else {
-                                let color_str = item.get("color").unwrap().as_str().unwrap_or("rgba(0,0,0,1)");
-                                split_color_string(color_str).unwrap_or((0u8, 0u8, 0u8, 1.0f32))
-                            };
-                        let offset =
-                            if item.get("offset").is_none() {
-                                (0f32, 0f32)
-                            } else {
-                                let default_num = Value::Number(serde_json::Number::from_f64(0.0).unwrap());
-                                let default_vec = vec![];
-                                let offset = item.get("offset").unwrap().as_array().unwrap_or(&default_vec);
-                                let o0 = offset.get(0).unwrap_or(&default_num.clone()).as_f64().unwrap_or(0.0) as f32;
-                                let o1 = offset.get(1).unwrap_or(&default_num.clone()).as_f64().unwrap_or(0.0) as f32;
-                                (o0, o1)
-                            };
-                        v.push((color, offset, blur))
-                    }
-                    v
-                };
-                shadow
-            };
-
-            let fill = Some(get_fill());
-            let texture =
-                if art_text_json.get("texture").is_some() {
-                    let text = art_text_json.get("texture").unwrap().as_str();
-                    if text.is_some() {
-                        Some(text.unwrap().to_string())
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-            let stroke = get_stroke();
-            let shadow = get_shadow();
-            let use_ = art_text_json.get("use").and_then(|v| v.as_bool()).unwrap_or(true);
-            art_text = Some(ArtTextOption {
-                fill,
-                texture,
-                stroke,
-                shadow,
-                use_,
-            })
+/// 以逗号分隔的字体族，去掉两侧的空白与引号
+pub fn split_font_family(font_family: &str) -> Vec<String> {
+    font_family.split(',')
+        .map(|family| family.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .filter(|family| !family.is_empty())
+        .collect()
+}
+
+/// 解析 `"wght" 700, "wdth" 80` 形式的轴取值，空字符串与 normal 表示不指定
+pub(crate) fn split_variation_settings(s: &str) -> Option<Vec<([u8; 4], f32)>> {
+    let s = s.trim();
+    if s.is_empty() || s == "normal" { return Some(vec![]); }
+    s.split(',').map(|item| {
+        let item = item.trim();
+        let quote = item.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let rest = &item[1..];
+        let end = rest.find(quote)?;
+        let tag = &rest.as_bytes()[..end];
+        if tag.len() != 4 { return None; }
+        let value = rest[end + 1..].trim().parse::<f32>().ok().filter(|value| value.is_finite())?;
+        Some(([tag[0], tag[1], tag[2], tag[3]], value))
+    }).collect()
+}
+
+/// 颜色与 `(r, g, b, a)` 互转，输出统一为 rgba 形式
+mod color {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde_json::Value;
+
+    pub fn serialize<S: Serializer>(color: &(u8, u8, u8, f32), serializer: S) -> Result<S::Ok, S::Error> {
+        let (r, g, b, a) = color;
+        serializer.serialize_str(&format!("rgba({},{},{},{})", r, g, b, a))
+    }
+
+    /// 不是字符串或无法识别时使用黑色，由严格模式报告
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(u8, u8, u8, f32), D::Error> {
+        Ok(Value::deserialize(deserializer)?.as_str()
+            .and_then(super::split_color_string)
+            .unwrap_or_else(super::default_color))
+    }
+}
+
+/// 渐变色标按位置从小到大排列，与旧版按键排序的结果一致
+mod color_stop {
+    use serde::{Deserializer, Serializer};
+    use serde::de::{MapAccess, Visitor};
+    use serde::ser::SerializeMap;
+    use std::fmt;
+
+    struct Color((u8, u8, u8, f32));
+
+    impl<'de> serde::Deserialize<'de> for Color {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            super::color::deserialize(deserializer).map(Color)
         }
+    }
 
-        let paragraph_content = {
-            let mut v = Vec::<ParagraphContent>::new();
-            let content_json: &Vec<Value> = paragraph_json.get("contents")?.as_array()?;
-            for item in content_json.iter() {
-                let obj = item.as_object();
-                if obj.is_none() { continue; }
-                let obj = obj.unwrap();
-                let line_height = {
-                    let value = obj.get("lineHeight");
-                    if value.is_none() {
-                        1.2f32
-                    } else {
-                        value.unwrap().as_f64().unwrap_or(1.2f64) as f32
-                    }
-                };
-                let paragraph_indentation = {
-                    let value = obj.get("paragraphIndentation");
-                    if value.is_none() {
-                        0f32
-                    } else {
-                        value.unwrap().as_f64().unwrap_or(0f64) as f32
-                    }
-                };
-                let blocks = {
-                    let mut block_vec = Vec::<TextBlock>::new();
-                    let block_vec_json = obj.get("blocks")?.as_array()?;
-                    for item in block_vec_json.iter() {
-                        let obj = item.as_object();
-                        if obj.is_none() { continue; }
-                        let obj = obj.unwrap();
-                        let text = {
-                            let value = obj.get("text");
-                            if value.is_none() {
-                                ""
-                            } else {
-                                value.unwrap().as_str().unwrap_or("")
-                            }
-                        }.to_string();
-                        let font_family = {
-                            let value = obj.get("fontFamily");
-                            if value.is_none() {
-                                "default"
-                            } else {
-                                value.unwrap().as_str().unwrap_or("default")
-                            }
-                        }.to_string();
-                        let fill = {
-                            let value = obj.get("fill");
-                            if value.is_none() {
-                                "#000000"
-                            } else {
-                                value.unwrap().as_str().unwrap_or("#000000")
-                            }
-                        }.to_string();
-                        let stroke = {
-                            let value = obj.get("stroke");
-                            if value.is_none() {
-                                "#000000"
-                            } else {
-                                value.unwrap().as_str().unwrap_or("#000000")
-                            }
-                        }.to_string();
-                        let decoration = {
-                            let value = obj.get("decoration");
-                            if value.is_none() {
-                                ""
-                            } else {
-                                value.unwrap().as_str().unwrap_or("")
-                            }
-                        }.to_string();
-                        let font_size = {
-                            let value = obj.get("fontSize");
-                            if value.is_none() {
-                                16f32
-                            } else {
-                                value.unwrap().as_f64().unwrap_or(16f64) as f32
-                            }
-                        };
-                        let letter_spacing = {
-                            let value = obj.get("letterSpacing");
-                            if value.is_none() {
-                                0f32
-                            } else {
-                                value.unwrap().as_f64().unwrap_or(0f64) as f32
-                            }
-                        };
-                        let stroke_width = {
-                            let value = obj.get("strokeWidth");
-                            if value.is_none() {
-                                0f32
-                            } else {
-                                value.unwrap().as_f64().unwrap_or(0f64) as f32
-                            }
-                        };
-                        let italic = {
-                            let value = obj.get("italic");
-                            if value.is_none() {
-                                false
-                            } else {
-                                value.unwrap().as_bool().unwrap_or(false)
-                            }
-                        };
-                        let block = TextBlock {
-                            text,
-                            font_family,
-                            font_size,
-                            letter_spacing,
-                            fill,
-                            italic,
-                            stroke,
-                            stroke_width,
-                            decoration,
-                        };
-                        block_vec.push(block);
-                    }
-                    block_vec
-                };
-                let content = ParagraphContent {
-                    line_height,
-                    paragraph_indentation,
-                    blocks,
-                };
-                v.push(content);
+    pub fn serialize<S: Serializer>(stop: &Vec<(String, (u8, u8, u8, f32))>, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(stop.len()))?;
+        for (key, (r, g, b, a)) in stop.iter() {
+            map.serialize_entry(key, &format!("rgba({},{},{},{})", r, g, b, a))?;
+        }
+        map.end()
+    }
+
+    struct StopVisitor;
+
+    impl<'de> Visitor<'de> for StopVisitor {
+        type Value = Vec<(String, (u8, u8, u8, f32))>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("色标位置到颜色的映射")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut stop = Vec::new();
+            while let Some((key, Color(color))) = map.next_entry::<String, Color>()? {
+                stop.push((key, color));
             }
-            v
-        };
+            // 无法解析为数字的位置排在最后
+            let offset = |key: &String| key.trim().parse::<f32>().unwrap_or(f32::INFINITY);
+            stop.sort_by(|a, b| offset(&a.0).partial_cmp(&offset(&b.0)).unwrap_or(std::cmp::Ordering::Equal));
+            Ok(stop)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, (u8, u8, u8, f32))>, D::Error> {
+        deserializer.deserialize_map(StopVisitor)
+    }
+}
+
+/// 类型不符时使用默认值，与旧版解析一致，类型错误由严格模式报告
+/// 数组中不是对象的项跳过，与旧版一致，严格模式由 `check_types` 报告
+mod objects {
+    use serde::{Deserialize, Deserializer};
+    use serde::de::{IgnoredAny, MapAccess, SeqAccess, Visitor};
+    use serde::de::value::MapAccessDeserializer;
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    struct Item<T>(Option<T>);
+
+    struct ItemVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for ItemVisitor<T> {
+        type Value = Item<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("对象")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(|value| Item(Some(value)))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            while seq.next_element::<IgnoredAny>()?.is_some() {}
+            Ok(Item(None))
+        }
+
+        fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> { Ok(Item(None)) }
+        fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> { Ok(Item(None)) }
+        fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> { Ok(Item(None)) }
+        fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> { Ok(Item(None)) }
+        fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> { Ok(Item(None)) }
+        fn visit_unit<E>(self) -> Result<Self::Value, E> { Ok(Item(None)) }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for Item<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(ItemVisitor(PhantomData))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Vec<T>, D::Error> {
+        let items = Vec::<Item<T>>::deserialize(deserializer)?;
+        Ok(items.into_iter().filter_map(|item| item.0).collect())
+    }
+}
+
+macro_rules! lenient {
+    ($($name:ident: $type:ty = $default:expr;)*) => {$(
+        fn $name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<$type, D::Error> {
+            Ok(<$type>::deserialize(Value::deserialize(deserializer)?).unwrap_or_else(|_| $default))
+        }
+    )*};
+}
+
+lenient! {
+    deserialize_number: f32 = 0f32;
+    deserialize_bool: bool = false;
+    deserialize_use: bool = true;
+    deserialize_string: String = String::new();
+    deserialize_texture: Option<String> = None;
+    deserialize_font_size: f32 = default_font_size();
+    deserialize_line_height: f32 = default_line_height();
+    deserialize_font_weight: u16 = default_font_weight();
+    deserialize_writing_mode: WritingMode = WritingMode::default();
+    deserialize_direction: String = default_direction();
+    deserialize_line_break: String = default_line_break();
+    deserialize_hyphens: String = default_hyphens();
+    deserialize_line_breaking: String = default_line_breaking();
+    deserialize_text_align: String = default_text_align();
+    deserialize_resizing: String = default_resizing();
+    deserialize_align: String = default_align();
+    deserialize_font_style: String = default_font_style();
+    deserialize_color_string: String = default_color_string();
+    deserialize_decoration_style: String = default_decoration_style();
+    deserialize_decoration_skip_ink: String = default_decoration_skip_ink();
+    deserialize_linear: String = default_linear();
+    deserialize_vector: (f32, f32) = default_vector();
+}
+
+/// 宽高可以是数字或数字字符串
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Number(n) => n.as_f64().unwrap_or(200f64) as f32,
+        Value::String(s) => s.parse::<f32>().unwrap_or(200f32),
+        _ => 200f32
+    })
+}
+
+/// 字体族数组按 CSS 的写法以逗号连接，其他类型使用默认字体
+fn deserialize_font_family<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s,
+        Value::Array(items) => items.iter()
+            .map(|item| item.as_str())
+            .collect::<Option<Vec<&str>>>()
+            .map(|families| families.join(", "))
+            .unwrap_or_else(default_font_family),
+        _ => default_font_family()
+    })
+}
+
+/// 出现 width 即保留描边，非数字时宽度为 0
+fn deserialize_width<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    Ok(Some(Value::deserialize(deserializer)?.as_f64().unwrap_or(0f64) as f32))
+}
+
+/// 非数组时没有偏移，非数字的分量为 0
+fn deserialize_offset<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Array(items) => items.iter().map(|item| item.as_f64().unwrap_or(0f64) as f32).collect(),
+        _ => vec![]
+    })
+}
+
+/// 兼容 `"fontKerning": "none"` 的写法
+fn deserialize_font_kerning<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Bool(value) => value,
+        value => value.as_str() != Some("none")
+    })
+}
+
+fn default_size() -> f32 { 200f32 }
+fn default_text_align() -> String { "center".to_string() }
+fn default_resizing() -> String { "grow-vertically".to_string() }
+fn default_align() -> String { "middle".to_string() }
+fn default_direction() -> String { "ltr".to_string() }
+fn default_line_break() -> String { "normal".to_string() }
+fn default_hyphens() -> String { "manual".to_string() }
+fn default_line_breaking() -> String { "greedy".to_string() }
+fn default_line_height() -> f32 { 1.2f32 }
+fn default_font_family() -> String { "default".to_string() }
+fn default_font_size() -> f32 { 16f32 }
+fn default_font_weight() -> u16 { 400 }
+fn default_font_style() -> String { "normal".to_string() }
+fn default_color_string() -> String { "#000000".to_string() }
+fn default_decoration_style() -> String { "solid".to_string() }
+fn default_decoration_skip_ink() -> String { "none".to_string() }
+fn default_true() -> bool { true }
+fn default_color() -> (u8, u8, u8, f32) { (0u8, 0u8, 0u8, 1.0f32) }
+fn default_linear() -> String { "linear".to_string() }
+fn default_vector() -> (f32, f32) { (0.0, 1.0) }
+
+fn default_gradient() -> Gradient {
+    Gradient {
+        type_: default_linear(),
+        vector: default_vector(),
+        stop: vec![("0".to_string(), default_color())],
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "Stroke", deny_unknown_fields)]
+struct StrokeJson {
+    #[serde(default = "default_color", with = "color")]
+    #[schemars(with = "String")]
+    color: (u8, u8, u8, f32),
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_width")]
+    #[schemars(range(min = 0))]
+    width: Option<f32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not", deserialize_with = "deserialize_bool")]
+    hidden: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "Shadow", deny_unknown_fields)]
+struct ShadowJson {
+    #[serde(default = "default_color", with = "color")]
+    #[schemars(with = "String")]
+    color: (u8, u8, u8, f32),
+    #[serde(default, deserialize_with = "deserialize_offset")]
+    offset: Vec<f32>,
+    #[serde(default, deserialize_with = "deserialize_number")]
+    blur: f32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not", deserialize_with = "deserialize_bool")]
+    hidden: bool,
+}
+
+/// `advancedData` 的原始结构，隐藏或缺少宽度的描边在转换时去掉
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "AdvancedData", deny_unknown_fields)]
+struct ArtTextJson {
+    #[serde(default = "default_gradient")]
+    fill: Gradient,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_texture")]
+    texture: Option<String>,
+    #[serde(default)]
+    stroke: Vec<StrokeJson>,
+    #[serde(default)]
+    shadow: Vec<ShadowJson>,
+    #[serde(rename = "use", default = "default_true", deserialize_with = "deserialize_use")]
+    use_: bool,
+}
+
+impl JsonSchema for ArtTextOption {
+    fn schema_name() -> String {
+        ArtTextJson::schema_name()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        ArtTextJson::json_schema(gen)
+    }
+}
 
-        let paragraph = ParagraphData {
-            text_align,
-            resizing,
-            align,
-            paragraph_spacing,
-            paragraph_content,
-            art_text,
-            writing_mode,
+impl From<ArtTextJson> for ArtTextOption {
+    fn from(item: ArtTextJson) -> Self {
+        ArtTextOption {
+            fill: Some(item.fill),
+            texture: item.texture,
+            stroke: item.stroke.into_iter()
+                .filter(|stroke| !stroke.hidden)
+                .filter_map(|stroke| Some((stroke.color, stroke.width?)))
+                .collect(),
+            shadow: item.shadow.into_iter()
+                .filter(|shadow| !shadow.hidden)
+                .map(|shadow| {
+                    let offset = (shadow.offset.get(0).cloned().unwrap_or(0f32), shadow.offset.get(1).cloned().unwrap_or(0f32));
+                    (shadow.color, offset, shadow.blur)
+                })
+                .collect(),
+            use_: item.use_,
+        }
+    }
+}
+
+impl From<ArtTextOption> for ArtTextJson {
+    fn from(item: ArtTextOption) -> Self {
+        ArtTextJson {
+            fill: item.fill.unwrap_or_else(default_gradient),
+            texture: item.texture,
+            stroke: item.stroke.into_iter()
+                .map(|(color, width)| StrokeJson { color, width: Some(width), hidden: false })
+                .collect(),
+            shadow: item.shadow.into_iter()
+                .map(|(color, (x, y), blur)| ShadowJson { color, offset: vec![x, y], blur, hidden: false })
+                .collect(),
+            use_: item.use_,
+        }
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for Error {
+    fn from(error: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let path = match error.path().to_string().as_ref() {
+            "." => "$".to_string(),
+            path => format!("$.{}", path)
+        };
+        let inner = error.into_inner();
+        if inner.is_syntax() || inner.is_eof() || inner.is_io() {
+            return Error::Syntax(inner.to_string());
+        }
+        // 缺少字段时路径指向所在对象，补上字段名
+        let message = inner.to_string();
+        if message.starts_with("missing field `") {
+            if let Some(field) = message.split('`').nth(1) {
+                return Error::Missing { path: format!("{}.{}", path, field) };
+            }
+        }
+        let message = match message.find(" at line ") {
+            Some(index) => message[..index].to_string(),
+            None => message
         };
+        Error::Invalid { path, message }
+    }
+}
 
-        Some(TextData {
-            width,
-            height,
-            paragraph,
-            source: source.to_string(),
-        })
+impl TextData {
+    pub fn parse(source: &str) -> error::Result<TextData> {
+        let deserializer = &mut serde_json::Deserializer::from_str(source);
+        let mut text_data: TextData = serde_path_to_error::deserialize(deserializer)?;
+        text_data.source = source.to_string();
+        Ok(text_data)
+    }
+
+    /// 按默认值补全后序列化，可用于规范化文档
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
     }
 }
 
+
 //impl From<&Vec<f32>> for TextData {
 //    fn from(item: &Vec<f32>) -> Self {
 //
 //    }
-//}
\ No newline at end of file
+//}
+#[cfg(test)]
+mod test {
+    use super::{split_variation_settings, TextData, WritingMode};
+    use crate::error::Error;
+
+    #[test]
+    fn test_parse_error() {
+        assert!(matches!(TextData::parse("{"), Err(Error::Syntax(_))));
+        assert_eq!(TextData::parse("{}").unwrap_err(), Error::missing("$.paragraph"));
+        let source = r#"{"paragraph": {"contents": [{"blocks": []}, {}]}}"#;
+        assert_eq!(TextData::parse(source).unwrap_err(), Error::missing("$.paragraph.contents[1].blocks"));
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        // 类型不符与无法识别的颜色沿用旧版的默认值
+        let source = r##"{"width": null, "paragraph": {"textAlign": 1, "writingMode": "sideways", "advancedData": {"texture": 1, "stroke": [{"width": "2", "color": "red"}], "shadow": [{"color": 1, "offset": [1, "2"], "blur": null}]}, "contents": [{"lineHeight": null, "blocks": [{"text": "a", "fontSize": "16", "italic": 0, "fontWeight": "bold", "fontFamily": [1], "fill": 0}]}]}}"##;
+        let text_data = TextData::parse(source).unwrap();
+        assert_eq!(text_data.width, 200f32);
+        let paragraph = &text_data.paragraph;
+        assert_eq!(paragraph.text_align, "center");
+        assert!(matches!(paragraph.writing_mode, WritingMode::HorizontalTB));
+        assert_eq!(paragraph.paragraph_content[0].line_height, 1.2f32);
+        let block = &paragraph.paragraph_content[0].blocks[0];
+        assert_eq!((block.font_size, block.italic, block.font_weight), (16f32, false, 400));
+        assert_eq!((block.font_family.as_str(), block.fill.as_str()), ("default", "#000000"));
+        let art_text = paragraph.art_text.as_ref().unwrap();
+        assert_eq!(art_text.texture, None);
+        assert_eq!(art_text.stroke, vec![((0, 0, 0, 1f32), 0f32)]);
+        assert_eq!(art_text.shadow, vec![((0, 0, 0, 1f32), (1f32, 0f32), 0f32)]);
+
+        // 不是对象的段落与文字块跳过
+        let source = r##"{"paragraph": {"contents": [1, {"blocks": ["x", {"text": "a"}, null]}, []]}}"##;
+        let text_data = TextData::parse(source).unwrap();
+        let contents = &text_data.paragraph.paragraph_content;
+        assert_eq!(contents.len(), 1);
+        assert_eq!(contents[0].blocks.iter().map(|block| block.text.as_str()).collect::<Vec<&str>>(), vec!["a"]);
+        // 跳过的项不影响错误路径中的下标
+        let source = r##"{"paragraph": {"contents": [1, {"blocks": ["x"]}, {}]}}"##;
+        assert_eq!(TextData::parse(source).unwrap_err(), Error::missing("$.paragraph.contents[2].blocks"));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let source = r##"{"width": "300", "paragraph": {"textAlign": "left", "advancedData": {"fill": {"stop": {"1": "#ff0000", "0": "rgb(0,0,255)"}, "vector": [0, 1]}, "stroke": [{"color": "#00ff00", "width": 2}, {"width": 1, "hidden": true}]}, "contents": [{"blocks": [{"text": "ab", "fontKerning": "none"}]}]}}"##;
+        let text_data = TextData::parse(source).unwrap();
+        assert_eq!(text_data.width, 300f32);
+        assert_eq!(text_data.height, 200f32);
+        let block = &text_data.paragraph.paragraph_content[0].blocks[0];
+        assert_eq!((block.font_size, block.font_kerning, block.font_family.as_str()), (16f32, false, "default"));
+        let art_text = text_data.paragraph.art_text.as_ref().unwrap();
+        assert_eq!(art_text.stroke, vec![((0, 255, 0, 1f32), 2f32)]);
+        assert_eq!(art_text.fill.as_ref().unwrap().stop[0].0, "0");
+        // 色标按位置排序，不按原文顺序
+        let stop_source = r##"{"paragraph": {"advancedData": {"fill": {"stop": {"1": "#ff0000", "0.5": "#00ff00", "0": "#0000ff"}}}, "contents": []}}"##;
+        let stop_data = TextData::parse(stop_source).unwrap();
+        let offsets: Vec<&str> = stop_data.paragraph.art_text.as_ref().unwrap().fill.as_ref().unwrap().stop.iter().map(|(offset, _)| offset.as_str()).collect();
+        assert_eq!(offsets, vec!["0", "0.5", "1"]);
+
+        let json = text_data.to_json();
+        let normalized = TextData::parse(&json).unwrap();
+        assert_eq!(normalized.to_json(), json);
+        assert!(json.contains(r#""lineHeight":1.2"#));
+        assert!(json.contains(r#""writingMode":"horizontal-tb""#));
+    }
+
+    #[test]
+    fn test_variation_settings() {
+        let source = r#"{"paragraph": {"contents": [{"blocks": [{"text": "a", "fontWeight": 700, "fontVariationSettings": "'wdth' 80, \"wght\" 300"}]}]}}"#;
+        let text_data = TextData::parse(source).unwrap();
+        let variations = text_data.paragraph.paragraph_content[0].blocks[0].get_variations();
+        assert_eq!((variations.get(b"wght"), variations.get(b"wdth")), (Some(300f32), Some(80f32)));
+        assert_eq!(String::from(&variations), "wdth=80,wght=300");
+        assert_eq!(split_variation_settings("normal"), Some(vec![]));
+        assert_eq!(split_variation_settings("wght 300"), None);
+        assert_eq!(split_variation_settings("\"wgh\" 300"), None);
+    }
+
+    #[test]
+    fn test_font_family() {
+        let source = r#"{"paragraph": {"contents": [{"blocks": [{"text": "a", "fontFamily": ["Brand", "Source Han Sans"]}, {"text": "b", "fontFamily": "Brand, 'Noto Color Emoji'"}]}]}}"#;
+        let text_data = TextData::parse(source).unwrap();
+        let blocks = &text_data.paragraph.paragraph_content[0].blocks;
+        assert_eq!(blocks[0].font_family, "Brand, Source Han Sans");
+        assert_eq!(blocks[0].get_font_families(), vec!["Brand", "Source Han Sans"]);
+        assert_eq!(blocks[1].get_font_families(), vec!["Brand", "Noto Color Emoji"]);
+        let source = r#"{"paragraph": {"contents": [{"blocks": [{"text": "a", "fontFamily": [1]}]}]}}"#;
+        assert_eq!(TextData::parse(source).unwrap().paragraph.paragraph_content[0].blocks[0].font_family, "default");
+        assert!(TextData::parse_strict(source).is_err());
+    }
+}
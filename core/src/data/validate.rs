@@ -0,0 +1,371 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation};
+use serde_json::Value;
+use super::text_data::{split_color_string, split_variation_settings, TextData};
+use crate::error::Error;
+
+pub const TEXT_ALIGN: &[&str] = &["left", "center", "right", "justify", "start", "end"];
+pub const RESIZING: &[&str] = &["fixed", "grow-vertically", "grow-horizontally", "auto-width", "shrink-to-fit"];
+pub const ALIGN: &[&str] = &["top", "middle", "bottom"];
+pub const WRITING_MODE: &[&str] = &["horizontal-tb", "vertical-rl", "vertical-lr"];
+pub const DIRECTION: &[&str] = &["ltr", "rtl", "auto"];
+pub const LINE_BREAK: &[&str] = &["strict", "normal", "loose"];
+pub const HYPHENS: &[&str] = &["none", "manual", "auto"];
+pub const LINE_BREAKING: &[&str] = &["greedy", "optimal"];
+pub const DECORATION: &[&str] = &["underline", "line-through", "overline"];
+pub const DECORATION_STYLE: &[&str] = &["solid", "double", "dotted", "dashed", "wavy"];
+pub const DECORATION_SKIP_INK: &[&str] = &["none", "auto", "all"];
+pub const FONT_STYLE: &[&str] = &["normal", "italic", "oblique"];
+
+/// 只允许列出取值的字符串
+pub fn enum_schema(values: &[&str]) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values: Some(values.iter().map(|value| Value::String(value.to_string())).collect()),
+        ..Default::default()
+    }.into()
+}
+
+pub(crate) fn direction_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(DIRECTION) }
+pub(crate) fn line_break_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(LINE_BREAK) }
+pub(crate) fn hyphens_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(HYPHENS) }
+pub(crate) fn line_breaking_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(LINE_BREAKING) }
+pub(crate) fn text_align_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(TEXT_ALIGN) }
+pub(crate) fn resizing_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(RESIZING) }
+pub(crate) fn align_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(ALIGN) }
+pub(crate) fn decoration_style_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(DECORATION_STYLE) }
+pub(crate) fn decoration_skip_ink_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(DECORATION_SKIP_INK) }
+pub(crate) fn font_style_schema(_: &mut SchemaGenerator) -> Schema { enum_schema(FONT_STYLE) }
+
+/// 字符串或字符串数组
+pub(crate) fn font_family_schema(gen: &mut SchemaGenerator) -> Schema {
+    let mut schema = SchemaObject::default();
+    schema.subschemas().any_of = Some(vec![gen.subschema_for::<String>(), gen.subschema_for::<Vec<String>>()]);
+    schema.into()
+}
+
+/// 空字符串、none 或以空格分隔的装饰线
+pub(crate) fn decoration_schema(_: &mut SchemaGenerator) -> Schema {
+    let line = DECORATION.join("|");
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(format!("^(none|({0})( ({0}))*)?$", line)),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }.into()
+}
+
+/// 文字数据格式的 JSON Schema
+pub fn json_schema() -> String {
+    let schema = schemars::schema_for!(TextData);
+    serde_json::to_string_pretty(&schema).unwrap_or_default()
+}
+
+fn check_enum(errors: &mut Vec<Error>, path: &str, value: &str, values: &[&str]) {
+    if !values.contains(&value) {
+        errors.push(Error::invalid(path, &format!("应为 {} 之一", values.join(" | "))));
+    }
+}
+
+fn check_min(errors: &mut Vec<Error>, path: &str, value: f32, min: f32) {
+    if value.is_nan() || value < min {
+        errors.push(Error::invalid(path, &format!("不能小于 {}", min)));
+    }
+}
+
+fn check_alpha(errors: &mut Vec<Error>, path: &str, color: &(u8, u8, u8, f32)) {
+    if !(0f32..=1f32).contains(&color.3) {
+        errors.push(Error::invalid(path, "透明度应在 0 到 1 之间"));
+    }
+}
+
+fn check_color(errors: &mut Vec<Error>, path: &str, value: &str) {
+    match split_color_string(value) {
+        Some(color) => check_alpha(errors, path, &color),
+        None => errors.push(Error::invalid(path, &format!("无法识别的颜色 {:?}", value)))
+    }
+}
+
+/// serde_ignored 的路径转为 `$.a.b[0]` 形式
+fn ignored_path(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => "$".to_string(),
+        serde_ignored::Path::Seq { parent, index } => format!("{}[{}]", ignored_path(parent), index),
+        serde_ignored::Path::Map { parent, key } => format!("{}.{}", ignored_path(parent), key),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => ignored_path(parent),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Number,
+    /// 不小于 0 的数字
+    Size,
+    Bool,
+    String,
+    Color,
+}
+
+const PARAGRAPH_KINDS: &[(&str, Kind)] = &[
+    ("writingMode", Kind::String), ("direction", Kind::String), ("lineBreak", Kind::String),
+    ("hyphens", Kind::String), ("lineBreaking", Kind::String), ("textAlign", Kind::String),
+    ("resizing", Kind::String), ("align", Kind::String), ("paragraphSpacing", Kind::Number),
+];
+const CONTENT_KINDS: &[(&str, Kind)] = &[("lineHeight", Kind::Number), ("paragraphIndentation", Kind::Number)];
+const BLOCK_KINDS: &[(&str, Kind)] = &[
+    ("text", Kind::String), ("fontSize", Kind::Number), ("letterSpacing", Kind::Number),
+    ("fill", Kind::String), ("italic", Kind::Bool), ("fontWeight", Kind::Number),
+    ("fontStyle", Kind::String), ("fontVariationSettings", Kind::String), ("stroke", Kind::String),
+    ("strokeWidth", Kind::Number), ("decoration", Kind::String), ("decorationStyle", Kind::String),
+    ("decorationColor", Kind::String), ("decorationSkipInk", Kind::String), ("fontKerning", Kind::Bool),
+    ("lang", Kind::String),
+];
+const ART_TEXT_KINDS: &[(&str, Kind)] = &[("texture", Kind::String), ("use", Kind::Bool)];
+const FILL_KINDS: &[(&str, Kind)] = &[("type", Kind::String)];
+const STROKE_KINDS: &[(&str, Kind)] = &[("color", Kind::Color), ("width", Kind::Size), ("hidden", Kind::Bool)];
+const SHADOW_KINDS: &[(&str, Kind)] = &[("color", Kind::Color), ("blur", Kind::Size), ("hidden", Kind::Bool)];
+
+fn check_kind(errors: &mut Vec<Error>, path: &str, value: &Value, kind: Kind) {
+    match kind {
+        Kind::Number if !value.is_number() => errors.push(Error::invalid(path, "应为数字")),
+        Kind::Size => match value.as_f64() {
+            Some(size) => check_min(errors, path, size as f32, 0f32),
+            None => errors.push(Error::invalid(path, "应为数字")),
+        },
+        Kind::Bool if !value.is_boolean() => errors.push(Error::invalid(path, "应为布尔值")),
+        Kind::String if !value.is_string() => errors.push(Error::invalid(path, "应为字符串")),
+        Kind::Color => match value.as_str() {
+            Some(color) => check_color(errors, path, color),
+            None => errors.push(Error::invalid(path, &format!("无法识别的颜色 {}", value))),
+        },
+        _ => {}
+    }
+}
+
+fn check_kinds(errors: &mut Vec<Error>, path: &str, value: &Value, kinds: &[(&str, Kind)]) {
+    for (key, kind) in kinds.iter() {
+        if let Some(item) = value.get(key) {
+            check_kind(errors, &format!("{}.{}", path, key), item, *kind);
+        }
+    }
+}
+
+fn check_numbers(errors: &mut Vec<Error>, path: &str, value: Option<&Value>, len: usize) {
+    let value = match value {
+        Some(value) => value,
+        None => return
+    };
+    match value.as_array() {
+        Some(items) if items.len() <= len => {
+            for (i, item) in items.iter().enumerate() {
+                check_kind(errors, &format!("{}[{}]", path, i), item, Kind::Number);
+            }
+        }
+        _ => errors.push(Error::invalid(path, &format!("应为不超过 {} 个数字的数组", len)))
+    }
+}
+
+/// 宽松模式下类型不符的取值使用默认值，严格模式在原始 JSON 上报告
+///
+/// 隐藏的描边与阴影在解析时会去掉，advancedData 的取值也在这里检查，路径中的下标与原文一致
+fn check_types(errors: &mut Vec<Error>, value: &Value) {
+    check_kinds(errors, "$", value, &[("width", Kind::Number), ("height", Kind::Number)]);
+    let paragraph = match value.get("paragraph") {
+        Some(paragraph) => paragraph,
+        None => return
+    };
+    check_kinds(errors, "$.paragraph", paragraph, PARAGRAPH_KINDS);
+    if let Some(writing_mode) = paragraph.get("writingMode").and_then(|v| v.as_str()) {
+        check_enum(errors, "$.paragraph.writingMode", writing_mode, WRITING_MODE);
+    }
+
+    let contents = paragraph.get("contents").and_then(|v| v.as_array());
+    for (i, content) in contents.into_iter().flatten().enumerate() {
+        let path = format!("$.paragraph.contents[{}]", i);
+        if !content.is_object() {
+            errors.push(Error::invalid(&path, "应为对象"));
+            continue;
+        }
+        check_kinds(errors, &path, content, CONTENT_KINDS);
+        let blocks = content.get("blocks").and_then(|v| v.as_array());
+        for (j, block) in blocks.into_iter().flatten().enumerate() {
+            let path = format!("{}.blocks[{}]", path, j);
+            if !block.is_object() {
+                errors.push(Error::invalid(&path, "应为对象"));
+                continue;
+            }
+            check_kinds(errors, &path, block, BLOCK_KINDS);
+            match block.get("fontFamily") {
+                Some(Value::String(_)) | None => {}
+                Some(Value::Array(items)) if items.iter().all(|item| item.is_string()) => {}
+                Some(_) => errors.push(Error::invalid(&format!("{}.fontFamily", path), "应为字符串或字符串数组")),
+            }
+        }
+    }
+
+    let art_text = match paragraph.get("advancedData") {
+        Some(art_text) => art_text,
+        None => return
+    };
+    let path = "$.paragraph.advancedData";
+    check_kinds(errors, path, art_text, ART_TEXT_KINDS);
+    if let Some(fill) = art_text.get("fill") {
+        check_kinds(errors, &format!("{}.fill", path), fill, FILL_KINDS);
+        check_numbers(errors, &format!("{}.fill.vector", path), fill.get("vector"), 2);
+        let stop = fill.get("stop").and_then(|v| v.as_object());
+        for (key, color) in stop.into_iter().flatten() {
+            check_kind(errors, &format!("{}.fill.stop.{}", path, key), color, Kind::Color);
+        }
+    }
+    let stroke = art_text.get("stroke").and_then(|v| v.as_array());
+    for (i, item) in stroke.into_iter().flatten().enumerate() {
+        check_kinds(errors, &format!("{}.stroke[{}]", path, i), item, STROKE_KINDS);
+    }
+    let shadow = art_text.get("shadow").and_then(|v| v.as_array());
+    for (i, item) in shadow.into_iter().flatten().enumerate() {
+        let path = format!("{}.shadow[{}]", path, i);
+        check_kinds(errors, &path, item, SHADOW_KINDS);
+        check_numbers(errors, &format!("{}.offset", path), item.get("offset"), 2);
+    }
+}
+
+impl TextData {
+    /// 检查枚举取值与数值范围，advancedData 由 `parse_strict` 按原始 JSON 检查
+    pub fn validate(&self) -> Vec<Error> {
+        let mut errors = Vec::<Error>::new();
+        let errors_ref = &mut errors;
+        check_min(errors_ref, "$.width", self.width, 0f32);
+        check_min(errors_ref, "$.height", self.height, 0f32);
+
+        let paragraph = &self.paragraph;
+        check_enum(errors_ref, "$.paragraph.direction", &paragraph.direction, DIRECTION);
+        check_enum(errors_ref, "$.paragraph.lineBreak", &paragraph.line_break, LINE_BREAK);
+        check_enum(errors_ref, "$.paragraph.hyphens", &paragraph.hyphens, HYPHENS);
+        check_enum(errors_ref, "$.paragraph.lineBreaking", &paragraph.line_breaking, LINE_BREAKING);
+        check_enum(errors_ref, "$.paragraph.textAlign", &paragraph.text_align, TEXT_ALIGN);
+        check_enum(errors_ref, "$.paragraph.resizing", &paragraph.resizing, RESIZING);
+        check_enum(errors_ref, "$.paragraph.align", &paragraph.align, ALIGN);
+
+        for (i, content) in paragraph.paragraph_content.iter().enumerate() {
+            let path = format!("$.paragraph.contents[{}]", i);
+            check_min(errors_ref, &format!("{}.lineHeight", path), content.line_height, 0f32);
+            for (j, block) in content.blocks.iter().enumerate() {
+                let path = format!("{}.blocks[{}]", path, j);
+                check_min(errors_ref, &format!("{}.fontSize", path), block.font_size, 1f32);
+                check_min(errors_ref, &format!("{}.strokeWidth", path), block.stroke_width, 0f32);
+                if !(1..=1000).contains(&block.font_weight) {
+                    errors_ref.push(Error::invalid(&format!("{}.fontWeight", path), "应在 1 到 1000 之间"));
+                }
+                check_enum(errors_ref, &format!("{}.fontStyle", path), &block.font_style, FONT_STYLE);
+                if split_variation_settings(&block.font_variation_settings).is_none() {
+                    errors_ref.push(Error::invalid(&format!("{}.fontVariationSettings", path), "应为 \"wght\" 700, \"wdth\" 80 的形式"));
+                }
+                check_color(errors_ref, &format!("{}.fill", path), &block.fill);
+                check_color(errors_ref, &format!("{}.stroke", path), &block.stroke);
+                if block.decoration != "none" {
+                    for line in block.decoration.split_whitespace() {
+                        check_enum(errors_ref, &format!("{}.decoration", path), line, DECORATION);
+                    }
+                }
+                check_enum(errors_ref, &format!("{}.decorationStyle", path), &block.decoration_style, DECORATION_STYLE);
+                check_enum(errors_ref, &format!("{}.decorationSkipInk", path), &block.decoration_skip_ink, DECORATION_SKIP_INK);
+                if !block.decoration_color.is_empty() {
+                    check_color(errors_ref, &format!("{}.decorationColor", path), &block.decoration_color);
+                }
+            }
+        }
+        errors
+    }
+
+    /// 严格模式解析，一次返回所有未知字段、类型错误与越界取值
+    pub fn parse_strict(source: &str) -> std::result::Result<TextData, Vec<Error>> {
+        let value: Value = serde_json::from_str(source).map_err(|e| vec![Error::Syntax(e.to_string())])?;
+        let mut errors = Vec::<Error>::new();
+        check_types(&mut errors, &value);
+
+        let mut unknown = Vec::<Error>::new();
+        let mut callback = |path: serde_ignored::Path| unknown.push(Error::Unknown { path: ignored_path(&path) });
+        let result: std::result::Result<TextData, _> = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(value, &mut callback));
+        errors.append(&mut unknown);
+        let mut text_data = match result {
+            Ok(text_data) => text_data,
+            Err(e) => {
+                errors.push(e.into());
+                return Err(errors);
+            }
+        };
+
+        errors.append(&mut text_data.validate());
+        if !errors.is_empty() { return Err(errors); }
+        text_data.source = source.to_string();
+        Ok(text_data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::json_schema;
+    use crate::data::text_data::TextData;
+    use crate::error::Error;
+
+    #[test]
+    fn test_parse_strict() {
+        let source = r##"{"width": "300", "paragraph": {"align": "center", "contents": [{"blocks": [{"text": "a", "fontsize": 20, "fontSize": 0}]}]}}"##;
+        assert!(TextData::parse(source).is_ok());
+        let errors = TextData::parse_strict(source).unwrap_err();
+        assert!(errors.contains(&Error::Unknown { path: "$.paragraph.contents[0].blocks[0].fontsize".to_string() }));
+        assert!(errors.iter().any(|e| e.path() == Some("$.width")));
+        assert!(errors.iter().any(|e| e.path() == Some("$.paragraph.align")));
+        let font_size = errors.iter().find(|e| e.path() == Some("$.paragraph.contents[0].blocks[0].fontSize")).unwrap();
+        assert_eq!(font_size.pointer().unwrap(), "/paragraph/contents/0/blocks/0/fontSize");
+        assert_eq!(errors.len(), 4);
+
+        let source = r##"{"paragraph": {"contents": [{"blocks": [{"text": "a"}]}]}}"##;
+        assert!(TextData::parse_strict(source).is_ok());
+
+        // 宽松模式跳过不是对象的项，严格模式报错
+        let source = r##"{"paragraph": {"contents": [1, {"blocks": ["x", {"text": "a"}]}]}}"##;
+        assert!(TextData::parse(source).is_ok());
+        let paths: Vec<String> = TextData::parse_strict(source).unwrap_err().iter().filter_map(|e| e.path().map(|path| path.to_string())).collect();
+        assert_eq!(paths, vec!["$.paragraph.contents[0]", "$.paragraph.contents[1].blocks[0]"]);
+
+        let source = r##"{"paragraph": {"writingMode": "sideways", "advancedData": {"stroke": [{"width": "2", "color": "#zz"}]}, "contents": [{"lineHeight": null, "blocks": [{"fontSize": "16", "italic": 0}]}]}}"##;
+        let paths: Vec<String> = TextData::parse_strict(source).unwrap_err().iter().filter_map(|e| e.path().map(|path| path.to_string())).collect();
+        assert_eq!(paths, vec![
+            "$.paragraph.writingMode",
+            "$.paragraph.contents[0].lineHeight",
+            "$.paragraph.contents[0].blocks[0].fontSize",
+            "$.paragraph.contents[0].blocks[0].italic",
+            "$.paragraph.advancedData.stroke[0].color",
+            "$.paragraph.advancedData.stroke[0].width",
+        ]);
+    }
+
+    #[test]
+    fn test_art_text_path() {
+        // 隐藏的项在解析时去掉，报告的下标仍然对应原文
+        let source = r##"{"paragraph": {"advancedData": {"stroke": [{"width": 1, "hidden": true}, {"width": -1, "color": "rgba(0,0,0,2)"}], "shadow": [{"hidden": true}, {"blur": -1}]}, "contents": []}}"##;
+        assert_eq!(TextData::parse(source).unwrap().paragraph.art_text.unwrap().stroke.len(), 1);
+        let paths: Vec<String> = TextData::parse_strict(source).unwrap_err().iter().filter_map(|e| e.path().map(|path| path.to_string())).collect();
+        assert_eq!(paths, vec![
+            "$.paragraph.advancedData.stroke[1].color",
+            "$.paragraph.advancedData.stroke[1].width",
+            "$.paragraph.advancedData.shadow[1].blur",
+        ]);
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&json_schema()).unwrap();
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(schema["required"], serde_json::json!(["paragraph"]));
+        let block = &schema["definitions"]["TextBlock"]["properties"];
+        assert_eq!(block["fontSize"]["minimum"], 1.0);
+        assert!(schema["definitions"]["ParagraphData"]["properties"]["align"]["enum"].is_array());
+        assert_eq!(block["fontFamily"]["anyOf"].as_array().map(|any_of| any_of.len()), Some(2));
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+/// 解析与排版过程中的错误，`path` 为出错字段的 JSON 路径，如 `$.paragraph.contents[0].blocks`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// 不是合法的 JSON
+    Syntax(String),
+    /// 缺少必需的字段
+    Missing { path: String },
+    /// 字段类型或取值不正确
+    Invalid { path: String, message: String },
+    /// 严格模式下出现未定义的字段
+    Unknown { path: String },
+    /// 找不到字形，通常是字体未加载或字形未预先缓存
+    Glyph { path: String, font_family: String, char: char },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn missing(path: &str) -> Self {
+        Error::Missing { path: path.to_string() }
+    }
+
+    pub fn invalid(path: &str, message: &str) -> Self {
+        Error::Invalid { path: path.to_string(), message: message.to_string() }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::Syntax(_) => None,
+            Error::Missing { path } | Error::Invalid { path, .. } | Error::Unknown { path } | Error::Glyph { path, .. } => Some(path)
+        }
+    }
+
+    /// 路径转为 RFC 6901 JSON Pointer，如 `/paragraph/contents/0/blocks`
+    pub fn pointer(&self) -> Option<String> {
+        let path = self.path()?.trim_start_matches('$');
+        let mut pointer = String::new();
+        let mut token = String::new();
+        fn push(token: &mut String, pointer: &mut String) {
+            pointer.push('/');
+            pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+            token.clear();
+        }
+        let mut started = false;
+        for c in path.chars() {
+            match c {
+                '.' | '[' => {
+                    if started { push(&mut token, &mut pointer); }
+                    started = true;
+                }
+                ']' => {}
+                c => token.push(c)
+            }
+        }
+        if started { push(&mut token, &mut pointer); }
+        Some(pointer)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax(message) => write!(f, "JSON 格式错误: {}", message),
+            Error::Missing { path } => write!(f, "缺少字段 {}", path),
+            Error::Invalid { path, message } => write!(f, "字段 {} 不正确: {}", path, message),
+            Error::Unknown { path } => write!(f, "未知字段 {}", path),
+            Error::Glyph { path, font_family, char } => write!(f, "{} 的字体 {:?} 中找不到字符 {:?}", path, font_family, char),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,9 +1,11 @@
 extern crate svgtypes;
 extern crate json;
-extern crate regex;
 #[macro_use]
 extern crate lazy_static;
 
+pub mod error;
 pub mod data;
 pub mod typesetting;
-pub mod open_type_like;
\ No newline at end of file
+pub mod open_type_like;
+
+pub use error::Error;
@@ -0,0 +1,66 @@
+use unicode_bidi::{BidiInfo, Level};
+
+/// 按 UAX #9 计算段落中每个字节的嵌入层级，返回 (层级, 段落层级)
+pub fn get_levels(text: &str, direction: &str) -> Vec<(u8, u8)> {
+    let default_level = match direction {
+        "rtl" => Some(Level::rtl()),
+        "auto" => None,
+        _ => Some(Level::ltr()),
+    };
+    let info = BidiInfo::new(text, default_level);
+    let mut levels = Vec::<(u8, u8)>::with_capacity(text.len());
+    for paragraph in info.paragraphs.iter() {
+        let paragraph_level = paragraph.level.number();
+        for index in paragraph.range.clone() {
+            levels.push((info.levels[index].number(), paragraph_level));
+        }
+    }
+    levels
+}
+
+/// 按 L2 规则将一行的层级转换为视觉顺序，返回原下标
+pub fn reorder(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let max_level = levels.iter().cloned().max().unwrap_or(0);
+    let min_odd_level = levels.iter().cloned().filter(|level| level % 2 == 1).min();
+    let min_odd_level = match min_odd_level {
+        Some(level) => level,
+        None => return order
+    };
+    let mut level = max_level;
+    while level >= min_odd_level {
+        let mut index = 0usize;
+        while index < order.len() {
+            if levels[order[index]] < level {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < order.len() && levels[order[index]] >= level {
+                index += 1;
+            }
+            order[start..index].reverse();
+        }
+        level -= 1;
+    }
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reorder() {
+        let text = "abc אבג def";
+        let levels: Vec<u8> = get_levels(text, "ltr").iter().map(|(level, _)| *level).collect();
+        let chars: Vec<u8> = text.char_indices().map(|(index, _)| levels[index]).collect();
+        let order = reorder(&chars);
+        let visual: String = order.iter().map(|index| text.chars().nth(*index).unwrap()).collect();
+        assert_eq!(visual, "abc גבא def");
+
+        let levels = get_levels("אבג abc", "auto");
+        assert_eq!(levels[0].1, 1);
+        assert_eq!(reorder(&vec![1, 1, 2, 2, 1]), vec![4, 2, 3, 1, 0]);
+    }
+}
@@ -1,4 +1,6 @@
 use super::path::PathData;
+use super::glyph::{ColorGlyph, ColorLayer, GradientExtend, Glyph, LayerColor, LayerPaint};
+use super::decoration::get_decoration_commands;
 use super::super::data::text_data::{TextBlock, TextBlockDetail};
 
 use super::transform::Transform;
@@ -6,7 +8,10 @@ use super::transform::Transform;
 use std::f64::consts::PI;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
-use crate::data::text_data::WritingMode;
+
+/// 合成粗体时轮廓每侧扩展的宽度，相对字号
+const SYNTHETIC_BOLD: f32 = 1f32 / 48f32;
+const SYNTHETIC_BOLD_SUFFIX: &str = "#synthetic-bold";
 
 macro_rules! join_str {
     ( $( $x:expr ),* ) => {
@@ -23,12 +28,75 @@ macro_rules! join_str {
 
 #[derive(Debug, Clone)]
 pub enum CommandSegment {
+    /// 字体名、字形序号与字号，对应 `get_path_commands` 中以 (字体名, 字形序号) 为键的轮廓
     Use(String, u32, f64),
     Path(PathData),
     Transform(Transform, bool),
     Fill(String),
     Stroke(String, f64),
     Clip,
+    /// 彩色字形，坐标与转换后的 `Path` 相同
+    Color(ColorCommand),
+}
+
+#[derive(Debug, Clone)]
+pub enum ColorCommand {
+    /// 按顺序填充的图层，颜色中不再有 `Foreground`
+    Layers(Vec<ColorLayer>),
+    /// 图片格式、数据及左上角坐标与宽高
+    Image(String, Vec<u8>, (f32, f32, f32, f32)),
+}
+
+fn get_color_str(color: &LayerColor) -> String {
+    match color {
+        LayerColor::Rgba(r, g, b, a) => format!("rgba({},{},{},{})", r, g, b, *a as f32 / 255f32),
+        LayerColor::Foreground(a) => format!("rgba(0,0,0,{})", *a as f32 / 255f32),
+    }
+}
+
+fn get_paint_str(paint: &LayerPaint) -> String {
+    let get_stops_str = |stops: &Vec<(f32, LayerColor)>| {
+        let stops: Vec<String> = stops.iter().map(|(offset, color)| format!("[{:?}, {:?}]", offset, get_color_str(color))).collect();
+        stops.join(", ")
+    };
+    let get_extend_str = |extend: &GradientExtend| match extend {
+        GradientExtend::Pad => "pad",
+        GradientExtend::Repeat => "repeat",
+        GradientExtend::Reflect => "reflect",
+    };
+    match paint {
+        LayerPaint::Solid(color) => format!(r#"{{"type": "solid", "color": {:?}}}"#, get_color_str(color)),
+        LayerPaint::LinearGradient { start, end, stops, extend, transform } => {
+            format!(r#"{{"type": "linear", "start": [{:?}, {:?}], "end": [{:?}, {:?}], "stops": [{}], "extend": {:?}, "transform": {}}}"#,
+                    start.0, start.1, end.0, end.1, get_stops_str(stops), get_extend_str(extend), get_transform_str(transform))
+        }
+        LayerPaint::RadialGradient { start, start_radius, end, end_radius, stops, extend, transform } => {
+            format!(r#"{{"type": "radial", "start": [{:?}, {:?}], "startRadius": {:?}, "end": [{:?}, {:?}], "endRadius": {:?}, "stops": [{}], "extend": {:?}, "transform": {}}}"#,
+                    start.0, start.1, start_radius, end.0, end.1, end_radius, get_stops_str(stops), get_extend_str(extend), get_transform_str(transform))
+        }
+    }
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(TABLE[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// 十六进制颜色，无法解析时为黑色
+fn parse_hex_color(color: &str) -> (u8, u8, u8) {
+    let color = i64::from_str_radix(color.trim_start_matches("#"), 16u32).unwrap_or(0i64);
+    ((color >> 16) as u8, (color >> 8) as u8, color as u8)
 }
 
 fn get_transform_str(t: &Transform) -> String {
@@ -57,6 +125,16 @@ impl From<&CommandSegment> for String {
             CommandSegment::Transform(ref a, ref _b) => {
                 join_str!("{\"type\": \"transform\", \"value\": ", get_transform_str(a), "}")
             }
+            CommandSegment::Color(ColorCommand::Layers(ref layers)) => {
+                let layers: Vec<String> = layers.iter().map(|layer| {
+                    format!(r#"{{"path": {:?}, "paint": {}}}"#, String::from(&layer.path), get_paint_str(&layer.paint))
+                }).collect();
+                format!(r#"{{"type": "color", "layers": [{}]}}"#, layers.join(", "))
+            }
+            CommandSegment::Color(ColorCommand::Image(ref format, ref data, (x, y, width, height))) => {
+                format!(r#"{{"type": "image", "format": {:?}, "x": {:?}, "y": {:?}, "width": {:?}, "height": {:?}, "data": {:?}}}"#,
+                        format, x, y, width, height, encode_base64(data))
+            }
         }
     }
 }
@@ -86,6 +164,19 @@ impl From<&CommandSegment> for Vec<f32> {
                 let color = i64::from_str_radix(without_prefix, 16u32).unwrap_or(0i64) as i64;
                 vec![3f32, color as f32]
             }
+            // 紧凑格式只有纯色填充，渐变取第一个颜色，不输出图片
+            CommandSegment::Color(ColorCommand::Layers(ref layers)) => {
+                let mut packed = vec![];
+                for layer in layers.iter() {
+                    let (r, g, b) = match layer.paint.get_main_color() {
+                        Some(LayerColor::Rgba(r, g, b, _)) => (r, g, b),
+                        _ => (0, 0, 0)
+                    };
+                    packed.extend(Vec::<f32>::from(&CommandSegment::Path(layer.path.clone())));
+                    packed.extend(vec![3f32, ((r as i64) << 16 | (g as i64) << 8 | b as i64) as f32]);
+                }
+                packed
+            }
             _ => vec![]
         }
     }
@@ -105,36 +196,110 @@ impl<'a> CommandList<'a> {
         CommandList(d)
     }
 
-    fn get_transform(block: &TextBlock, detail: &TextBlockDetail) -> CommandSegment {
+    fn get_transform(detail: &TextBlockDetail, offset: (f32, f32)) -> CommandSegment {
         let a = 1f32;
         let b = 0f32;
         let d = 1f32;
 
-        let x = detail.position.0 as f32;
-        let y = detail.position.1 as f32;
+        let x = detail.position.0 as f32 + detail.glyph_offset + offset.0;
+        let y = detail.position.1 as f32 + offset.1;
         let line_height = detail.line_height as f32;
 
-        let c = if block.italic { (-(PI * 15f64 / 180f64).sin()) as f32 } else { 0f32 };
+        let c = if detail.synthetic_oblique { (-(PI * 15f64 / 180f64).sin()) as f32 } else { 0f32 };
         let e = x - line_height * c;
         let f = y;
         CommandSegment::Transform(Transform { a, b, c, d, e, f }, false)
     }
 
+    /// 字形簇中的字形及其相对字框的偏移
+    fn get_glyphs<'b>(block: &TextBlock, detail: &'b TextBlockDetail) -> Vec<(&'b Glyph, (f32, f32))> {
+        let mut glyphs = Vec::<(&Glyph, (f32, f32))>::new();
+        if detail.cluster.is_empty() {
+            glyphs.push((detail.glyph, (0f32, 0f32)));
+        }
+        let mut pen = 0f32;
+        for (glyph, shaped) in detail.cluster.iter() {
+            let scale = block.font_size as f32 / glyph.units_per_em as f32;
+            glyphs.push((glyph, (pen + shaped.x_offset as f32 * scale, -shaped.y_offset as f32 * scale)));
+            pen += shaped.x_advance as f32 * scale;
+        }
+        glyphs
+    }
+
+    /// 字形轮廓，合成粗体时向外扩展
+    fn get_glyph_path(detail: &TextBlockDetail, glyph: &Glyph, font_size: f32) -> PathData {
+        let mut path = glyph.get_path(0f32, 0f32, font_size, &detail.writing_mode);
+        if detail.synthetic_bold {
+            path.embolden(font_size * SYNTHETIC_BOLD);
+        }
+        path
+    }
+
+    /// 合成粗体及可变字体不同实例的轮廓与原字形分开缓存
+    fn get_path_key(detail: &TextBlockDetail, glyph: &Glyph) -> (String, u32) {
+        let mut font_name = glyph.font_name.to_string();
+        if !glyph.variations.is_empty() {
+            font_name = format!("{}#{}", font_name, String::from(&glyph.variations));
+        }
+        if detail.synthetic_bold {
+            font_name.push_str(SYNTHETIC_BOLD_SUFFIX);
+        }
+        (font_name, glyph.glyph_id)
+    }
+
+    /// 排版后字形轮廓的实际位置，与输出的绘制指令一致
+    pub fn get_glyph_outlines(block: &TextBlock, detail: &TextBlockDetail) -> Vec<PathData> {
+        Self::get_glyphs(block, detail).into_iter().map(|(glyph, offset)| {
+            let mut path = Self::get_glyph_path(detail, glyph, block.font_size);
+            if let CommandSegment::Transform(transform, _) = Self::get_transform(detail, offset) {
+                path.transform(transform);
+            }
+            path
+        }).collect()
+    }
+
     fn get_path_commands(&self) -> HashMap<(String, u32), PathData> {
         let mut paths = HashMap::<(String, u32), PathData>::new();
         for item in self.iter() {
-            let (b, d) = item;
-            let path = d.glyph.get_path(0f32, 0f32, 100f32, &d.writing_mode);
-            let font_family = &b.font_family;
-            let mut chars = b.text.chars();
-            while let Some(text) = chars.next() {
-                let unicode = text as u32;
-                &paths.insert((font_family.to_string(), unicode), path.clone());
+            let (_b, d) = item;
+            if d.cluster.is_empty() {
+                paths.insert(Self::get_path_key(d, d.glyph), Self::get_glyph_path(d, d.glyph, 100f32));
+            }
+            for (glyph, _) in d.cluster.iter() {
+                paths.insert(Self::get_path_key(d, glyph), Self::get_glyph_path(d, glyph, 100f32));
             }
         }
         paths
     }
 
+    /// 彩色字形的图层或位图，坐标与 `get_glyph_path` 一致，文字颜色的图层使用文字的填充色
+    fn get_color_command(block: &TextBlock, detail: &TextBlockDetail, glyph: &Glyph) -> Option<CommandSegment> {
+        let transform = glyph.get_transform(0f32, 0f32, block.font_size, &detail.writing_mode);
+        let command = match glyph.color.as_ref()? {
+            ColorGlyph::Layers(layers) => {
+                let (r, g, b) = parse_hex_color(&block.fill);
+                let layers = layers.iter().map(|layer| {
+                    let mut path = layer.path.clone();
+                    path.transform(transform);
+                    let mut paint = layer.paint.map_color(|color| match color {
+                        LayerColor::Foreground(a) => LayerColor::Rgba(r, g, b, a),
+                        color => color
+                    });
+                    paint.prepend_transform(&transform);
+                    ColorLayer { path, paint }
+                }).collect();
+                ColorCommand::Layers(layers)
+            }
+            ColorGlyph::Bitmap(bitmap) => {
+                let (x1, y1) = transform.apply(bitmap.x, bitmap.y);
+                let (x2, y2) = transform.apply(bitmap.x + bitmap.width, bitmap.y + bitmap.height);
+                let rect = (x1.min(x2), y1.min(y2), (x2 - x1).abs(), (y2 - y1).abs());
+                ColorCommand::Image(bitmap.format.clone(), bitmap.data.clone(), rect)
+            }
+        };
+        Some(CommandSegment::Color(command))
+    }
+
     fn get_stroke(block: &TextBlock) -> CommandSegment {
         let font_size = block.font_size;
         let fill = block.fill.clone();
@@ -144,80 +309,29 @@ impl<'a> CommandList<'a> {
         CommandSegment::Stroke(fill, stroke_width)
     }
 
-    fn get_decoration(block: &TextBlock, detail: &TextBlockDetail) -> Vec<CommandSegment> {
-        let decoration = &block.decoration;
-        let font_size = block.font_size;
-        let (x, mut y) = detail.position;
-        let b_width = detail.b_width;
-        let writing_mode = &detail.writing_mode;
-        let line_width = 0.04f32 * font_size as f32;
-        y += line_width;
-        let transform = CommandSegment::Transform(Default::default(), true);
-        let mut path_data = PathData::new();
-
-        match writing_mode {
-            &WritingMode::HorizontalTB => {
-                match decoration.as_ref() {
-                    "line-through" => {}
-                    "overline" => {}
-                    "underline" => {
-                        path_data.move_to(x, y);
-                        path_data.line_to(x + b_width, y);
-                        path_data.line_to(x + b_width, y + line_width);
-                        path_data.line_to(x, y + line_width);
-                        path_data.line_to(x, y);
-                        path_data.close();
-                    }
-                    _ => {}
-                }
-            }
-            _ => {
-                match decoration.as_ref() {
-                    "line-through" => {}
-                    "overline" => {}
-                    "underline" => {
-                        path_data.move_to(x, y);
-                        path_data.line_to(x, y + b_width);
-                        path_data.line_to(x + line_width, y + b_width);
-                        path_data.line_to(x + line_width, y);
-                        path_data.line_to(x, y);
-                        path_data.close();
-                    }
-                    _ => {}
-                }
-            }
-        }
-
-        let mut result = Vec::<CommandSegment>::new();
-        if !path_data.is_empty() {
-            result.push(transform);
-            result.push(CommandSegment::Path(path_data));
-            result.push(CommandSegment::Fill(block.fill.clone()));
-        }
-        result
-    }
-
     pub fn get_commands(&self) -> (HashMap<(String, u32), PathData>, Vec<CommandSegment>) {
         let paths = self.get_path_commands();
-        let mut commands = Vec::<CommandSegment>::new();
+        let (below, above) = get_decoration_commands(self.0);
+        let mut commands = below;
         for (b, d) in self.iter() {
-            let mut chars = b.text.chars();
-            while let Some(text) = chars.next() {
-                let transform = Self::get_transform(b, d);
-//                transform
-                let path = CommandSegment::Use(b.font_family.to_string(), text as u32, b.font_size as f64);
+            for (glyph, offset) in Self::get_glyphs(b, d) {
+                let transform = Self::get_transform(d, offset);
+                if let Some(color) = Self::get_color_command(b, d, glyph) {
+                    commands.push(transform);
+                    commands.push(color);
+                    continue;
+                }
+                let (font_name, glyph_id) = Self::get_path_key(d, glyph);
+                let path = CommandSegment::Use(font_name, glyph_id, b.font_size as f64);
                 let fill = CommandSegment::Fill(b.fill.to_string());
                 let stroke = Self::get_stroke(b);
-                let decoration = Self::get_decoration(b, d);
                 commands.push(transform);
                 commands.push(path);
                 commands.push(fill);
                 commands.push(stroke);
-                for command in decoration {
-                    commands.push(command);
-                }
             }
         }
+        commands.extend(above);
         (paths, commands)
     }
 }
@@ -289,9 +403,9 @@ pub fn tran_commands_stream(content: &(HashMap<(String, u32), PathData>, Vec<Com
     let mut commands = CommandsList::new();
     for command in source_commands {
         match command {
-            CommandSegment::Use(font_family, unicode, font_size) => {
+            CommandSegment::Use(font_name, glyph_id, font_size) => {
                 let default_path_data = PathData::new();
-                let mut result = g_path.get(&(font_family.clone(), *unicode)).unwrap_or(&default_path_data).clone();
+                let mut result = g_path.get(&(font_name.clone(), *glyph_id)).unwrap_or(&default_path_data).clone();
                 result.transform(Transform::new(*font_size as f32 / 100f32, 0f32, 0f32, *font_size as f32 / 100f32, 0f32, 0f32));
                 (commands.0).push(CommandSegment::Path(result));
             }
@@ -301,4 +415,70 @@ pub fn tran_commands_stream(content: &(HashMap<(String, u32), PathData>, Vec<Com
         }
     }
     commands
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::glyph::GlyphBitmap;
+
+    fn get_square() -> PathData {
+        let mut path = PathData::new();
+        path.move_to(0f32, 0f32);
+        path.line_to(1024f32, 0f32);
+        path.line_to(1024f32, 1024f32);
+        path.close();
+        path
+    }
+
+    fn get_color_segment(color: ColorGlyph) -> CommandSegment {
+        let glyph = Glyph { advance_width: 1024, units_per_em: 1024, color: Some(color), ..Glyph::get_none() };
+        let block = TextBlock { text: "a".to_string(), font_size: 16f32, fill: "#336699".to_string(), ..Default::default() };
+        let detail = TextBlockDetail::default(&glyph);
+        CommandList::get_color_command(&block, &detail, &glyph).unwrap()
+    }
+
+    #[test]
+    fn test_color_layers() {
+        let layers = vec![
+            ColorLayer { path: get_square(), paint: LayerPaint::Solid(LayerColor::Foreground(255)) },
+            ColorLayer { path: get_square(), paint: LayerPaint::Solid(LayerColor::Rgba(255, 0, 0, 51)) },
+        ];
+        let segment = get_color_segment(ColorGlyph::Layers(layers));
+        // 文字颜色的图层使用填充色，轮廓转换到字号坐标
+        match &segment {
+            CommandSegment::Color(ColorCommand::Layers(layers)) => {
+                assert_eq!(layers[0].paint.get_main_color(), Some(LayerColor::Rgba(0x33, 0x66, 0x99, 255)));
+                assert_eq!(String::from(&layers[0].path), "M 0.0 0.0 L 16.0 0.0 L 16.0 -16.0 Z");
+            }
+            _ => panic!("应为彩色图层"),
+        }
+        assert_eq!(String::from(&segment), concat!(
+            r#"{"type": "color", "layers": ["#,
+            r#"{"path": "M 0.0 0.0 L 16.0 0.0 L 16.0 -16.0 Z", "paint": {"type": "solid", "color": "rgba(51,102,153,1)"}}, "#,
+            r#"{"path": "M 0.0 0.0 L 16.0 0.0 L 16.0 -16.0 Z", "paint": {"type": "solid", "color": "rgba(255,0,0,0.2)"}}]}"#
+        ));
+        // 紧凑格式每个图层为路径加纯色填充
+        let packed = Vec::<f32>::from(&segment);
+        assert_eq!(&packed[..2], &[1f32, 4f32]);
+        assert_eq!(&packed[12..16], &[3f32, 0x336699 as f32, 1f32, 4f32]);
+        assert_eq!(&packed[26..], &[3f32, 0xff0000 as f32]);
+    }
+
+    #[test]
+    fn test_color_image() {
+        let bitmap = GlyphBitmap { format: "png".to_string(), data: b"png".to_vec(), x: 0f32, y: -256f32, width: 1024f32, height: 1024f32 };
+        let segment = get_color_segment(ColorGlyph::Bitmap(bitmap));
+        // 左上角为 y 较小的一边
+        assert_eq!(String::from(&segment), r#"{"type": "image", "format": "png", "x": 0.0, "y": -12.0, "width": 16.0, "height": 16.0, "data": "cG5n"}"#);
+        assert!(Vec::<f32>::from(&segment).is_empty());
+    }
+
+    #[test]
+    fn test_encode_base64() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(&[0xff, 0xfe]), "//4=");
+    }
+}
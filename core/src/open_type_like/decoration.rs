@@ -0,0 +1,424 @@
+use super::path::{PathData, PathSegment};
+use super::glyph::Glyph;
+use super::command::{CommandSegment, CommandList};
+use super::kinsoku;
+use crate::data::text_data::{TextBlock, TextBlockDetail, WritingMode};
+
+/// 行内坐标与画布坐标之间的映射
+type Mapping = Box<dyn Fn(f32, f32) -> (f32, f32)>;
+
+/// 相邻且样式相同的字合并为一段装饰线
+struct Run<'a> {
+    letters: Vec<&'a (TextBlock, TextBlockDetail<'a>)>,
+    block: &'a TextBlock,
+    glyph: &'a Glyph,
+    writing_mode: &'a WritingMode,
+    baseline: f32,
+    start: f32,
+    end: f32,
+    /// 不含末尾空白的结束位置
+    ink_end: f32,
+}
+
+fn has_line(block: &TextBlock, line: &str) -> bool {
+    block.decoration.split_whitespace().any(|item| item == line)
+}
+
+fn get_color(block: &TextBlock) -> String {
+    if block.decoration_color.is_empty() { block.fill.clone() } else { block.decoration_color.clone() }
+}
+
+/// 字在行方向上的起止与基线位置
+fn get_span(detail: &TextBlockDetail) -> (f32, f32, f32) {
+    let (x, y) = detail.position;
+    match detail.writing_mode {
+        WritingMode::HorizontalTB => (x, x + detail.b_width, y),
+        _ => (y, y + detail.b_width, x),
+    }
+}
+
+fn is_same_style(run: &Run, block: &TextBlock, detail: &TextBlockDetail) -> bool {
+    let (start, _, baseline) = get_span(detail);
+    run.block.font_size == block.font_size
+        && run.block.decoration_style == block.decoration_style
+        && run.block.decoration_skip_ink == block.decoration_skip_ink
+        && get_color(run.block) == get_color(block)
+        && run.glyph.font_name == detail.glyph.font_name
+        && (run.baseline - baseline).abs() < 0.5
+        && (run.end - start).abs() < 0.5
+}
+
+fn get_runs<'a>(letters: &'a Vec<(TextBlock, TextBlockDetail<'a>)>, line: &str) -> Vec<Run<'a>> {
+    let mut runs = Vec::<Run>::new();
+    let mut is_open = false;
+    for letter in letters.iter() {
+        let (block, detail) = letter;
+        if !has_line(block, line) {
+            is_open = false;
+            continue;
+        }
+        let (start, end, baseline) = get_span(detail);
+        let is_blank = block.text.chars().all(|c| c.is_whitespace());
+        match runs.last_mut() {
+            Some(run) if is_open && is_same_style(run, block, detail) => {
+                run.letters.push(letter);
+                run.end = end;
+                if !is_blank { run.ink_end = end; }
+            }
+            _ => runs.push(Run { letters: vec![letter], block, glyph: detail.glyph, writing_mode: &detail.writing_mode, baseline, start, end, ink_end: if is_blank { start } else { end } })
+        }
+        is_open = true;
+    }
+    runs
+}
+
+/// 线的中心到基线的距离、粗细以及 double 样式第二条线的方向，向上为正
+fn get_band(run: &Run, line: &str) -> (f32, f32, f32) {
+    let glyph = run.glyph;
+    let scale = run.block.font_size / glyph.units_per_em.max(1) as f32;
+    let metrics = &glyph.decoration;
+    let underline_thickness = metrics.underline_thickness as f32 * scale;
+    match line {
+        "underline" => (metrics.underline_position as f32 * scale - underline_thickness / 2f32, underline_thickness, -1f32),
+        "overline" => (glyph.ascender as f32 * scale - underline_thickness / 2f32, underline_thickness, 1f32),
+        _ => {
+            let thickness = metrics.strikeout_size as f32 * scale;
+            (metrics.strikeout_position as f32 * scale - thickness / 2f32, thickness, 0f32)
+        }
+    }
+}
+
+/// 各条线的中心，wavy 向外偏移一个振幅
+fn get_centers(style: &str, center: f32, t: f32, outward: f32) -> Vec<f32> {
+    match style {
+        "double" if outward == 0f32 => vec![center + t, center - t],
+        "double" => vec![center, center + outward * 2f32 * t],
+        "wavy" => vec![center + outward * t],
+        _ => vec![center]
+    }
+}
+
+/// 装饰线在垂直于行方向上覆盖的范围
+fn get_extent(style: &str, center: f32, thickness: f32, outward: f32) -> (f32, f32) {
+    let t = thickness.max(0.5f32);
+    let half = if style == "wavy" { t * 1.5f32 } else { t / 2f32 };
+    get_centers(style, center, t, outward).iter().fold((f32::MAX, f32::MIN), |(low, high), c| {
+        (low.min(c - half), high.max(c + half))
+    })
+}
+
+/// 轮廓拆成闭合折线，曲线按固定段数细分
+fn flatten(path: &PathData) -> Vec<Vec<(f32, f32)>> {
+    let mut contours = Vec::<Vec<(f32, f32)>>::new();
+    let mut current = (0f32, 0f32);
+    for segment in path.iter() {
+        match *segment {
+            PathSegment::MoveTo { x, y } => contours.push(vec![(x, y)]),
+            PathSegment::LineTo { x, y } => {
+                if let Some(contour) = contours.last_mut() { contour.push((x, y)); }
+            }
+            PathSegment::CurveTo { x, y, x1, y1, x2, y2 } => {
+                if let Some(contour) = contours.last_mut() {
+                    let (x0, y0) = current;
+                    for i in 1..=8 {
+                        let t = i as f32 / 8f32;
+                        let m = 1f32 - t;
+                        contour.push((
+                            m * m * m * x0 + 3f32 * m * m * t * x1 + 3f32 * m * t * t * x2 + t * t * t * x,
+                            m * m * m * y0 + 3f32 * m * m * t * y1 + 3f32 * m * t * t * y2 + t * t * t * y,
+                        ));
+                    }
+                }
+            }
+            PathSegment::ClosePath => {}
+        }
+        if let Some(point) = contours.last().and_then(|contour| contour.last()) {
+            current = *point;
+        }
+    }
+    contours
+}
+
+/// 字形轮廓在 `d` 方向 low 到 high 之间覆盖的 `u` 区间
+fn get_ink_intervals(contours: &[Vec<(f32, f32)>], low: f32, high: f32) -> Vec<(f32, f32)> {
+    let mut intervals = Vec::<(f32, f32)>::new();
+    let edges: Vec<((f32, f32), (f32, f32))> = contours.iter().flat_map(|contour| {
+        contour.iter().enumerate().map(move |(i, p)| (*p, contour[(i + 1) % contour.len()]))
+    }).collect();
+    // 落在范围内的轮廓线段
+    for ((u1, d1), (u2, d2)) in edges.iter() {
+        if (d1.max(*d2) < low) || (d1.min(*d2) > high) { continue; }
+        let clip = |d: f32| if (d2 - d1).abs() < f32::EPSILON { *u1 } else { u1 + (u2 - u1) * ((d - d1) / (d2 - d1)).clamp(0f32, 1f32) };
+        let (ua, ub) = (clip(d1.max(low).min(high)), clip(d2.max(low).min(high)));
+        intervals.push((ua.min(ub), ua.max(ub)));
+    }
+    // 扫描线按非零环绕规则取填充区间，覆盖整条线宽的笔画
+    for d in [low, (low + high) / 2f32, high].iter() {
+        let mut crossings = Vec::<(f32, i32)>::new();
+        for ((u1, d1), (u2, d2)) in edges.iter() {
+            if (d1 <= d) == (d2 <= d) { continue; }
+            let u = u1 + (u2 - u1) * (d - d1) / (d2 - d1);
+            crossings.push((u, if d2 > d1 { 1 } else { -1 }));
+        }
+        crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        let mut winding = 0;
+        let mut open = 0f32;
+        for (u, direction) in crossings {
+            if winding == 0 { open = u; }
+            winding += direction;
+            if winding == 0 { intervals.push((open, u)); }
+        }
+    }
+    intervals
+}
+
+/// 从 start 到 end 中去掉与字形相交的部分，过短的线段不保留
+fn get_skip_ink_segments(run: &Run, low: f32, high: f32, to_local: &dyn Fn(f32, f32) -> (f32, f32), padding: f32) -> Vec<(f32, f32)> {
+    let mut gaps = Vec::<(f32, f32)>::new();
+    for (block, detail) in run.letters.iter().map(|letter| (&letter.0, &letter.1)) {
+        let is_cjk = block.text.chars().all(kinsoku::is_cjk);
+        if block.decoration_skip_ink == "auto" && is_cjk { continue; }
+        for outline in CommandList::get_glyph_outlines(block, detail) {
+            let contours: Vec<Vec<(f32, f32)>> = flatten(&outline).into_iter()
+                .map(|contour| contour.into_iter().map(|(x, y)| to_local(x, y)).collect())
+                .collect();
+            for (u1, u2) in get_ink_intervals(&contours, low, high) {
+                gaps.push((u1 - padding, u2 + padding));
+            }
+        }
+    }
+    gaps.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    let mut segments = Vec::<(f32, f32)>::new();
+    let mut start = run.start;
+    for (gap_start, gap_end) in gaps {
+        if gap_start > start {
+            segments.push((start, gap_start.min(run.ink_end)));
+        }
+        start = start.max(gap_end);
+    }
+    segments.push((start, run.ink_end));
+    segments.into_iter().filter(|(start, end)| end - start >= padding).collect()
+}
+
+/// 生成装饰线轮廓，`u` 为行方向坐标，`d` 为到基线的距离
+pub fn get_decoration_path(style: &str, start: f32, end: f32, center: f32, thickness: f32, outward: f32, point: &dyn Fn(f32, f32) -> (f32, f32)) -> PathData {
+    let mut path_data = PathData::new();
+    let t = thickness.max(0.5f32);
+    let mut rect = |u1: f32, u2: f32, d1: f32, d2: f32| {
+        let (x, y) = point(u1, d1);
+        path_data.move_to(x, y);
+        for (u, d) in [(u2, d1), (u2, d2), (u1, d2)].iter() {
+            let (x, y) = point(*u, *d);
+            path_data.line_to(x, y);
+        }
+        path_data.close();
+    };
+    match style {
+        "double" => {
+            for c in get_centers(style, center, t, outward).iter() {
+                rect(start, end, c - t / 2f32, c + t / 2f32);
+            }
+        }
+        "dotted" | "dashed" => {
+            let (dash, gap) = if style == "dotted" { (t, t) } else { (3f32 * t, 2f32 * t) };
+            let mut u = start;
+            while u < end {
+                rect(u, (u + dash).min(end), center - t / 2f32, center + t / 2f32);
+                u += dash + gap;
+            }
+        }
+        "wavy" => {
+            let amplitude = t;
+            let center = get_centers(style, center, t, outward)[0];
+            let count = ((end - start) / (3f32 * t)).ceil().max(1f32) as usize;
+            let half = (end - start) / count as f32;
+            let edges = [center + t / 2f32, center - t / 2f32];
+            let (x, y) = point(start, edges[0]);
+            path_data.move_to(x, y);
+            for k in 0..count {
+                let sign = if k % 2 == 0 { 1f32 } else { -1f32 };
+                let (cx, cy) = point(start + half * (k as f32 + 0.5f32), edges[0] + sign * 2f32 * amplitude);
+                let (x, y) = point(start + half * (k + 1) as f32, edges[0]);
+                path_data.quad_to(x, y, cx, cy);
+            }
+            let (x, y) = point(end, edges[1]);
+            path_data.line_to(x, y);
+            for k in (0..count).rev() {
+                let sign = if k % 2 == 0 { 1f32 } else { -1f32 };
+                let (cx, cy) = point(start + half * (k as f32 + 0.5f32), edges[1] + sign * 2f32 * amplitude);
+                let (x, y) = point(start + half * k as f32, edges[1]);
+                path_data.quad_to(x, y, cx, cy);
+            }
+            path_data.close();
+        }
+        _ => rect(start, end, center - t / 2f32, center + t / 2f32)
+    }
+    path_data
+}
+
+/// 下划线与上划线画在文字下面，删除线画在文字上面，返回 `(下层, 上层)`
+pub fn get_decoration_commands(letters: &Vec<(TextBlock, TextBlockDetail)>) -> (Vec<CommandSegment>, Vec<CommandSegment>) {
+    let mut below = Vec::<CommandSegment>::new();
+    let mut above = Vec::<CommandSegment>::new();
+    for line in ["underline", "overline", "line-through"].iter() {
+        for run in get_runs(letters, line) {
+            if run.ink_end <= run.start { continue; }
+            let (center, thickness, outward) = get_band(&run, line);
+            let baseline = run.baseline;
+            let style = &run.block.decoration_style;
+            let (point, to_local): (Mapping, Mapping) = match run.writing_mode {
+                WritingMode::HorizontalTB => (Box::new(move |u, d| (u, baseline - d)), Box::new(move |x, y| (x, baseline - y))),
+                _ => (Box::new(move |u, d| (baseline + d, u)), Box::new(move |x, y| (y, x - baseline))),
+            };
+            // 删除线不避开字形
+            let segments = if *line != "line-through" && run.block.decoration_skip_ink != "none" {
+                let (low, high) = get_extent(style, center, thickness, outward);
+                let padding = thickness.max(run.block.font_size / 20f32);
+                get_skip_ink_segments(&run, low, high, &*to_local, padding)
+            } else {
+                vec![(run.start, run.ink_end)]
+            };
+            let mut path_data = PathData::new();
+            for (start, end) in segments {
+                path_data.extend(get_decoration_path(style, start, end, center, thickness, outward, &*point).iter().cloned());
+            }
+            if path_data.is_empty() { continue; }
+            let commands = if *line == "line-through" { &mut above } else { &mut below };
+            commands.push(CommandSegment::Transform(Default::default(), true));
+            commands.push(CommandSegment::Path(path_data));
+            commands.push(CommandSegment::Fill(get_color(run.block)));
+        }
+    }
+    (below, above)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::glyph::DecorationMetrics;
+
+    fn get_test_glyph() -> Glyph {
+        Glyph {
+            advance_width: 1000,
+            ascender: 800,
+            descender: -200,
+            decoration: DecorationMetrics { underline_position: -100, underline_thickness: 50, strikeout_position: 300, strikeout_size: 50 },
+            ..Glyph::get_none()
+        }
+    }
+
+    /// 横排一行中的字，每个字宽 10
+    fn get_letters<'a>(texts: &[(&str, &str, &str)], glyph: &'a Glyph) -> Vec<(TextBlock, TextBlockDetail<'a>)> {
+        texts.iter().enumerate().map(|(i, (text, decoration, fill))| {
+            let block = TextBlock {
+                text: text.to_string(),
+                font_size: 10f32,
+                fill: fill.to_string(),
+                decoration: decoration.to_string(),
+                decoration_style: "solid".to_string(),
+                decoration_skip_ink: "none".to_string(),
+                ..Default::default()
+            };
+            let detail = TextBlockDetail { position: (i as f32 * 10f32, 20f32), b_width: 10f32, ..TextBlockDetail::default(glyph) };
+            (block, detail)
+        }).collect()
+    }
+
+    fn count_rects(path: &PathData) -> usize {
+        path.iter().filter(|segment| matches!(segment, PathSegment::MoveTo { .. })).count()
+    }
+
+    #[test]
+    fn test_band() {
+        let glyph = get_test_glyph();
+        let letters = get_letters(&[("a", "underline overline line-through", "#000000")], &glyph);
+        let band = |line: &str| get_band(&get_runs(&letters, line)[0], line);
+        assert_eq!(band("underline"), (-1.25f32, 0.5f32, -1f32));
+        assert_eq!(band("overline"), (7.75f32, 0.5f32, 1f32));
+        assert_eq!(band("line-through"), (2.75f32, 0.5f32, 0f32));
+    }
+
+    #[test]
+    fn test_runs() {
+        let glyph = get_test_glyph();
+        // 相邻且样式相同的字合并，末尾的空白不画线
+        let letters = get_letters(&[("a", "underline", "#000000"), ("b", "underline", "#000000"), (" ", "underline", "#000000")], &glyph);
+        let runs = get_runs(&letters, "underline");
+        assert_eq!(runs.len(), 1);
+        assert_eq!((runs[0].letters.len(), runs[0].start, runs[0].end, runs[0].ink_end), (3, 0f32, 30f32, 20f32));
+        // 颜色不同或中间没有装饰线时分段
+        let letters = get_letters(&[("a", "underline", "#000000"), ("b", "underline", "#ff0000"), ("c", "", "#ff0000"), ("d", "underline", "#ff0000")], &glyph);
+        let runs: Vec<(f32, f32)> = get_runs(&letters, "underline").iter().map(|run| (run.start, run.end)).collect();
+        assert_eq!(runs, vec![(0f32, 10f32), (10f32, 20f32), (30f32, 40f32)]);
+        assert!(get_runs(&letters, "overline").is_empty());
+    }
+
+    #[test]
+    fn test_decoration_path() {
+        let point = |u: f32, d: f32| (u, -d);
+        assert_eq!(count_rects(&get_decoration_path("solid", 0f32, 100f32, 0f32, 2f32, -1f32, &point)), 1);
+        assert_eq!(count_rects(&get_decoration_path("double", 0f32, 100f32, 0f32, 2f32, -1f32, &point)), 2);
+        // dotted 的点与间隔都等于线宽，dashed 为 3 倍线宽的线段加 2 倍的间隔
+        assert_eq!(count_rects(&get_decoration_path("dotted", 0f32, 100f32, 0f32, 2f32, -1f32, &point)), 25);
+        assert_eq!(count_rects(&get_decoration_path("dashed", 0f32, 100f32, 0f32, 2f32, -1f32, &point)), 10);
+        let wavy = get_decoration_path("wavy", 0f32, 100f32, 0f32, 2f32, -1f32, &point);
+        assert_eq!(count_rects(&wavy), 1);
+        assert!(wavy.iter().any(|segment| matches!(segment, PathSegment::CurveTo { .. })));
+        // 下划线的 double 向下加线，删除线的 double 以中心对称
+        assert_eq!(get_extent("double", 0f32, 2f32, -1f32), (-5f32, 1f32));
+        assert_eq!(get_extent("double", 0f32, 2f32, 0f32), (-3f32, 3f32));
+    }
+
+    #[test]
+    fn test_decoration_commands() {
+        let glyph = get_test_glyph();
+        let letters = get_letters(&[("a", "underline line-through", "#000000"), ("b", "underline line-through", "#000000")], &glyph);
+        let (below, above) = get_decoration_commands(&letters);
+        assert_eq!((below.len(), above.len()), (3, 3));
+        match (&below[1], &below[2]) {
+            (CommandSegment::Path(path), CommandSegment::Fill(color)) => {
+                assert_eq!(count_rects(path), 1);
+                assert_eq!(color, "#000000");
+            }
+            _ => panic!("应为路径与填充"),
+        }
+    }
+
+    #[test]
+    fn test_ink_intervals() {
+        let square = vec![vec![(0f32, 0f32), (10f32, 0f32), (10f32, 10f32), (0f32, 10f32)]];
+        assert!(get_ink_intervals(&square, 2f32, 4f32).contains(&(0f32, 10f32)));
+        assert!(get_ink_intervals(&square, 11f32, 12f32).is_empty());
+    }
+
+    #[test]
+    fn test_skip_ink() {
+        // 竖笔画从基线下方穿过下划线
+        let glyph = Glyph { path: Glyph::parse("M 400 -300 L 600 -300 L 600 700 L 400 700 Z", 1000, 1000, 800, -200, 0).unwrap().path, ..get_test_glyph() };
+        let segments = |text: &str, skip_ink: &str| {
+            let mut letters = get_letters(&[(text, "underline", "#000000")], &glyph);
+            letters[0].0.decoration_skip_ink = skip_ink.to_string();
+            let run = &get_runs(&letters, "underline")[0];
+            let (center, thickness, outward) = get_band(run, "underline");
+            let (low, high) = get_extent("solid", center, thickness, outward);
+            let to_local = move |x: f32, y: f32| (x, 20f32 - y);
+            get_skip_ink_segments(run, low, high, &to_local, 0.5f32).iter()
+                .map(|(start, end)| ((start * 100f32).round() / 100f32, (end * 100f32).round() / 100f32))
+                .collect::<Vec<(f32, f32)>>()
+        };
+        assert_eq!(segments("a", "auto"), vec![(0f32, 3.5f32), (6.5f32, 10f32)]);
+        // auto 不避开中日韩文字，all 都避开
+        assert_eq!(segments("中", "auto"), vec![(0f32, 10f32)]);
+        assert_eq!(segments("中", "all"), vec![(0f32, 3.5f32), (6.5f32, 10f32)]);
+
+        // 删除线不避开字形
+        let mut letters = get_letters(&[("a", "underline line-through", "#000000")], &glyph);
+        letters[0].0.decoration_skip_ink = "all".to_string();
+        let (below, above) = get_decoration_commands(&letters);
+        let rects = |commands: &[CommandSegment]| commands.iter().map(|command| match command {
+            CommandSegment::Path(path) => count_rects(path),
+            _ => 0
+        }).sum::<usize>();
+        assert_eq!((rects(&below), rects(&above)), (2, 1));
+    }
+}
@@ -13,23 +13,13 @@ impl Font for FontData {
 
         while let Some(c) = chars.next() {
             let index = c as u32;
-            let pack_index = self.glyph_index_map.get(&index.to_string());
-//            let mut aw = &200i32;
-//            let mut gs = &"".to_string();
-            match pack_index {
-                Some(i) => {
-                    let (_a, g) = &self.glyphs_pack[*i];
-                    char_glyphs.push((c.to_string(), g.clone()));
-                }
-                None => {
-                    if let Some(fall_back) = default_font {
-                        let pack_index = fall_back.glyph_index_map.get(&index.to_string());
-                        if let Some(i) = pack_index {
-                            let (_a, g) = &fall_back.glyphs_pack[*i];
-                            char_glyphs.push((c.to_string(), g.clone()));
-                        }
-                    }
-                }
+            // 依次查找自身、fall_back 链与默认字体
+            let mut fonts = std::iter::successors(Some(self), |font| font.fall_back.as_deref()).chain(default_font);
+            let glyph = fonts.find_map(|font| {
+                font.glyph_index_map.get(&index.to_string()).map(|i| &font.glyphs_pack[*i].1)
+            });
+            if let Some(g) = glyph {
+                char_glyphs.push((c.to_string(), g.clone()));
             }
         }
         Some(char_glyphs)
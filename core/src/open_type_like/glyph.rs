@@ -13,6 +13,203 @@ pub struct Glyph {
     pub descender: i32,
     pub char_code: Option<u32>,
     pub left_side_bearing: i32,
+    pub glyph_id: u32,
+    pub font_name: String,
+    /// 所属字体的装饰线度量
+    pub decoration: DecorationMetrics,
+    /// 可变字体实例的轴取值，非可变字体为空
+    pub variations: Variations,
+    /// 彩色字形，没有时以 `path` 绘制
+    pub color: Option<ColorGlyph>,
+}
+
+/// 彩色字形，来自 COLR/CPAL 的图层或 CBDT/CBLC、sbix 中的位图，坐标为字体单位
+#[derive(Debug, Clone)]
+pub enum ColorGlyph {
+    /// 按顺序从下往上绘制的图层
+    Layers(Vec<ColorLayer>),
+    Bitmap(GlyphBitmap),
+}
+
+#[derive(Debug, Clone)]
+pub struct ColorLayer {
+    pub path: PathData,
+    pub paint: LayerPaint,
+}
+
+/// 图层颜色，`Foreground` 使用文字的颜色，参数为不透明度
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerColor {
+    Rgba(u8, u8, u8, u8),
+    Foreground(u8),
+}
+
+/// 渐变在起止点之外的延伸方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientExtend {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+/// 图层的填充，渐变的坐标经过 `transform` 变换后与轮廓处于同一坐标系
+#[derive(Debug, Clone)]
+pub enum LayerPaint {
+    Solid(LayerColor),
+    LinearGradient {
+        start: (f32, f32),
+        end: (f32, f32),
+        stops: Vec<(f32, LayerColor)>,
+        extend: GradientExtend,
+        transform: Transform,
+    },
+    RadialGradient {
+        start: (f32, f32),
+        start_radius: f32,
+        end: (f32, f32),
+        end_radius: f32,
+        stops: Vec<(f32, LayerColor)>,
+        extend: GradientExtend,
+        transform: Transform,
+    },
+}
+
+impl LayerPaint {
+    /// 纯色或渐变第一个颜色，供不支持渐变的输出使用
+    pub fn get_main_color(&self) -> Option<LayerColor> {
+        match self {
+            LayerPaint::Solid(color) => Some(*color),
+            LayerPaint::LinearGradient { stops, .. } | LayerPaint::RadialGradient { stops, .. } => stops.first().map(|(_, color)| *color),
+        }
+    }
+
+    pub fn map_color<F: Fn(LayerColor) -> LayerColor>(&self, f: F) -> LayerPaint {
+        match self {
+            LayerPaint::Solid(color) => LayerPaint::Solid(f(*color)),
+            LayerPaint::LinearGradient { start, end, stops, extend, transform } => LayerPaint::LinearGradient {
+                start: *start,
+                end: *end,
+                stops: stops.iter().map(|(offset, color)| (*offset, f(*color))).collect(),
+                extend: *extend,
+                transform: *transform,
+            },
+            LayerPaint::RadialGradient { start, start_radius, end, end_radius, stops, extend, transform } => LayerPaint::RadialGradient {
+                start: *start,
+                start_radius: *start_radius,
+                end: *end,
+                end_radius: *end_radius,
+                stops: stops.iter().map(|(offset, color)| (*offset, f(*color))).collect(),
+                extend: *extend,
+                transform: *transform,
+            },
+        }
+    }
+
+    /// 在渐变原有的变换之前再应用 `ts`
+    pub fn prepend_transform(&mut self, ts: &Transform) {
+        match self {
+            LayerPaint::Solid(_) => (),
+            LayerPaint::LinearGradient { transform, .. } | LayerPaint::RadialGradient { transform, .. } => transform.prepend(ts),
+        }
+    }
+}
+
+/// 位图字形，`x`、`y` 为图片左下角
+#[derive(Debug, Clone)]
+pub struct GlyphBitmap {
+    /// 图片格式，如 `png`
+    pub format: String,
+    pub data: Vec<u8>,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// 可变字体各轴的取值，按轴标签排序，数值为 16.16 定点数以便用作缓存的键
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Variations(pub Vec<([u8; 4], i32)>);
+
+impl Variations {
+    /// 同一个轴出现多次时以最后一次为准
+    pub fn new(values: &[([u8; 4], f32)]) -> Self {
+        let mut variations = Vec::<([u8; 4], i32)>::new();
+        for (tag, value) in values.iter() {
+            variations.retain(|(t, _)| t != tag);
+            variations.push((*tag, (value * 65536f32).round() as i32));
+        }
+        variations.sort_by_key(|(tag, _)| *tag);
+        Variations(variations)
+    }
+
+    pub fn get(&self, tag: &[u8; 4]) -> Option<f32> {
+        self.0.iter().find(|(t, _)| t == tag).map(|(_, value)| *value as f32 / 65536f32)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<&Variations> for String {
+    fn from(variations: &Variations) -> Self {
+        let items: Vec<String> = variations.0.iter().map(|(tag, value)| {
+            format!("{}={}", String::from_utf8_lossy(tag), *value as f32 / 65536f32)
+        }).collect();
+        items.join(",")
+    }
+}
+
+/// 下划线与删除线的位置和粗细，字体单位，位置为线的上沿到基线的距离，向上为正
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecorationMetrics {
+    pub underline_position: i32,
+    pub underline_thickness: i32,
+    pub strikeout_position: i32,
+    pub strikeout_size: i32,
+}
+
+impl DecorationMetrics {
+    /// 字体没有 `post` 或 `OS/2` 表时的估计值
+    pub fn fallback(units_per_em: i32) -> Self {
+        DecorationMetrics {
+            underline_position: -units_per_em / 10,
+            underline_thickness: units_per_em / 20,
+            strikeout_position: units_per_em * 3 / 10,
+            strikeout_size: units_per_em / 20,
+        }
+    }
+}
+
+/// GSUB/GPOS 处理后的单个字形，数值均为字体单位
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShapedGlyph {
+    pub glyph_id: u32,
+    /// 字形所属字形簇在原文中的字节偏移
+    pub cluster: usize,
+    pub x_advance: i32,
+    pub y_advance: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+/// 排版时传给字体的选项
+#[derive(Debug, Clone)]
+pub struct ShapeOption {
+    pub kerning: bool,
+    /// 文字方向，为 `None` 时根据文字推断
+    pub rtl: Option<bool>,
+    pub variations: Variations,
+}
+
+impl Default for ShapeOption {
+    fn default() -> Self {
+        ShapeOption {
+            kerning: true,
+            rtl: None,
+            variations: Variations::default(),
+        }
+    }
 }
 
 fn is_orientation(char_code: u32) -> bool {
@@ -36,6 +233,11 @@ impl Glyph {
             descender,
             left_side_bearing,
             char_code: None,
+            glyph_id: 0,
+            font_name: "".to_string(),
+            decoration: DecorationMetrics::fallback(units_per_em),
+            variations: Variations::default(),
+            color: None,
         })
     }
 
@@ -48,6 +250,11 @@ impl Glyph {
             descender: -100i32,
             left_side_bearing: 0,
             char_code: None,
+            glyph_id: 0,
+            font_name: "".to_string(),
+            decoration: DecorationMetrics::fallback(1000),
+            variations: Variations::default(),
+            color: None,
         }
     }
 
@@ -57,6 +264,12 @@ impl Glyph {
 
     pub fn get_path(&self, x: f32, y: f32, font_size: f32, writing_mode: &WritingMode) -> PathData {
         let mut path_data = self.path.clone();
+        path_data.transform(self.get_transform(x, y, font_size, writing_mode));
+        path_data
+    }
+
+    /// 字体单位到绘制坐标的变换
+    pub fn get_transform(&self, x: f32, y: f32, font_size: f32, writing_mode: &WritingMode) -> Transform {
         let scale = 1.0f32 / (self.units_per_em as f32) * font_size;
         let mut transform = Transform {
             a: scale,
@@ -79,8 +292,7 @@ impl Glyph {
                 transform.translate(dx, dy)
             }
         }
-        path_data.transform(transform);
-        path_data
+        transform
     }
 
     pub fn get_advance_width(&self, font_size: f32) -> f32 {
@@ -120,6 +332,11 @@ impl Default for Glyph {
             descender: 0,
             left_side_bearing: 0,
             char_code: None,
+            glyph_id: 0,
+            font_name: "".to_string(),
+            decoration: Default::default(),
+            variations: Variations::default(),
+            color: None,
         }
     }
 }
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// 断词时插入的连字符
+pub const HYPHEN: char = '-';
+/// 软连字符，只在此处断行时显示连字符
+pub const SOFT_HYPHEN: char = '\u{AD}';
+
+#[cfg(feature = "hyphenation-en")]
+lazy_static! {
+    static ref PATTERNS_EN: Arc<Patterns> = Arc::new(Patterns::parse(include_str!("../../patterns/en.pat"), 2, 3));
+}
+
+#[cfg(feature = "hyphenation-de")]
+lazy_static! {
+    static ref PATTERNS_DE: Arc<Patterns> = Arc::new(Patterns::parse(include_str!("../../patterns/de.pat"), 2, 2));
+}
+
+lazy_static! {
+    /// 运行时加载的断词模式，按主语言标签保存，优先于内置模式
+    static ref LOADED_PATTERNS: RwLock<HashMap<String, Arc<Patterns>>> = RwLock::new(HashMap::new());
+}
+
+/// Liang 断词模式
+pub struct Patterns {
+    patterns: HashMap<String, Vec<u8>>,
+    max_len: usize,
+    left_min: usize,
+    right_min: usize,
+}
+
+impl Patterns {
+    pub fn parse(source: &str, left_min: usize, right_min: usize) -> Self {
+        let mut patterns = HashMap::<String, Vec<u8>>::new();
+        let mut max_len = 0usize;
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('%') { continue; }
+            let mut letters = String::new();
+            let mut levels = vec![0u8];
+            for c in line.chars() {
+                if let Some(level) = c.to_digit(10) {
+                    *levels.last_mut().unwrap() = level as u8;
+                } else {
+                    letters.push(c);
+                    levels.push(0);
+                }
+            }
+            max_len = max_len.max(levels.len() - 1);
+            patterns.insert(letters, levels);
+        }
+        Patterns {
+            patterns,
+            max_len,
+            left_min,
+            right_min,
+        }
+    }
+
+    /// 返回可以断词的字符下标，在该字符之前断开
+    pub fn hyphenate(&self, word: &str) -> Vec<usize> {
+        let mut chars = vec!['.'];
+        for c in word.chars() {
+            chars.push(c.to_lowercase().next().unwrap_or(c));
+        }
+        chars.push('.');
+        let len = word.chars().count();
+        if len < self.left_min + self.right_min { return vec![]; }
+
+        let mut points = vec![0u8; chars.len() + 1];
+        for start in 0..chars.len() {
+            let mut key = String::new();
+            for c in chars[start..chars.len().min(start + self.max_len)].iter() {
+                key.push(*c);
+                if let Some(levels) = self.patterns.get(&key) {
+                    for (offset, level) in levels.iter().enumerate() {
+                        let point = &mut points[start + offset];
+                        *point = (*point).max(*level);
+                    }
+                }
+            }
+        }
+        (self.left_min..=len - self.right_min)
+            .filter(|index| points[index + 1] % 2 == 1)
+            .collect()
+    }
+}
+
+fn get_primary_language(lang: &str) -> String {
+    lang.split(&['-', '_'][..]).next().unwrap_or("").to_lowercase()
+}
+
+/// 加载语言的断词模式，`lang` 只取主语言标签
+pub fn set_patterns(lang: &str, patterns: Patterns) {
+    LOADED_PATTERNS.write().unwrap().insert(get_primary_language(lang), Arc::new(patterns));
+}
+
+/// 按语言标签取断词模式，英语与德语分别由 `hyphenation-en`、`hyphenation-de` 特性内置，
+/// 其他语言需要先调用 `set_patterns`
+pub fn get_patterns(lang: &str) -> Option<Arc<Patterns>> {
+    let primary = get_primary_language(lang);
+    if let Some(patterns) = LOADED_PATTERNS.read().unwrap().get(&primary) {
+        return Some(patterns.clone());
+    }
+    match primary.as_str() {
+        #[cfg(feature = "hyphenation-en")]
+        "en" => Some(PATTERNS_EN.clone()),
+        #[cfg(feature = "hyphenation-de")]
+        "de" => Some(PATTERNS_DE.clone()),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_patterns, set_patterns, Patterns};
+
+    fn hyphenate(lang: &str, word: &str) -> String {
+        let points = get_patterns(lang).unwrap().hyphenate(word);
+        let mut result = String::new();
+        for (index, c) in word.chars().enumerate() {
+            if points.contains(&index) { result.push('-'); }
+            result.push(c);
+        }
+        result
+    }
+
+    #[test]
+    #[cfg(all(feature = "hyphenation-en", feature = "hyphenation-de"))]
+    fn test_hyphenate() {
+        assert_eq!(hyphenate("en-US", "hyphenation"), "hy-phen-ation");
+        assert_eq!(hyphenate("en", "extensive"), "ex-ten-sive");
+        assert_eq!(hyphenate("en", "hi"), "hi");
+        assert_eq!(hyphenate("de", "Silbentrennung"), "Sil-ben-tren-nung");
+    }
+
+    #[test]
+    fn test_set_patterns() {
+        assert!(get_patterns("nl").is_none());
+        set_patterns("nl-NL", Patterns::parse("% test\n1ba\n", 1, 1));
+        assert_eq!(hyphenate("nl", "abab"), "a-bab");
+    }
+}
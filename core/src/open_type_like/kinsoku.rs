@@ -0,0 +1,95 @@
+/// 行首禁则，loose 模式
+const LINE_START_LOOSE: &str = ",.!?:;)]}、。，．：；！？）」』】〕〉》〗〙〛｝］’”〞»";
+/// normal 模式额外禁止的行首字符
+const LINE_START_NORMAL: &str = "々〻ゝゞヽヾ‐–〜～・…‥゠";
+/// strict 模式额外禁止的行首字符
+const LINE_START_STRICT: &str = "ぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶㇰㇱㇲㇳㇴㇵㇶㇷㇸㇹㇺㇻㇼㇽㇾㇿー";
+/// 行尾禁则
+const LINE_END: &str = "([{（「『【〔〈《〖〘〚｛［‘“〝«";
+/// 可以悬挂在行尾的标点
+const HANGING: &str = "、。，．";
+
+pub fn is_cjk(c: char) -> bool {
+    let c = c as u32;
+    (0x2E80..=0x9FFF).contains(&c) || (0xF900..=0xFAFF).contains(&c) || (0xFE30..=0xFE4F).contains(&c)
+        || (0xFF00..=0xFFEF).contains(&c) || (0x20000..=0x3FFFF).contains(&c)
+}
+
+/// 禁则表中的非 ASCII 标点，如弯引号与省略号，本身不算中日韩文字
+fn is_kinsoku_punctuation(c: char) -> bool {
+    !c.is_ascii() && (LINE_START_LOOSE.contains(c) || LINE_START_NORMAL.contains(c) || LINE_START_STRICT.contains(c) || LINE_END.contains(c))
+}
+
+/// 两个字之间按禁则断行：至少一个是中日韩文字，另一个是中日韩文字或禁则表中的标点
+pub fn is_cjk_pair(prev: char, next: char) -> bool {
+    (is_cjk(prev) || is_cjk(next))
+        && (is_cjk(prev) || is_kinsoku_punctuation(prev))
+        && (is_cjk(next) || is_kinsoku_punctuation(next))
+}
+
+pub fn is_line_start_prohibited(c: char, mode: &str) -> bool {
+    if LINE_START_LOOSE.contains(c) { return true; }
+    match mode {
+        "loose" => false,
+        "strict" => LINE_START_NORMAL.contains(c) || LINE_START_STRICT.contains(c),
+        _ => LINE_START_NORMAL.contains(c)
+    }
+}
+
+pub fn is_line_end_prohibited(c: char, _mode: &str) -> bool {
+    LINE_END.contains(c)
+}
+
+pub fn is_hanging_punctuation(c: char) -> bool {
+    HANGING.contains(c)
+}
+
+/// strict 模式不允许标点悬挂
+pub fn is_hanging(c: char, mode: &str) -> bool {
+    mode != "strict" && is_hanging_punctuation(c)
+}
+
+/// 两个中日文字符之间能否断行
+pub fn can_break(prev: char, next: char, mode: &str) -> bool {
+    !is_line_end_prohibited(prev, mode) && !is_line_start_prohibited(next, mode)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_line_start() {
+        // loose 只禁止句读点与闭括号
+        for c in "、。」）!".chars() {
+            assert!(is_line_start_prohibited(c, "loose") && is_line_start_prohibited(c, "normal") && is_line_start_prohibited(c, "strict"));
+        }
+        // normal 额外禁止叠字符与连接号
+        for c in "々ゝ〜・…".chars() {
+            assert!(!is_line_start_prohibited(c, "loose"));
+            assert!(is_line_start_prohibited(c, "normal") && is_line_start_prohibited(c, "strict"));
+        }
+        // strict 额外禁止小写假名与长音符
+        for c in "ぁっゃァッャー".chars() {
+            assert!(!is_line_start_prohibited(c, "normal"));
+            assert!(is_line_start_prohibited(c, "strict"));
+        }
+        assert!(!is_line_start_prohibited('中', "strict"));
+    }
+
+    #[test]
+    fn test_break() {
+        assert!(is_line_end_prohibited('「', "loose") && is_line_end_prohibited('（', "strict"));
+        assert!(!can_break('「', '中', "loose"));
+        assert!(!can_break('中', '。', "loose"));
+        assert!(can_break('中', 'ッ', "normal"));
+        assert!(!can_break('中', 'ッ', "strict"));
+        assert!(is_hanging('。', "normal") && is_hanging('、', "loose"));
+        assert!(!is_hanging('。', "strict") && !is_hanging('」', "normal"));
+        assert!(is_cjk('中') && is_cjk('ア') && is_cjk('，') && !is_cjk('a'));
+        // 弯引号与省略号只在中日韩文字旁按禁则处理
+        assert!(!is_cjk('“') && !is_cjk('…'));
+        assert!(is_cjk_pair('中', '“') && is_cjk_pair('”', '中') && is_cjk_pair('中', '…'));
+        assert!(!is_cjk_pair('a', '”') && !is_cjk_pair('“', '…') && !is_cjk_pair('中', ','));
+    }
+}
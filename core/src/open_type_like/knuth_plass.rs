@@ -0,0 +1,184 @@
+/// 罚分为该值时表示禁止断行，为其相反数时表示必须断行
+pub const INFINITY: f32 = 10000.0;
+
+const LINE_PENALTY: f64 = 10.0;
+const FLAGGED_DEMERITS: f64 = 3000.0;
+const FITNESS_DEMERITS: f64 = 3000.0;
+
+/// 盒子、粘连与罚点，宽度单位与行宽一致
+#[derive(Debug, Clone)]
+pub enum Item {
+    Box(f32),
+    Glue { width: f32, stretch: f32, shrink: f32 },
+    Penalty { width: f32, penalty: f32, flagged: bool },
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    position: usize,
+    line: usize,
+    fitness: usize,
+    total_width: f32,
+    total_stretch: f32,
+    total_shrink: f32,
+    demerits: f64,
+    previous: Option<usize>,
+}
+
+fn get_fitness(ratio: f32) -> usize {
+    if ratio < -0.5 {
+        0
+    } else if ratio <= 0.5 {
+        1
+    } else if ratio <= 1.0 {
+        2
+    } else {
+        3
+    }
+}
+
+/// 计算整段的最优断点，返回断点在 items 中的下标，超出容差无法排版时返回 `None`
+pub fn compute_breaks(items: &[Item], limit: f32, tolerance: f32) -> Option<Vec<usize>> {
+    let mut sum_width = vec![0f32; items.len() + 1];
+    let mut sum_stretch = vec![0f32; items.len() + 1];
+    let mut sum_shrink = vec![0f32; items.len() + 1];
+    for (index, item) in items.iter().enumerate() {
+        let (width, stretch, shrink) = match item {
+            Item::Box(width) => (*width, 0f32, 0f32),
+            Item::Glue { width, stretch, shrink } => (*width, *stretch, *shrink),
+            Item::Penalty { .. } => (0f32, 0f32, 0f32),
+        };
+        sum_width[index + 1] = sum_width[index] + width;
+        sum_stretch[index + 1] = sum_stretch[index] + stretch;
+        sum_shrink[index + 1] = sum_shrink[index] + shrink;
+    }
+
+    let mut nodes = vec![Node {
+        position: 0,
+        line: 0,
+        fitness: 1,
+        total_width: 0f32,
+        total_stretch: 0f32,
+        total_shrink: 0f32,
+        demerits: 0f64,
+        previous: None,
+    }];
+    let mut active = vec![0usize];
+
+    for (index, item) in items.iter().enumerate() {
+        let (penalty_width, penalty, flagged) = match item {
+            Item::Penalty { width, penalty, flagged } if *penalty < INFINITY => (*width, *penalty, *flagged),
+            Item::Glue { .. } if index > 0 => match items[index - 1] {
+                Item::Box(_) => (0f32, 0f32, false),
+                _ => continue
+            },
+            _ => continue
+        };
+        let is_forced = penalty <= -INFINITY;
+
+        let mut candidates: [Option<(f64, usize)>; 4] = [None; 4];
+        let mut next_active = Vec::<usize>::new();
+        for node_index in active.iter() {
+            let node = &nodes[*node_index];
+            let width = sum_width[index] - node.total_width + penalty_width;
+            let ratio = if width < limit {
+                let stretch = sum_stretch[index] - node.total_stretch;
+                if stretch > 0f32 { (limit - width) / stretch } else { INFINITY }
+            } else if width > limit {
+                let shrink = sum_shrink[index] - node.total_shrink;
+                if shrink > 0f32 { (limit - width) / shrink } else { -INFINITY }
+            } else {
+                0f32
+            };
+            if ratio >= -1f32 && !is_forced {
+                next_active.push(*node_index);
+            }
+            if ratio < -1f32 || ratio > tolerance { continue; }
+
+            let badness = 100f64 * (ratio.abs() as f64).powi(3);
+            let mut demerits = if penalty >= 0f32 {
+                (LINE_PENALTY + badness).powi(2) + (penalty as f64).powi(2)
+            } else if penalty > -INFINITY {
+                (LINE_PENALTY + badness).powi(2) - (penalty as f64).powi(2)
+            } else {
+                (LINE_PENALTY + badness).powi(2)
+            };
+            if flagged {
+                if let Item::Penalty { flagged: true, .. } = items[node.position] {
+                    if node.position > 0 { demerits += FLAGGED_DEMERITS; }
+                }
+            }
+            let fitness = get_fitness(ratio);
+            if (fitness as i32 - node.fitness as i32).abs() > 1 {
+                demerits += FITNESS_DEMERITS;
+            }
+            demerits += node.demerits;
+            match candidates[fitness] {
+                Some((best, _)) if best <= demerits => {}
+                _ => candidates[fitness] = Some((demerits, *node_index))
+            }
+        }
+
+        // 断行后跳过行首的粘连与罚点，其后紧跟强制断行时在此断行会产生空行
+        let mut after = index;
+        while after < items.len() {
+            match items[after] {
+                Item::Box(_) => break,
+                Item::Penalty { penalty, .. } if penalty <= -INFINITY && after > index => {
+                    candidates = [None; 4];
+                    break;
+                }
+                _ => after += 1
+            }
+        }
+        for (fitness, candidate) in candidates.iter().enumerate() {
+            if let Some((demerits, previous)) = candidate {
+                nodes.push(Node {
+                    position: index,
+                    line: nodes[*previous].line + 1,
+                    fitness,
+                    total_width: sum_width[after],
+                    total_stretch: sum_stretch[after],
+                    total_shrink: sum_shrink[after],
+                    demerits: *demerits,
+                    previous: Some(*previous),
+                });
+                next_active.push(nodes.len() - 1);
+            }
+        }
+        if next_active.is_empty() { return None; }
+        active = next_active;
+    }
+
+    let mut best = *active.iter().min_by(|a, b| {
+        nodes[**a].demerits.partial_cmp(&nodes[**b].demerits).unwrap_or(std::cmp::Ordering::Equal)
+    })?;
+    let mut breaks = Vec::<usize>::new();
+    while let Some(previous) = nodes[best].previous {
+        breaks.push(nodes[best].position);
+        best = previous;
+    }
+    breaks.reverse();
+    Some(breaks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compute_breaks() {
+        let mut items = Vec::<Item>::new();
+        for width in [3f32, 2f32, 4f32, 1f32, 3f32].iter() {
+            items.push(Item::Box(*width));
+            items.push(Item::Glue { width: 1f32, stretch: 1f32, shrink: 0.5f32 });
+        }
+        items.pop();
+        items.push(Item::Penalty { width: 0f32, penalty: INFINITY, flagged: false });
+        items.push(Item::Glue { width: 0f32, stretch: INFINITY, shrink: 0f32 });
+        items.push(Item::Penalty { width: 0f32, penalty: -INFINITY, flagged: false });
+        let breaks = compute_breaks(&items, 7f32, 2f32).unwrap();
+        assert_eq!(breaks, vec![3, 7, 11]);
+        assert!(compute_breaks(&vec![Item::Box(10f32), Item::Penalty { width: 0f32, penalty: -INFINITY, flagged: false }], 7f32, 2f32).is_none());
+    }
+}
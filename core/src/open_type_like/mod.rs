@@ -4,4 +4,9 @@ pub mod font;
 pub mod glyph;
 pub mod command;
 pub mod word;
-pub mod transform;
\ No newline at end of file
+pub mod bidi;
+pub mod kinsoku;
+pub mod hyphenation;
+pub mod knuth_plass;
+pub mod decoration;
+pub mod transform;pub mod script;
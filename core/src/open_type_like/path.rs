@@ -60,6 +60,9 @@ impl From<&PathSegment> for Vec<f32> {
     }
 }
 
+/// `(线段序号, 点序号)` 与坐标
+type ContourPoint = ((usize, usize), (f32, f32));
+
 #[derive(Clone, Default, Debug)]
 pub struct PathData(pub Vec<PathSegment>);
 
@@ -145,6 +148,76 @@ impl PathData {
         transform_path(&mut self[offset..], ts);
     }
 
+    /// 合成粗体，每个轮廓点沿相邻两边法线的角平分线向填充区域外移动 `strength`
+    pub fn embolden(&mut self, strength: f32) {
+        let contours = self.get_contour_points();
+        let area: f32 = contours.iter().map(|points| {
+            (0..points.len()).fold(0f32, |area, i| {
+                let (x1, y1) = points[i].1;
+                let (x2, y2) = points[(i + 1) % points.len()].1;
+                area + x1 * y2 - x2 * y1
+            })
+        }).sum();
+        let sign = if area < 0f32 { -1f32 } else { 1f32 };
+        let normal = |(x1, y1): (f32, f32), (x2, y2): (f32, f32)| {
+            let (dx, dy) = (x2 - x1, y2 - y1);
+            let length = (dx * dx + dy * dy).sqrt();
+            (dy / length * sign, -dx / length * sign)
+        };
+        for points in contours.iter() {
+            let count = points.len();
+            for (i, (slot, point)) in points.iter().enumerate() {
+                // 跳过重合的点，找到前后两个不同的点
+                let prev = (1..count).map(|j| points[(i + count - j) % count].1).find(|p| p != point);
+                let next = (1..count).map(|j| points[(i + j) % count].1).find(|p| p != point);
+                let (prev, next) = match (prev, next) {
+                    (Some(prev), Some(next)) => (prev, next),
+                    _ => continue
+                };
+                let (n1, n2) = (normal(prev, *point), normal(*point, next));
+                let q = (1f32 + n1.0 * n2.0 + n1.1 * n2.1).max(0.25f32);
+                let shift = ((n1.0 + n2.0) / q * strength, (n1.1 + n2.1) / q * strength);
+                self.set_point(*slot, (point.0 + shift.0, point.1 + shift.1));
+            }
+        }
+    }
+
+    /// 按轮廓分组的所有端点与控制点
+    fn get_contour_points(&self) -> Vec<Vec<ContourPoint>> {
+        let mut contours = Vec::<Vec<ContourPoint>>::new();
+        for (index, segment) in self.iter().enumerate() {
+            match *segment {
+                PathSegment::MoveTo { x, y } => contours.push(vec![((index, 0), (x, y))]),
+                PathSegment::LineTo { x, y } => {
+                    if let Some(points) = contours.last_mut() { points.push(((index, 0), (x, y))); }
+                }
+                PathSegment::CurveTo { x, y, x1, y1, x2, y2 } => {
+                    if let Some(points) = contours.last_mut() {
+                        points.push(((index, 1), (x1, y1)));
+                        points.push(((index, 2), (x2, y2)));
+                        points.push(((index, 0), (x, y)));
+                    }
+                }
+                PathSegment::ClosePath => {}
+            }
+        }
+        contours
+    }
+
+    fn set_point(&mut self, (index, slot): (usize, usize), (px, py): (f32, f32)) {
+        match (&mut self[index], slot) {
+            (PathSegment::MoveTo { x, y }, 0)
+            | (PathSegment::LineTo { x, y }, 0)
+            | (PathSegment::CurveTo { x, y, .. }, 0)
+            | (PathSegment::CurveTo { x1: x, y1: y, .. }, 1)
+            | (PathSegment::CurveTo { x2: x, y2: y, .. }, 2) => {
+                *x = px;
+                *y = py;
+            }
+            _ => {}
+        }
+    }
+
     pub fn get_bounding_box(&self) -> Option<BoundingBox> {
         let first = self.0.get(0);
         if first.is_none() { return None; }
@@ -401,4 +474,46 @@ fn derive(v0: f32, v1: f32, v2: f32, v3: f32, t: f32) -> f32 {
         3.0 * (1.0 - t).powf(2.0) * t * v1 +
         3.0 * (1.0 - t) * t.powf(2.0) * v2 +
         t.powf(3.0) * v3;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PathData, PathSegment};
+
+    fn get_square(clockwise: bool) -> PathData {
+        let mut path = PathData::new();
+        let points = if clockwise { [(0f32, 10f32), (10f32, 10f32), (10f32, 0f32)] } else { [(10f32, 0f32), (10f32, 10f32), (0f32, 10f32)] };
+        path.move_to(0f32, 0f32);
+        for (x, y) in points.iter() {
+            path.line_to(*x, *y);
+        }
+        path.close();
+        path
+    }
+
+    #[test]
+    fn test_embolden() {
+        // 不论轮廓方向都向填充区域外扩
+        for clockwise in [false, true].iter() {
+            let mut path = get_square(*clockwise);
+            path.embolden(1f32);
+            let bbox = path.get_bounding_box().unwrap();
+            assert_eq!((bbox.x1, bbox.y1, bbox.x2, bbox.y2), (-1f32, -1f32, 11f32, 11f32));
+        }
+        // 内轮廓与外轮廓方向相反，向内收缩
+        let mut path = get_square(false);
+        let mut hole = PathData::new();
+        hole.move_to(4f32, 4f32);
+        for (x, y) in [(4f32, 6f32), (6f32, 6f32), (6f32, 4f32)].iter() {
+            hole.line_to(*x, *y);
+        }
+        hole.close();
+        path.extend(hole.iter().cloned());
+        path.embolden(0.5f32);
+        let points: Vec<(f32, f32)> = path[5..].iter().filter_map(|segment| match *segment {
+            PathSegment::MoveTo { x, y } | PathSegment::LineTo { x, y } => Some((x, y)),
+            _ => None
+        }).collect();
+        assert_eq!(points, vec![(4.5f32, 4.5f32), (4.5f32, 5.5f32), (5.5f32, 5.5f32), (5.5f32, 4.5f32)]);
+    }
+}
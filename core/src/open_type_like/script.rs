@@ -0,0 +1,181 @@
+/// 通用字符，跟随前后文字
+pub const COMMON: &str = "Zyyy";
+/// 表情符号
+pub const EMOJI: &str = "Zsye";
+
+/// 按起始码位排序的 (起, 止, ISO 15924 代码)
+const SCRIPT_RANGES: &[(u32, u32, &str)] = &[
+    (0x0041, 0x005A, "Latn"),
+    (0x0061, 0x007A, "Latn"),
+    (0x00AA, 0x00AA, "Latn"),
+    (0x00BA, 0x00BA, "Latn"),
+    (0x00C0, 0x00D6, "Latn"),
+    (0x00D8, 0x00F6, "Latn"),
+    (0x00F8, 0x024F, "Latn"),
+    (0x0250, 0x02AF, "Latn"),
+    (0x0370, 0x03FF, "Grek"),
+    (0x0400, 0x052F, "Cyrl"),
+    (0x0530, 0x058F, "Armn"),
+    (0x0590, 0x05FF, "Hebr"),
+    (0x0600, 0x06FF, "Arab"),
+    (0x0700, 0x074F, "Syrc"),
+    (0x0750, 0x077F, "Arab"),
+    (0x0780, 0x07BF, "Thaa"),
+    (0x08A0, 0x08FF, "Arab"),
+    (0x0900, 0x097F, "Deva"),
+    (0x0980, 0x09FF, "Beng"),
+    (0x0A00, 0x0A7F, "Guru"),
+    (0x0A80, 0x0AFF, "Gujr"),
+    (0x0B00, 0x0B7F, "Orya"),
+    (0x0B80, 0x0BFF, "Taml"),
+    (0x0C00, 0x0C7F, "Telu"),
+    (0x0C80, 0x0CFF, "Knda"),
+    (0x0D00, 0x0D7F, "Mlym"),
+    (0x0D80, 0x0DFF, "Sinh"),
+    (0x0E00, 0x0E7F, "Thai"),
+    (0x0E80, 0x0EFF, "Laoo"),
+    (0x0F00, 0x0FFF, "Tibt"),
+    (0x1000, 0x109F, "Mymr"),
+    (0x10A0, 0x10FF, "Geor"),
+    (0x1100, 0x11FF, "Hang"),
+    (0x1200, 0x139F, "Ethi"),
+    (0x1780, 0x17FF, "Khmr"),
+    (0x1E00, 0x1EFF, "Latn"),
+    (0x1F00, 0x1FFF, "Grek"),
+    (0x2C60, 0x2C7F, "Latn"),
+    (0x2DE0, 0x2DFF, "Cyrl"),
+    (0x2E80, 0x2FDF, "Hani"),
+    (0x3005, 0x3005, "Hani"),
+    (0x3007, 0x3007, "Hani"),
+    (0x3021, 0x3029, "Hani"),
+    (0x3038, 0x303B, "Hani"),
+    (0x3041, 0x3096, "Hira"),
+    (0x309D, 0x309F, "Hira"),
+    (0x30A1, 0x30FA, "Kana"),
+    (0x30FD, 0x30FF, "Kana"),
+    (0x3105, 0x312F, "Bopo"),
+    (0x3131, 0x318E, "Hang"),
+    (0x31A0, 0x31BF, "Bopo"),
+    (0x31F0, 0x31FF, "Kana"),
+    (0x3400, 0x4DBF, "Hani"),
+    (0x4E00, 0x9FFF, "Hani"),
+    (0xA640, 0xA69F, "Cyrl"),
+    (0xA720, 0xA7FF, "Latn"),
+    (0xA960, 0xA97F, "Hang"),
+    (0xAB30, 0xAB6F, "Latn"),
+    (0xAC00, 0xD7FF, "Hang"),
+    (0xF900, 0xFAFF, "Hani"),
+    (0xFB00, 0xFB06, "Latn"),
+    (0xFB1D, 0xFB4F, "Hebr"),
+    (0xFB50, 0xFDFF, "Arab"),
+    (0xFE70, 0xFEFF, "Arab"),
+    (0xFF21, 0xFF3A, "Latn"),
+    (0xFF41, 0xFF5A, "Latn"),
+    (0xFF66, 0xFF9D, "Kana"),
+    (0xFFA0, 0xFFDC, "Hang"),
+    (0x1B000, 0x1B0FF, "Kana"),
+    (0x20000, 0x3FFFF, "Hani"),
+];
+
+/// 表情符号与其修饰符
+pub fn is_emoji(c: char) -> bool {
+    let c = c as u32;
+    (0x1F000..=0x1FAFF).contains(&c) || (0x2600..=0x27BF).contains(&c)
+        || (0xE0020..=0xE007F).contains(&c) || c == 0xFE0F || c == 0x200D
+}
+
+/// 字符所属文字的 ISO 15924 代码，标点、数字等通用字符为 `Zyyy`
+pub fn get_script(c: char) -> &'static str {
+    if is_emoji(c) { return EMOJI; }
+    let code = c as u32;
+    let index = SCRIPT_RANGES.binary_search_by(|&(start, end, _)| {
+        if end < code {
+            std::cmp::Ordering::Less
+        } else if start > code {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    });
+    match index {
+        Ok(index) => SCRIPT_RANGES[index].2,
+        Err(_) => COMMON
+    }
+}
+
+/// 不可见的格式字符，没有字形时不算缺字
+pub fn is_default_ignorable(c: char) -> bool {
+    let c = c as u32;
+    matches!(c, 0x00AD | 0x034F | 0x061C | 0x115F | 0x1160 | 0x17B4 | 0x17B5 | 0x3164 | 0xFEFF | 0xFFA0)
+        || (0x180B..=0x180E).contains(&c) || (0x200B..=0x200F).contains(&c) || (0x202A..=0x202E).contains(&c)
+        || (0x2060..=0x206F).contains(&c) || (0xFE00..=0xFE0F).contains(&c) || (0xE0000..=0xE0FFF).contains(&c)
+}
+
+/// 组合符号、变体选择符、连接符与肤色修饰符跟随前一个字
+fn is_extend(c: char) -> bool {
+    let c = c as u32;
+    (0x0300..=0x036F).contains(&c) || (0xFE00..=0xFE0F).contains(&c) || (0x1F3FB..=0x1F3FF).contains(&c)
+        || (0xE0020..=0xE007F).contains(&c) || c == 0x200C || c == 0x200D || c == 0x20E3
+}
+
+/// 按文字拆分，返回 (起, 止, ISO 15924 代码)，起止为字节位置，
+/// 通用字符并入前一段，位于开头时并入后一段
+pub fn split_script_runs(text: &str) -> Vec<(usize, usize, &'static str)> {
+    let mut runs = Vec::<(usize, usize, &'static str)>::new();
+    for (index, c) in text.char_indices() {
+        let end = index + c.len_utf8();
+        let script = if is_extend(c) { COMMON } else { get_script(c) };
+        match runs.last_mut() {
+            Some((_, last_end, last)) if script == COMMON || *last == script => *last_end = end,
+            Some((_, last_end, last)) if *last == COMMON => {
+                *last_end = end;
+                *last = script;
+            }
+            _ => runs.push((index, end, script))
+        }
+    }
+    runs
+}
+
+/// 用于检查字体是否支持该文字的代表字符
+pub fn get_sample(script: &str) -> Option<char> {
+    let sample = match script {
+        "Latn" => 'a',
+        "Grek" => 'α',
+        "Cyrl" => 'д',
+        "Arab" => 'ب',
+        "Hebr" => 'א',
+        "Hani" => '中',
+        "Hira" => 'あ',
+        "Kana" => 'ア',
+        "Hang" => '한',
+        "Bopo" => 'ㄅ',
+        "Thai" => 'ก',
+        "Deva" => 'क',
+        "Zsye" => '😀',
+        _ => {
+            let (start, _, _) = SCRIPT_RANGES.iter().find(|(_, _, s)| *s == script)?;
+            return std::char::from_u32(*start);
+        }
+    };
+    Some(sample)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_script, split_script_runs};
+
+    #[test]
+    fn test_get_script() {
+        let scripts: Vec<&str> = "aΩж中あア한ไ1😀".chars().map(get_script).collect();
+        assert_eq!(scripts, vec!["Latn", "Grek", "Cyrl", "Hani", "Hira", "Kana", "Hang", "Thai", "Zyyy", "Zsye"]);
+    }
+
+    #[test]
+    fn test_split_script_runs() {
+        let text = "「漢字」かな, abc 👍🏻‍!";
+        let runs: Vec<(&str, &str)> = split_script_runs(text).into_iter().map(|(start, end, script)| (&text[start..end], script)).collect();
+        assert_eq!(runs, vec![("「漢字」", "Hani"), ("かな, ", "Hira"), ("abc ", "Latn"), ("👍🏻\u{200d}!", "Zsye")]);
+        assert_eq!(split_script_runs("1 2"), vec![(0, 3, "Zyyy")]);
+    }
+}
@@ -1,49 +1,80 @@
 use super::super::data::text_data::{TextBlock, TextBlockDetail};
-use regex::Regex;
-
-lazy_static! {
-    static ref REGEX0: Regex = Regex::new(r#"['!)}\],./?|%“”‘’"]"#).unwrap();
-    static ref REGEX1: Regex = Regex::new(r#"[A-Za-z0-9~`@#$^&*(_+={\[\\<>￥（【《]"#).unwrap();
-    static ref REGEX3: Regex = Regex::new(r" ").unwrap();
-}
+use super::kinsoku;
+use super::hyphenation::SOFT_HYPHEN;
+use unicode_linebreak::{break_property, linebreaks, BreakClass, BreakOpportunity};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct Word<'a> {
     pub letters: Vec<(TextBlock, TextBlockDetail<'a>)>,
+    /// 词后必须换行
+    pub mandatory_break: bool,
+}
+
+/// 可以在其后断行的空白：空格、全角空格、各种宽度的空格与制表符，
+/// 不换行空格（GL 类）与前后文字连在一起，作为普通字符处理
+fn is_space(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| {
+        c.is_whitespace() && matches!(break_property(c as u32), BreakClass::Space | BreakClass::After)
+    })
 }
 
 impl<'a> Word<'a> {
-    pub fn pick_words(letters: Vec<(TextBlock, TextBlockDetail<'a>)>) -> Vec<Word<'a>> {
+    /// 按 UAX #14 的断行机会拆分单词，空格单独成词，中日文字符之间按禁则断行；
+    /// 泰文等 SA 类文字需要词典分词，这里不处理，整段作为一个词，超长时按字拆分
+    pub fn pick_words(letters: Vec<(TextBlock, TextBlockDetail<'a>)>, line_break: &str) -> Vec<Word<'a>> {
+        let mut text = String::new();
+        let mut ends = Vec::<usize>::with_capacity(letters.len());
+        for (b, _) in letters.iter() {
+            text.push_str(&b.text);
+            ends.push(text.len());
+        }
+        let breaks: HashMap<usize, BreakOpportunity> = linebreaks(&text).collect();
+        let edges: Vec<(Option<char>, Option<char>)> = letters.iter()
+            .map(|(b, _)| (b.text.chars().next(), b.text.chars().last()))
+            .collect();
+
         let mut blocks = Vec::<(TextBlock, TextBlockDetail)>::new();
         let mut words = Vec::<Word>::new();
-        let mut point = 0usize;
-        let len = letters.len();
-
-        for letter in letters {
-            let (b, d) = letter;
-            let text = &b.text;
-
-            if point == len - 1 {
-                blocks.push((b, d));
-                words.push(Word { letters: blocks.to_vec() })
-            } else if REGEX3.is_match(text) {
-                if blocks.len() > 0 {
-                    words.push(Word { letters: blocks.to_vec() });
-                    blocks = vec![];
+        for (index, letter) in letters.into_iter().enumerate() {
+            let mandatory_break = match breaks.get(&ends[index]) {
+                Some(BreakOpportunity::Mandatory) => true,
+                _ => false
+            };
+            // 软连字符在 UAX #14 中允许在其后断行，转为前一个字的断词位置后不再保留
+            if letter.0.text.chars().all(|c| c == SOFT_HYPHEN) && !letter.0.text.is_empty() {
+                if let (Some(BreakOpportunity::Allowed), Some((_, d))) = (breaks.get(&ends[index]), blocks.last_mut()) {
+                    d.hyphen = letter.1.hyphen;
+                }
+                if mandatory_break {
+                    if let Some(word) = words.last_mut().filter(|_| blocks.is_empty()) {
+                        word.mandatory_break = true;
+                    } else if !blocks.is_empty() {
+                        words.push(Word { letters: blocks.splice(.., vec![]).collect(), mandatory_break });
+                    }
                 }
-                words.push(Word { letters: vec![(b, d)] });
-            } else if REGEX0.is_match(text) {
-                blocks.push((b, d));
-            } else if !REGEX1.is_match(text) {
+                continue;
+            }
+            if is_space(&letter.0.text) {
                 if blocks.len() > 0 {
-                    words.push(Word { letters: blocks.to_vec() });
-                    blocks = vec![];
+                    words.push(Word { letters: blocks.splice(.., vec![]).collect(), mandatory_break: false });
                 }
-                words.push(Word { letters: vec![(b, d)] });
-            } else {
-                blocks.push((b, d));
+                words.push(Word { letters: vec![letter], mandatory_break });
+                continue;
             }
-            point += 1;
+            blocks.push(letter);
+            let is_break = match (edges[index].1, edges.get(index + 1).and_then(|(c, _)| *c)) {
+                (Some(prev), Some(next)) if !mandatory_break && kinsoku::is_cjk_pair(prev, next) => {
+                    kinsoku::can_break(prev, next, line_break)
+                }
+                _ => breaks.get(&ends[index]).is_some()
+            };
+            if is_break {
+                words.push(Word { letters: blocks.splice(.., vec![]).collect(), mandatory_break });
+            }
+        }
+        if blocks.len() > 0 {
+            words.push(Word { letters: blocks, mandatory_break: false });
         }
         words
     }
@@ -51,7 +82,7 @@ impl<'a> Word<'a> {
     pub fn get_spacing(&self) -> f32 {
         let mut width = 0f32;
         for (b, d) in self.letters.iter() {
-            width += d.glyph.get_spacing(b.font_size as f32, &d.writing_mode) + b.font_size as f32 * b.letter_spacing as f32
+            width += d.get_spacing(b.font_size as f32) + b.font_size as f32 * b.letter_spacing as f32
         }
         width
     }
@@ -59,7 +90,7 @@ impl<'a> Word<'a> {
     pub fn is_blank(&self) -> bool {
         if self.letters.len() == 1 {
             if let Some(v) = self.letters.get(0) {
-                REGEX3.is_match(&v.0.text)
+                is_space(&v.0.text)
             } else {
                 false
             }
@@ -75,4 +106,50 @@ impl<'a> std::ops::Deref for Word<'a> {
     fn deref(&self) -> &Self::Target {
         &self.letters
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::Word;
+    use crate::data::text_data::{TextBlock, TextBlockDetail};
+    use crate::open_type_like::glyph::Glyph;
+
+    fn pick_words(text: &str, line_break: &str) -> Vec<(String, bool, bool)> {
+        let glyph = Glyph::default();
+        let letters = text.chars().map(|c| {
+            let block = TextBlock { text: c.to_string(), ..Default::default() };
+            (block, TextBlockDetail::default(&glyph))
+        }).collect();
+        Word::pick_words(letters, line_break).iter().map(|word| {
+            let text: String = word.iter().map(|(b, _)| b.text.as_str()).collect();
+            (text, word.is_blank(), word.mandatory_break)
+        }).collect()
+    }
+
+    fn texts(words: &[(String, bool, bool)]) -> Vec<&str> {
+        words.iter().map(|(text, _, _)| text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_pick_words() {
+        let words = pick_words("a-b c\nd", "normal");
+        assert_eq!(texts(&words), vec!["a-", "b", " ", "c\n", "d"]);
+        assert_eq!(words.iter().map(|(_, blank, _)| *blank).collect::<Vec<bool>>(), vec![false, false, true, false, false]);
+        assert!(words[3].2);
+        // 全角空格与 EN SPACE 可以断行，不换行空格不断
+        assert_eq!(texts(&pick_words("中\u{3000}文", "normal")), vec!["中", "\u{3000}", "文"]);
+        assert!(pick_words("a\u{2002}b", "normal")[1].1);
+        assert_eq!(texts(&pick_words("a\u{a0}b c", "normal")), vec!["a\u{a0}b", " ", "c"]);
+        // 零宽空格提供断行机会，连接符不提供
+        assert_eq!(texts(&pick_words("ab\u{200b}cd\u{2060}ef", "normal")), vec!["ab\u{200b}", "cd\u{2060}ef"]);
+        // 泰文没有分词，整段为一个词
+        assert_eq!(pick_words("ภาษาไทย", "normal").len(), 1);
+    }
+
+    #[test]
+    fn test_pick_cjk_words() {
+        assert_eq!(texts(&pick_words("中文。", "normal")), vec!["中", "文。"]);
+        assert_eq!(texts(&pick_words("「漢字」", "normal")), vec!["「漢", "字」"]);
+        assert_eq!(texts(&pick_words("ファイル", "strict")), vec!["ファ", "イ", "ル"]);
+        assert_eq!(texts(&pick_words("ファイル", "normal")), vec!["フ", "ァ", "イ", "ル"]);
+    }
+}
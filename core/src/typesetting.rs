@@ -1,40 +1,207 @@
 use super::data::text_data::{TextData, TextBlock, ParagraphContent, ParagraphData, TextBlockDetail};
 use super::open_type_like::command::{CommandSegment, CommandList};
-use super::open_type_like::glyph::Glyph;
+use super::open_type_like::glyph::{Glyph, ShapedGlyph, ShapeOption, Variations};
 use super::open_type_like::path::PathData;
 use super::open_type_like::bbox::{BBox, BBoxes};
 use super::open_type_like::word::Word;
+use super::open_type_like::bidi;
+use super::open_type_like::kinsoku;
+use super::open_type_like::hyphenation::{self, HYPHEN, SOFT_HYPHEN};
+use super::open_type_like::knuth_plass::{self, Item};
+use super::open_type_like::script::{is_default_ignorable, split_script_runs};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use crate::data::text_data::WritingMode;
+use crate::error::{Error, Result};
+use serde::Serialize;
+
+/// 字体族中实际选用的字体
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    pub font_name: String,
+    pub weight: u16,
+    pub italic: bool,
+}
+
+impl FontFace {
+    /// 需要的字重不低于 600 而字体不到 600 时合成粗体
+    pub fn is_synthetic_bold(&self, weight: u16) -> bool {
+        weight >= 600 && self.weight < 600
+    }
+
+    pub fn is_synthetic_oblique(&self, italic: bool) -> bool {
+        italic && !self.italic
+    }
+}
+
+/// 没有字体提供字形的字符，`offset` 为字符在文字块中的序号
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingGlyph {
+    pub content_index: usize,
+    pub block_index: usize,
+    pub offset: usize,
+    pub char_code: u32,
+    /// 文字块中指定的字体族
+    pub font_family: String,
+}
 
 pub trait MergedFont {
-    fn char_to_glyph<'a>(&'a self, font_name: String, char: char) -> &'a Box<Glyph>;
+    /// 字体未加载或字形未缓存时返回 `None`，`variations` 为可变字体的轴取值，其他字体忽略
+    fn char_to_glyph<'a>(&'a self, font_name: String, variations: &Variations, char: char) -> Option<&'a Box<Glyph>>;
+
+    /// 对一段文字执行 GSUB/GPOS，字形簇按原文顺序返回，不支持时返回 `None` 按单字排版
+    fn shape(&self, _font_name: String, _text: &str, _option: &ShapeOption) -> Option<Vec<ShapedGlyph>> {
+        None
+    }
+
+    /// 两个字形之间的字偶距，字体单位
+    fn get_kerning(&self, _font_name: String, _left: u32, _right: u32) -> i32 {
+        0
+    }
+
+    fn id_to_glyph<'a>(&'a self, _font_name: String, _variations: &Variations, _glyph_id: u32) -> Option<&'a Box<Glyph>> {
+        None
+    }
+
+    /// 按字重与是否斜体在字体族中选择字体，不支持时直接使用字体族名并视为常规字体
+    fn match_face(&self, font_family: &str, _weight: u16, _italic: bool) -> FontFace {
+        FontFace { font_name: font_family.to_string(), weight: 400, italic: false }
+    }
+
+    /// 按文字（ISO 15924 代码）与语言选择缺字时的回退字体，返回的字体名用于这一段文字，不支持时不变
+    fn match_script_face(&self, font_name: &str, _script: &str, _lang: &str) -> String {
+        font_name.to_string()
+    }
+}
+
+/// shrink-to-fit 的最小缩放比例与二分次数
+const MIN_SCALE: f32 = 0.05;
+const SHRINK_STEPS: usize = 12;
+
+/// 排版结果
+#[derive(Debug)]
+pub struct RenderResult {
+    /// 每个字的字框
+    pub b_boxes: BBoxes,
+    /// 字形路径与绘制命令，路径的键为 (字体名, 字形序号)
+    pub commands: (HashMap<(String, u32), PathData>, Vec<CommandSegment>),
+    /// 最宽的一个字
+    pub min_width: f32,
+    /// 文字框的宽高
+    pub size: (f32, f32),
+    /// 只有 shrink-to-fit 会缩放，其他模式为 1
+    pub scale: f32,
+    /// fixed 与 shrink-to-fit 放不下时为 true
+    pub overflow: bool,
+    /// 缺字的字符
+    pub missing: Vec<MissingGlyph>,
+}
+
+pub fn compute_render_command(text_data: &TextData, font: &impl MergedFont) -> Result<RenderResult> {
+    let missing = find_missing_glyphs(text_data, font);
+    let result = compute_layout(text_data, font)?;
+    if text_data.paragraph.resizing != "shrink-to-fit" || !result.overflow {
+        return Ok(RenderResult { missing, ..result });
+    }
+    // 二分查找能放下的最大缩放比例
+    let mut low = MIN_SCALE;
+    let mut high = 1f32;
+    let mut best = None;
+    for _ in 0..SHRINK_STEPS {
+        let scale = (low + high) / 2f32;
+        let result = compute_layout(&scale_text_data(text_data, scale), font)?;
+        if result.overflow {
+            high = scale;
+        } else {
+            low = scale;
+            best = Some(RenderResult { scale, ..result });
+        }
+    }
+    let result = match best {
+        Some(result) => result,
+        None => RenderResult { scale: MIN_SCALE, ..compute_layout(&scale_text_data(text_data, MIN_SCALE), font)? }
+    };
+    Ok(RenderResult { missing, ..result })
 }
 
-pub fn compute_render_command(text_data: &TextData, font: &impl MergedFont) -> Option<(BBoxes, (HashMap<(String, u32), PathData>, Vec<CommandSegment>), f32, (f32, f32))> {
+/// 回退字体也没有字形的字符，换行符与不可见的格式字符除外，
+/// 字体未加载或字形未缓存时排版无法使用该字符，同样报告
+pub fn find_missing_glyphs(text_data: &TextData, font: &impl MergedFont) -> Vec<MissingGlyph> {
+    let mut missing = Vec::<MissingGlyph>::new();
+    for (content_index, pc) in text_data.paragraph.paragraph_content.iter().enumerate() {
+        for (block_index, b) in pc.blocks.iter().enumerate() {
+            let font_name = font.match_face(&b.font_family, b.font_weight, b.is_italic()).font_name;
+            let variations = b.get_variations();
+            let mut offset = 0usize;
+            for (start, end, script) in split_script_runs(&b.text) {
+                let run_font_name = font.match_script_face(&font_name, script, &b.lang);
+                for c in b.text[start..end].chars() {
+                    let glyph = font.char_to_glyph(run_font_name.clone(), &variations, c);
+                    if glyph.map(|glyph| glyph.glyph_id == 0).unwrap_or(true) && !c.is_control() && !is_default_ignorable(c) {
+                        missing.push(MissingGlyph { content_index, block_index, offset, char_code: c as u32, font_family: b.font_family.clone() });
+                    }
+                    offset += 1;
+                }
+            }
+        }
+    }
+    missing
+}
+
+/// 字号及以像素为单位的尺寸统一缩放
+fn scale_text_data(text_data: &TextData, scale: f32) -> TextData {
+    let mut text_data = text_data.clone();
+    for content in text_data.paragraph.paragraph_content.iter_mut() {
+        content.paragraph_indentation *= scale;
+        for block in content.blocks.iter_mut() {
+            block.font_size *= scale;
+            block.stroke_width *= scale;
+        }
+    }
+    text_data.paragraph.paragraph_spacing *= scale;
+    text_data
+}
+
+/// 按原字号排版，`scale` 为 1，不检查缺字
+fn compute_layout(text_data: &TextData, font: &impl MergedFont) -> Result<RenderResult> {
     let mut width = text_data.width;
     let mut height = text_data.height;
-    let mut font_glyph = HashMap::<(String, String), &Box<Glyph>>::new();
-    for pc in text_data.paragraph.paragraph_content.iter() {
-        for b in pc.blocks.iter() {
+    let mut font_glyph = HashMap::<(String, Variations, String), &Box<Glyph>>::new();
+    for (content_index, pc) in text_data.paragraph.paragraph_content.iter().enumerate() {
+        for (block_index, b) in pc.blocks.iter().enumerate() {
             let text = (&b.text).clone();
-            let mut chars = text.chars();
+            let font_name = font.match_face(&b.font_family, b.font_weight, b.is_italic()).font_name;
+            let variations = b.get_variations();
+            let get_char_glyph = |font_name: &String, c: char| font.char_to_glyph(font_name.clone(), &variations, c).ok_or_else(|| Error::Glyph {
+                path: format!("$.paragraph.contents[{}].blocks[{}].text", content_index, block_index),
+                font_family: font_name.clone(),
+                char: c,
+            });
 
-            while let Some(c) = chars.next() {
-                let font_family = b.font_family.clone();
-                let g = font.char_to_glyph(font_family, c);
-                font_glyph.insert((b.font_family.clone(), c.to_string()), g);
+            // 每段文字按文字与语言选择回退字体
+            for (start, end, script) in split_script_runs(&text) {
+                let run_font_name = font.match_script_face(&font_name, script, &b.lang);
+                for c in text[start..end].chars() {
+                    let g = get_char_glyph(&run_font_name, c)?;
+                    font_glyph.insert((run_font_name.clone(), variations.clone(), c.to_string()), g);
+                }
             }
         }
     }
 //    let font_glyph: &Box<HashMap<(String, String), Glyph>> = &font_glyph;
     let glyph_none = Box::new(Glyph::get_none());
-    let get_glyph = |ff: String, text: String| *font_glyph.get(&(ff, text)).unwrap_or(&&glyph_none);
+    let get_glyph = |ff: String, variations: &Variations, text: String| *font_glyph.get(&(ff, variations.clone(), text)).unwrap_or(&&glyph_none);
+    // 连字符只在有断词机会时查找，字体中没有时不断词
+    let get_hyphen = |b: &TextBlock| font.char_to_glyph(b.font_family.clone(), &b.get_variations(), HYPHEN).map(|glyph| &**glyph);
 
     let mut mix_text_data = Vec::<Vec<(TextBlock, TextBlockDetail)>>::new();
     let ParagraphData {
         writing_mode,
+        direction,
+        line_break,
+        hyphens,
+        line_breaking,
         art_text: _,
         paragraph_content,
         paragraph_spacing,
@@ -52,6 +219,9 @@ pub fn compute_render_command(text_data: &TextData, font: &impl MergedFont) -> O
 
         let line_height = *line_height;
         let mut paragraph_indentation = *paragraph_indentation;
+        let paragraph_text: String = blocks.iter().map(|block| filter_line_break(&block.text)).collect();
+        let levels = bidi::get_levels(&paragraph_text, direction);
+        let mut text_offset = 0usize;
         for block in blocks.iter() {
             let TextBlock {
                 font_family,
@@ -60,32 +230,86 @@ pub fn compute_render_command(text_data: &TextData, font: &impl MergedFont) -> O
                 letter_spacing: _,
                 fill: _,
                 italic: _,
+                font_weight,
+                font_style: _,
+                font_variation_settings: _,
                 stroke: _,
                 stroke_width: _,
-                decoration: _
+                decoration: _,
+                decoration_style: _,
+                decoration_color: _,
+                decoration_skip_ink: _,
+                font_kerning,
+                lang,
             } = block;
-            let mut text_chars = text.chars();
-            while let Some(text) = text_chars.next() {
-                if text == 10 as char || text == 13 as char { continue; }
-                let glyph = get_glyph(font_family.clone(), text.to_string());
-                let text_block_detail = TextBlockDetail {
-                    glyph,
-                    line_height,
-                    paragraph_indentation,
-                    align: align.clone(),
-                    resizing: resizing.clone(),
-                    text_align: text_align.clone(),
-                    paragraph_spacing: *paragraph_spacing,
-                    b_width: 0f32,
-                    position: (0f32, 0f32),
-                    base_line_to_top: 0f32,
-                    base_line_to_bottom: 0f32,
-                    writing_mode: writing_mode.clone(),
+            let text = filter_line_break(text);
+            // 后续排版与绘制都使用实际选用的字体名
+            let face = font.match_face(font_family, *font_weight, block.is_italic());
+            let font_family = &face.font_name;
+            let block_start = mix_text_data.last().unwrap().len();
+            let script_runs = split_script_runs(&text);
+            for (start, end) in split_level_runs(&text, &levels[text_offset.min(levels.len())..]) {
+                let (bidi_level, paragraph_level) = levels.get(text_offset + start).cloned().unwrap_or((0, 0));
+                let option = ShapeOption {
+                    kerning: *font_kerning,
+                    rtl: Some(bidi_level % 2 == 1),
+                    variations: block.get_variations(),
+                };
+                // 同一层级内再按文字分段排版
+                let runs = script_runs.iter()
+                    .map(|(run_start, run_end, script)| ((*run_start).max(start), (*run_end).min(end), *script))
+                    .filter(|(run_start, run_end, _)| run_start < run_end);
+                let shaped = runs.flat_map(|(run_start, run_end, script)| {
+                    let run_font_name = font.match_script_face(font_family, script, lang);
+                    shape_text(&run_font_name, &text[run_start..run_end], &option, writing_mode, font, &get_glyph)
+                }).collect::<Vec<_>>();
+                for (text, glyph, cluster) in shaped {
+                    let mut new_text_block = block.clone();
+                    new_text_block.text = text;
+                    new_text_block.font_family = font_family.clone();
+                    // 软连字符不占位，保留到拆分单词时作为断行机会，记录在此断开时插入的连字符
+                    let is_soft_hyphen = is_soft_hyphen(&new_text_block);
+                    let hyphen = if is_soft_hyphen && hyphens != "none" { get_hyphen(&new_text_block) } else { None };
+                    let text_block_detail = TextBlockDetail {
+                        glyph,
+                        cluster,
+                        kerning: 0,
+                        line_height,
+                        paragraph_indentation: if is_soft_hyphen { 0f32 } else { paragraph_indentation },
+                        align: align.clone(),
+                        resizing: resizing.clone(),
+                        text_align: text_align.clone(),
+                        paragraph_spacing: *paragraph_spacing,
+                        b_width: 0f32,
+                        position: (0f32, 0f32),
+                        base_line_to_top: 0f32,
+                        base_line_to_bottom: 0f32,
+                        writing_mode: writing_mode.clone(),
+                        bidi_level,
+                        paragraph_level,
+                        glyph_offset: 0f32,
+                        hyphen,
+                        synthetic_bold: face.is_synthetic_bold(*font_weight),
+                        synthetic_oblique: face.is_synthetic_oblique(block.is_italic()),
+                    };
+                    mix_text_data.last_mut().unwrap().push((new_text_block, text_block_detail));
+                    if !is_soft_hyphen { paragraph_indentation = 0.0; }
+                }
+            }
+            text_offset += text.len();
+            // 同一文字块内经过 GPOS 处理的字形不再重复计算字偶距，软连字符两侧的字之间计算字偶距
+            let letters = mix_text_data.last_mut().unwrap();
+            for index in block_start.max(1)..letters.len() {
+                if is_soft_hyphen(&letters[index].0) { continue; }
+                let left_index = match letters[..index].iter().rposition(|(b, _)| !is_soft_hyphen(b)) {
+                    Some(left_index) => left_index,
+                    None => continue
                 };
-                let mut new_text_block = block.clone();
-                new_text_block.text = text.to_string();
-                mix_text_data.last_mut()?.push((new_text_block, text_block_detail));
-                paragraph_indentation = 0.0;
+                let (left, right) = letters.split_at_mut(index);
+                let left = &mut left[left_index];
+                let right = &right[0];
+                if left_index >= block_start && !left.1.cluster.is_empty() && !right.1.cluster.is_empty() { continue; }
+                left.1.kerning = get_pair_kerning(font, left, right);
             }
         }
     };
@@ -94,7 +318,7 @@ pub fn compute_render_command(text_data: &TextData, font: &impl MergedFont) -> O
 
     for item in mix_text_data.concat().iter() {
         let (b, d) = item;
-        let width = d.glyph.get_spacing(b.font_size as f32, writing_mode);
+        let width = d.get_spacing(b.font_size as f32);
         if width > min_width {
             min_width = width;
         }
@@ -102,30 +326,44 @@ pub fn compute_render_command(text_data: &TextData, font: &impl MergedFont) -> O
 
     let mut mix_word_data = Vec::<Vec<Word>>::new();
     for mix_text_data_in_line in mix_text_data {
-        let result = Word::pick_words(mix_text_data_in_line);
+        let mut result = Word::pick_words(mix_text_data_in_line, line_break);
+        if hyphens == "auto" {
+            set_hyphen_points(&mut result, &get_hyphen);
+        }
         mix_word_data.push(result);
     }
 
 
     let mut mix_word_data_wrapped = Vec::<Vec<Word>>::new();
 
+    // fixed 与 shrink-to-fit 不撑大文字框，放不下时记为溢出
+    let is_fit = resizing == "fixed" || resizing == "shrink-to-fit";
+    let is_auto_width = resizing == "auto-width" || resizing == "grow-horizontally";
+    let mut overflow = false;
     let limit_width = match writing_mode {
+        _ if is_auto_width => std::f32::INFINITY,
         WritingMode::HorizontalTB => {
             if min_width > width as f32 {
-                width = min_width.ceil();
+                if is_fit { overflow = true; } else { width = min_width.ceil(); }
             }
             width
         }
         _ => {
             if min_width > height as f32 {
-                height = min_width.ceil();
+                if is_fit { overflow = true; } else { height = min_width.ceil(); }
             }
             height
         }
     };
 
+    let wrap = WrapOption {
+        limit: limit_width,
+        optimal: line_breaking == "optimal" && !is_auto_width,
+        shrink: text_align == "justify",
+    };
     for x in &mix_word_data {
-        let result = compute_auto_wrap(limit_width, x, writing_mode);
+        let result = wrap.wrap(x);
+        let result = apply_kinsoku(result, &wrap, line_break);
         for line in result {
             mix_word_data_wrapped.push(line);
         }
@@ -133,6 +371,15 @@ pub fn compute_render_command(text_data: &TextData, font: &impl MergedFont) -> O
 
     std::mem::drop(mix_word_data);
 
+    // 不折行时行方向的尺寸取最长的一行
+    if is_auto_width {
+        let max_line = mix_word_data_wrapped.iter().fold(0f32, |p, line| p.max(get_line_spacing(line))).ceil();
+        match writing_mode {
+            WritingMode::HorizontalTB => width = max_line,
+            _ => height = max_line,
+        }
+    }
+
     let (width, height) = mix_word_data_wrapped.iter().fold(match writing_mode {
         WritingMode::HorizontalTB => (width, 0.0),
         _ => (0.0, height)
@@ -157,78 +404,420 @@ pub fn compute_render_command(text_data: &TextData, font: &impl MergedFont) -> O
 
     std::mem::drop(mix_word_data_wrapped);
 
+    let (mut width, mut height) = apply_align(&mut mix_letter_data_width_position, align, writing_mode, (width, height), (text_data.width, text_data.height));
+
+    if is_fit {
+        match writing_mode {
+            WritingMode::HorizontalTB => overflow |= height > text_data.height,
+            _ => overflow |= width > text_data.width,
+        }
+        // 竖排从右到左时溢出部分在左侧，首列贴住框的右边
+        if let WritingMode::VerticalRL = writing_mode {
+            let shift = text_data.width - width;
+            if shift < 0f32 {
+                mix_letter_data_width_position.iter_mut().for_each(|letter| letter.1.position.0 += shift);
+            }
+        }
+        // 文字框大小不变，完全落在框外的字不再输出
+        width = text_data.width;
+        height = text_data.height;
+        mix_letter_data_width_position.retain(|letter| {
+            let (x1, y1, x2, y2) = get_letter_box(letter);
+            x2 > 0f32 && y2 > 0f32 && x1 < width && y1 < height
+        });
+    }
+
     let mut mat_data = BBoxes::new();
     mix_letter_data_width_position.iter().for_each(|letter| {
-        let (x, y) = letter.1.position;
-        let w = letter.1.b_width;
-        let t = letter.1.base_line_to_top;
-        let b = letter.1.base_line_to_bottom;
-        mat_data.push(match writing_mode {
-            &WritingMode::HorizontalTB => {
-                BBox::new(x.into(), (y - t).into(), (x + w).into(), (y + b).into())
-            }
-            _ => {
-                BBox::new((x - b).into(), y.into(), (x + t).into(), (y + w).into())
-            }
-        })
+        let (x1, y1, x2, y2) = get_letter_box(letter);
+        mat_data.push(BBox::new(x1.into(), y1.into(), x2.into(), y2.into()));
     });
 
     let command_list = CommandList::new(&mix_letter_data_width_position);
     let commands = command_list.get_commands();
 
-    Some((mat_data, commands, min_width, (width, height)))
+    Ok(RenderResult {
+        b_boxes: mat_data,
+        commands,
+        min_width,
+        size: (width, height),
+        scale: 1f32,
+        overflow,
+        missing: vec![],
+    })
+}
+
+/// 字框的左上角与右下角
+fn get_letter_box(letter: &(TextBlock, TextBlockDetail)) -> (f32, f32, f32, f32) {
+    let (x, y) = letter.1.position;
+    let w = letter.1.b_width;
+    let t = letter.1.base_line_to_top;
+    let b = letter.1.base_line_to_bottom;
+    match letter.1.writing_mode {
+        WritingMode::HorizontalTB => (x, y - t, x + w, y + b),
+        _ => (x - b, y, x + t, y + w),
+    }
+}
+
+/// 将文字拆分为字形簇，字体支持时经过 GSUB/GPOS 处理，缺字的字形簇按单字回退
+fn shape_text<'a>(font_family: &String, text: &str, option: &ShapeOption, writing_mode: &WritingMode, font: &'a impl MergedFont, get_glyph: &dyn Fn(String, &Variations, String) -> &'a Box<Glyph>) -> Vec<(String, &'a Glyph, Vec<(&'a Glyph, ShapedGlyph)>)> {
+    let mut result = Vec::<(String, &'a Glyph, Vec<(&'a Glyph, ShapedGlyph)>)>::new();
+    let shaped = match writing_mode {
+        WritingMode::HorizontalTB => font.shape(font_family.clone(), text, option).unwrap_or(vec![]),
+        _ => vec![]
+    };
+    let mut clusters = Vec::<(usize, Vec<ShapedGlyph>)>::new();
+    for item in shaped {
+        match clusters.last_mut() {
+            Some((cluster, glyphs)) if *cluster == item.cluster => glyphs.push(item),
+            _ => clusters.push((item.cluster, vec![item]))
+        }
+    }
+    let mut start = 0usize;
+    for index in 0..clusters.len() {
+        let (cluster, glyphs) = &clusters[index];
+        let end = clusters.get(index + 1).map(|(c, _)| *c).unwrap_or(text.len());
+        if *cluster < start || end <= *cluster { continue; }
+        for c in text[start..*cluster].chars() {
+            result.push((c.to_string(), &**get_glyph(font_family.clone(), &option.variations, c.to_string()), vec![]));
+        }
+        let glyphs = glyphs.iter().map(|g| {
+            font.id_to_glyph(font_family.clone(), &option.variations, g.glyph_id)
+                .filter(|_| g.glyph_id != 0)
+                .map(|glyph| (&**glyph, *g))
+        }).collect::<Option<Vec<(&'a Glyph, ShapedGlyph)>>>();
+        match glyphs {
+            Some(glyphs) => result.push((text[*cluster..end].to_string(), glyphs[0].0, glyphs)),
+            None => {
+                // 缺字的字形簇逐字回退
+                for c in text[*cluster..end].chars() {
+                    result.push((c.to_string(), &**get_glyph(font_family.clone(), &option.variations, c.to_string()), vec![]));
+                }
+            }
+        }
+        start = end;
+    }
+    for c in text[start..].chars() {
+        result.push((c.to_string(), &**get_glyph(font_family.clone(), &option.variations, c.to_string()), vec![]));
+    }
+    result
+}
+
+fn filter_line_break(text: &str) -> String {
+    text.chars().filter(|c| *c != 10 as char && *c != 13 as char).collect()
+}
+
+/// 按嵌入层级将文字块拆分为若干段，每段单独排版
+fn split_level_runs(text: &str, levels: &[(u8, u8)]) -> Vec<(usize, usize)> {
+    let mut runs = Vec::<(usize, usize)>::new();
+    let mut start = 0usize;
+    for (index, _) in text.char_indices() {
+        if index > start && levels.get(index) != levels.get(start) {
+            runs.push((start, index));
+            start = index;
+        }
+    }
+    if start < text.len() {
+        runs.push((start, text.len()));
+    }
+    runs
+}
+
+fn is_soft_hyphen(block: &TextBlock) -> bool {
+    !block.text.is_empty() && block.text.chars().all(|c| c == SOFT_HYPHEN)
+}
+
+/// 相邻字形使用同一字体且字号相同时才计算字偶距
+fn get_pair_kerning(font: &impl MergedFont, left: &(TextBlock, TextBlockDetail), right: &(TextBlock, TextBlockDetail)) -> i32 {
+    let (lb, ld) = left;
+    let (rb, rd) = right;
+    if !lb.font_kerning || !rb.font_kerning || lb.font_size != rb.font_size || ld.bidi_level != rd.bidi_level { return 0; }
+    match ld.writing_mode {
+        WritingMode::HorizontalTB => {}
+        _ => return 0
+    }
+    let l = ld.cluster.last().map(|(g, _)| *g).unwrap_or(ld.glyph);
+    let r = rd.cluster.first().map(|(g, _)| *g).unwrap_or(rd.glyph);
+    if l.font_name != r.font_name || l.variations != r.variations || l.glyph_id == 0 || r.glyph_id == 0 { return 0; }
+    font.get_kerning(l.font_name.clone(), l.glyph_id, r.glyph_id)
 }
 
-fn compute_auto_wrap<'a>(limit: f32, words: &Vec<Word<'a>>, writing_mode: &WritingMode) -> Vec<Vec<Word<'a>>> {
+fn compute_auto_wrap<'a>(limit: f32, words: &Vec<Word<'a>>) -> Vec<Vec<Word<'a>>> {
     let mut wrapped_all = Vec::<Vec<Word>>::new();
-    let wrapped_words = words.iter().map(|word| {
+    let mut words: VecDeque<Word> = words.iter().cloned().collect();
+    let mut line_width = 0f32;
+    let mut new_line = true;
+    while let Some(word) = words.pop_front() {
         let word_width = word.get_spacing();
-        if limit < word_width.ceil() as f32 {
-            let mut split_words = Vec::<Word>::new();
-            let mut split_letters = Vec::<(TextBlock, TextBlockDetail)>::new();
-            word.iter().fold(0f32, |p, c| {
-                let c_width = c.1.glyph.get_spacing(c.0.font_size as f32, writing_mode) + c.0.font_size as f32 * c.0.letter_spacing as f32;
-                if (p + c_width).ceil() as f32 > limit {
-                    if split_letters.len() > 0 {
-                        split_words.push(Word { letters: split_letters.splice(.., vec![]).collect() });
-                    }
-                    split_letters.push((c.0.clone(), c.1.clone()));
-                    return c_width;
+        if !new_line && (line_width + word_width).ceil() <= limit {
+            new_line = word.mandatory_break;
+            line_width += word_width;
+            wrapped_all.last_mut().unwrap().push(word);
+            continue;
+        }
+        // 放不下时先尝试断词，剩余部分放到下一行
+        if !new_line {
+            if let Some((head, tail)) = split_hyphen(&word, limit - line_width) {
+                wrapped_all.last_mut().unwrap().push(head);
+                words.push_front(tail);
+                new_line = true;
+                continue;
+            }
+        }
+        new_line = word.mandatory_break;
+        let word = if word_width.ceil() > limit {
+            let (head, tail) = split_long_word(&word, limit);
+            if let Some(tail) = tail {
+                words.push_front(tail);
+                new_line = true;
+            }
+            head
+        } else {
+            word
+        };
+        line_width = word.get_spacing();
+        wrapped_all.push(vec![word]);
+    }
+    wrapped_all
+}
+
+/// 在可用宽度内找最后一个断词位置，前半部分末尾插入连字符
+fn split_hyphen<'a>(word: &Word<'a>, available: f32) -> Option<(Word<'a>, Word<'a>)> {
+    let mut width = 0f32;
+    let mut point = None;
+    for (index, (b, d)) in word.iter().enumerate() {
+        width += d.get_spacing(b.font_size) + b.font_size * b.letter_spacing;
+        if index + 1 == word.len() { break; }
+        if let Some(hyphen) = d.hyphen {
+            let hyphen_width = hyphen.get_spacing(b.font_size, &d.writing_mode);
+            if (width - d.get_kerning(b.font_size) + hyphen_width).ceil() > available { break; }
+            point = Some((index, hyphen));
+        }
+    }
+    let (index, hyphen) = point?;
+    let mut head = word.letters[..=index].to_vec();
+    push_hyphen(&mut head, hyphen);
+    let tail = word.letters[index + 1..].to_vec();
+    Some((Word { letters: head, mandatory_break: false }, Word { letters: tail, mandatory_break: word.mandatory_break }))
+}
+
+/// 在断词处的字后面补一个连字符
+fn push_hyphen<'a>(letters: &mut Vec<(TextBlock, TextBlockDetail<'a>)>, hyphen: &'a Glyph) {
+    let (mut hyphen_block, mut hyphen_detail) = letters.last().unwrap().clone();
+    letters.last_mut().unwrap().1.kerning = 0;
+    hyphen_block.text = HYPHEN.to_string();
+    hyphen_detail.glyph = hyphen;
+    hyphen_detail.cluster = vec![];
+    hyphen_detail.kerning = 0;
+    hyphen_detail.paragraph_indentation = 0f32;
+    hyphen_detail.hyphen = None;
+    letters.push((hyphen_block, hyphen_detail));
+}
+
+/// 按 Knuth–Plass 算法对整段求最优断行，单词作为盒子，空格与词间作为粘连，断词位置作为罚点，
+/// 无法在行宽内排下时回退到逐行折行
+fn compute_optimal_wrap<'a>(limit: f32, words: &Vec<Word<'a>>, shrink: bool) -> Vec<Vec<Word<'a>>> {
+    if words.is_empty() { return compute_auto_wrap(limit, words); }
+    // pieces 为断词后的片段，joined 表示与前一片段属于同一单词
+    let mut pieces = Vec::<(Word, bool)>::new();
+    let mut items = Vec::<Item>::new();
+    let mut item_pieces = Vec::<usize>::new();
+    let push_item = |items: &mut Vec<Item>, item_pieces: &mut Vec<usize>, item: Item, count: usize| {
+        items.push(item);
+        item_pieces.push(count);
+    };
+    // 段落或强制换行的结尾用无限伸展的粘连填满
+    let push_fill = |items: &mut Vec<Item>, item_pieces: &mut Vec<usize>, count: usize| {
+        push_item(items, item_pieces, Item::Penalty { width: 0f32, penalty: knuth_plass::INFINITY, flagged: false }, count);
+        push_item(items, item_pieces, Item::Glue { width: 0f32, stretch: knuth_plass::INFINITY, shrink: 0f32 }, count);
+        push_item(items, item_pieces, Item::Penalty { width: 0f32, penalty: -knuth_plass::INFINITY, flagged: false }, count);
+    };
+    for (index, word) in words.iter().enumerate() {
+        let font_size = word.letters.last().map(|(b, _)| b.font_size).unwrap_or(0f32);
+        if word.is_blank() {
+            push_item(&mut items, &mut item_pieces, Item::Box(word.get_spacing()), pieces.len());
+            pieces.push((word.clone(), false));
+            let width = word.get_spacing();
+            push_item(&mut items, &mut item_pieces, Item::Glue { width: 0f32, stretch: font_size * 0.2f32, shrink: if shrink { width / 3f32 } else { 0f32 } }, pieces.len());
+        } else {
+            if index > 0 && !words[index - 1].is_blank() && !words[index - 1].mandatory_break {
+                push_item(&mut items, &mut item_pieces, Item::Glue { width: 0f32, stretch: font_size * 0.2f32, shrink: 0f32 }, pieces.len());
+            }
+            let mut start = 0usize;
+            for (letter_index, (b, d)) in word.iter().enumerate() {
+                if letter_index + 1 == word.len() { break; }
+                if let Some(hyphen) = d.hyphen {
+                    let piece = Word { letters: word.letters[start..=letter_index].to_vec(), mandatory_break: false };
+                    push_item(&mut items, &mut item_pieces, Item::Box(piece.get_spacing() - d.get_kerning(b.font_size)), pieces.len());
+                    pieces.push((piece, start > 0));
+                    let width = hyphen.get_spacing(b.font_size, &d.writing_mode);
+                    push_item(&mut items, &mut item_pieces, Item::Penalty { width, penalty: 50f32, flagged: true }, pieces.len());
+                    start = letter_index + 1;
                 }
-                split_letters.push((c.0.clone(), c.1.clone()));
-                p + c_width
-            });
-            if split_letters.len() > 0 {
-                split_words.push(Word { letters: split_letters.splice(.., vec![]).collect() })
             }
-            return split_words;
+            let piece = Word { letters: word.letters[start..].to_vec(), mandatory_break: word.mandatory_break };
+            push_item(&mut items, &mut item_pieces, Item::Box(piece.get_spacing()), pieces.len());
+            pieces.push((piece, start > 0));
         }
-        vec![word.clone()]
-    });
+        if word.mandatory_break && index + 1 < words.len() {
+            push_fill(&mut items, &mut item_pieces, pieces.len());
+        }
+    }
+    push_fill(&mut items, &mut item_pieces, pieces.len());
+
+    // 先按常规容差求解，失败时放宽容差，仍有单词超出行宽时回退
+    let breaks = match knuth_plass::compute_breaks(&items, limit, 10f32)
+        .or_else(|| knuth_plass::compute_breaks(&items, limit, knuth_plass::INFINITY)) {
+        Some(breaks) => breaks,
+        None => return compute_auto_wrap(limit, words)
+    };
+    let mut wrapped_all = Vec::<Vec<Word>>::new();
+    let mut start = 0usize;
+    for position in breaks {
+        let end = item_pieces[position];
+        let mut line = Vec::<Word>::new();
+        for (piece, joined) in pieces[start..end].iter() {
+            match line.last_mut() {
+                Some(last) if *joined => {
+                    last.letters.extend(piece.letters.iter().cloned());
+                    last.mandatory_break = piece.mandatory_break;
+                }
+                _ => line.push(piece.clone())
+            }
+        }
+        if let Item::Penalty { flagged: true, .. } = items[position] {
+            if let Some(last) = line.last_mut() {
+                if let Some(hyphen) = last.letters.last().and_then(|(_, d)| d.hyphen) {
+                    push_hyphen(&mut last.letters, hyphen);
+                }
+            }
+        }
+        start = end;
+        if !line.is_empty() { wrapped_all.push(line); }
+    }
+    wrapped_all
+}
 
-    let mut flat_wrapped_words = Vec::<Word>::new();
+/// 超过行宽的单词优先断词，否则按字拆分
+fn split_long_word<'a>(word: &Word<'a>, limit: f32) -> (Word<'a>, Option<Word<'a>>) {
+    if let Some((head, tail)) = split_hyphen(word, limit) {
+        return (head, Some(tail));
+    }
+    let mut width = 0f32;
+    let mut index = 0usize;
+    for (b, d) in word.iter() {
+        width += d.get_spacing(b.font_size as f32) + b.font_size as f32 * b.letter_spacing as f32;
+        if index > 0 && width.ceil() > limit { break; }
+        index += 1;
+    }
+    if index >= word.len() {
+        return (word.clone(), None);
+    }
+    let head = Word { letters: word.letters[..index].to_vec(), mandatory_break: false };
+    let tail = Word { letters: word.letters[index..].to_vec(), mandatory_break: word.mandatory_break };
+    (head, Some(tail))
+}
 
-    for item1 in wrapped_words {
-        for item2 in item1 {
-            flat_wrapped_words.push(item2)
+/// 标记单词中可以断词的位置，单词首尾的标点不参与断词，含软连字符的单词只在软连字符处断词
+fn set_hyphen_points<'a>(words: &mut Vec<Word<'a>>, get_hyphen: &dyn Fn(&TextBlock) -> Option<&'a Glyph>) {
+    for word in words.iter_mut() {
+        let letters = &mut word.letters;
+        if letters.iter().any(|(_, d)| d.hyphen.is_some()) { continue; }
+        let is_alphabetic = |text: &String| text.chars().all(|c| c.is_alphabetic());
+        let start = match letters.iter().position(|(b, _)| is_alphabetic(&b.text)) {
+            Some(start) => start,
+            None => continue
+        };
+        let end = letters.iter().rposition(|(b, _)| is_alphabetic(&b.text)).unwrap() + 1;
+        if end - start < 2 || !letters[start..end].iter().all(|(b, _)| is_alphabetic(&b.text)) { continue; }
+        let patterns = match hyphenation::get_patterns(&letters[start].0.lang) {
+            Some(patterns) => patterns,
+            None => continue
+        };
+        let text: String = letters[start..end].iter().map(|(b, _)| b.text.as_str()).collect();
+        let points = patterns.hyphenate(&text);
+        let mut char_index = 0usize;
+        for (b, d) in letters[start..end].iter_mut() {
+            char_index += b.text.chars().count();
+            if points.contains(&char_index) {
+                d.hyphen = get_hyphen(b);
+            }
         }
     }
+}
+
+fn get_line_spacing(line: &Vec<Word>) -> f32 {
+    line.iter().fold(0f32, |p, word| p + word.get_spacing())
+}
+
+fn get_letter_char(line: &Vec<Word>, first: bool) -> Option<char> {
+    if first {
+        line.first()?.first()?.0.text.chars().next()
+    } else {
+        line.last()?.last()?.0.text.chars().last()
+    }
+}
+
+/// 折行方式，禁则处理调整后剩余的行用同样的方式重新折行
+struct WrapOption {
+    limit: f32,
+    /// lineBreaking 为 optimal 且不是 auto-width
+    optimal: bool,
+    /// 两端对齐时允许压缩空格
+    shrink: bool,
+}
 
-    flat_wrapped_words.iter().fold(0f32, |p, c| {
-        let word_width = c.get_spacing();
-        if (p + word_width).ceil() > limit as f32 {
-            wrapped_all.push(vec![c.clone()]);
-            return word_width;
+impl WrapOption {
+    fn wrap<'a>(&self, words: &Vec<Word<'a>>) -> Vec<Vec<Word<'a>>> {
+        if self.optimal {
+            compute_optimal_wrap(self.limit, words, self.shrink)
         } else {
-            if let Some(last) = wrapped_all.last_mut() {
-                last.push(c.clone());
-            } else {
-                wrapped_all.push(vec![c.clone()]);
+            compute_auto_wrap(self.limit, words)
+        }
+    }
+}
+
+/// 禁则处理，下一行行首的句读点悬挂到本行末尾（burasage），
+/// 行首禁则或行尾禁则的字符连同本行最后一个词移到下一行（oidashi）
+fn apply_kinsoku<'a>(lines: Vec<Vec<Word<'a>>>, wrap: &WrapOption, line_break: &str) -> Vec<Vec<Word<'a>>> {
+    let limit = wrap.limit;
+    let mut lines = lines;
+    let mut index = 0usize;
+    while index + 1 < lines.len() {
+        if lines[index].last().map(|word| word.mandatory_break).unwrap_or(true) {
+            index += 1;
+            continue;
+        }
+        let hanging = lines[index + 1].first().and_then(|word| {
+            let (b, d) = word.last()?;
+            if !kinsoku::is_hanging(b.text.chars().last()?, line_break) { return None; }
+            Some(word.get_spacing() - d.get_spacing(b.font_size) - b.font_size * b.letter_spacing)
+        });
+        if let Some(width) = hanging {
+            if (get_line_spacing(&lines[index]) + width).ceil() <= limit {
+                let word = lines[index + 1].remove(0);
+                lines[index].push(word);
+                let rest: Vec<Word> = lines.drain(index + 1..).flatten().collect();
+                lines.extend(wrap.wrap(&rest));
+                index += 1;
+                continue;
             }
-            return p + word_width;
         }
-    });
-    wrapped_all
+        let start_prohibited = get_letter_char(&lines[index + 1], true)
+            .map(|c| kinsoku::is_line_start_prohibited(c, line_break)).unwrap_or(false);
+        let end_prohibited = get_letter_char(&lines[index], false)
+            .map(|c| kinsoku::is_line_end_prohibited(c, line_break)).unwrap_or(false);
+        if (start_prohibited || end_prohibited) && lines[index].len() > 1 {
+            let word = lines[index].pop().unwrap();
+            lines[index + 1].insert(0, word);
+            if get_line_spacing(&lines[index + 1]).ceil() > limit {
+                let rest: Vec<Word> = lines.drain(index + 1..).flatten().collect();
+                lines.extend(wrap.wrap(&rest));
+            }
+        }
+        index += 1;
+    }
+    lines
 }
 
 enum JustifyText {
@@ -284,6 +873,29 @@ fn compute_box(line_data: &Vec<Word>, width: f32, height: f32) -> (f32, f32) {
     }
 }
 
+/// 按 align 在框内沿块方向移动所有行，返回移动后的宽高
+/// 横排在 height 内上下对齐；竖排在 width 内对齐，top 对应首行一侧
+fn apply_align(letters: &mut Vec<(TextBlock, TextBlockDetail)>, align: &str, writing_mode: &WritingMode, content: (f32, f32), frame: (f32, f32)) -> (f32, f32) {
+    let (width, height) = content;
+    let (free, size) = match writing_mode {
+        WritingMode::HorizontalTB => (frame.1 - height, (width, height.max(frame.1))),
+        _ => (frame.0 - width, (width.max(frame.0), height)),
+    };
+    if free <= 0f32 { return content; }
+    let shift = match (align, writing_mode) {
+        ("top", WritingMode::VerticalRL) | ("bottom", WritingMode::HorizontalTB) | ("bottom", WritingMode::VerticalLR) => free,
+        ("top", _) | ("bottom", _) => 0f32,
+        _ => free / 2f32
+    };
+    for (_, detail) in letters.iter_mut() {
+        match writing_mode {
+            WritingMode::HorizontalTB => detail.position.1 += shift,
+            _ => detail.position.0 += shift,
+        }
+    }
+    size
+}
+
 /// 计算每个字形的位置
 fn compute_glyph_position<'a>(line_data: &Vec<Word<'a>>, option: (f32, f32, String, f32), index: usize, writing_mode: &WritingMode) -> (Vec<(TextBlock, TextBlockDetail<'a>)>, (f32, f32, String, f32)) {
     let (width, height, text_align, mut offset) = option;
@@ -317,7 +929,8 @@ fn compute_glyph_position<'a>(line_data: &Vec<Word<'a>>, option: (f32, f32, Stri
         width
     };
     let word = Word {
-        letters: Vec::new()
+        letters: Vec::new(),
+        mandatory_break: false,
     };
 
     let ttb: TextBlock = Default::default();
@@ -325,8 +938,16 @@ fn compute_glyph_position<'a>(line_data: &Vec<Word<'a>>, option: (f32, f32, Stri
     let ttd = TextBlockDetail::default(&temp_glyph);
     let word = line_data.last().unwrap_or(&word);
     let default_letter = (ttb, ttd);
-    let (ttb, _ttd) = word.letters.last().unwrap_or(&default_letter);
-    line_width -= ttb.letter_spacing * ttb.font_size;
+    let (ttb, ttd) = word.letters.last().unwrap_or(&default_letter);
+    line_width -= ttb.letter_spacing * ttb.font_size + ttd.get_kerning(ttb.font_size);
+    let line_limit = match writing_mode {
+        &WritingMode::HorizontalTB => width,
+        _ => height,
+    };
+    // 悬挂的句读点不参与对齐
+    if line_width.ceil() > line_limit && ttb.text.chars().last().map(kinsoku::is_hanging_punctuation).unwrap_or(false) {
+        line_width -= ttd.get_spacing(ttb.font_size) - ttd.get_kerning(ttb.font_size);
+    }
     let diff_width = match writing_mode {
         &WritingMode::HorizontalTB => width - line_width,
         _ => height - line_width,
@@ -334,7 +955,15 @@ fn compute_glyph_position<'a>(line_data: &Vec<Word<'a>>, option: (f32, f32, Stri
     let mut padding_left = 0f32;
     let mut text_align_result = JustifyText::None;
 
-    match text_align.as_ref() {
+    // start/end 按段落方向对齐
+    let is_rtl_paragraph = max_letter.1.paragraph_level % 2 == 1;
+    let resolved_text_align = match (text_align.as_ref(), is_rtl_paragraph) {
+        ("start", false) | ("end", true) => "left",
+        ("start", true) | ("end", false) => "right",
+        (value, _) => value
+    };
+
+    match resolved_text_align {
         "right" => { padding_left = diff_width }
         "center" => { padding_left = diff_width / 2f32 }
         "justify" => {
@@ -369,19 +998,18 @@ fn compute_glyph_position<'a>(line_data: &Vec<Word<'a>>, option: (f32, f32, Stri
         &WritingMode::VerticalLR => (base_line_to_bottom + offset + if index == 0usize { 0f32 } else { base_line_to_top }, 0f32 + padding_left),
         &WritingMode::VerticalRL => (width - (base_line_to_top + offset), 0f32 + padding_left)
     };
+    let mut paragraph_indentation = 0f32;
 
     line_data.iter().for_each(|word| {
         let mut w_index = 0usize;
         word.iter().for_each(|letter| {
             let font_size = letter.0.font_size;
-            let letter_spacing =
-                if l_index == line_data.len() - 1 && w_index == word.len() - 1 {
-                    0f32
-                } else {
-                    letter.0.letter_spacing
-                };
-            let advance_width = letter.1.glyph.get_spacing(font_size as f32, writing_mode);
-            let paragraph_indentation = letter.1.paragraph_indentation;
+            let is_line_end = l_index == line_data.len() - 1 && w_index == word.len() - 1;
+            let letter_spacing = if is_line_end { 0f32 } else { letter.0.letter_spacing };
+            let kerning = letter.1.get_kerning(font_size);
+            let mut advance_width = letter.1.get_spacing(font_size as f32);
+            if is_line_end { advance_width -= kerning; }
+            paragraph_indentation += letter.1.paragraph_indentation;
             let mut b_width = advance_width + font_size as f32 * letter_spacing as f32;
 
             match text_align_result {
@@ -397,25 +1025,48 @@ fn compute_glyph_position<'a>(line_data: &Vec<Word<'a>>, option: (f32, f32, Stri
                 }
                 JustifyText::None => {}
             };
-            match writing_mode {
-                &WritingMode::HorizontalTB => { start_position.0 += paragraph_indentation as f32; }
-                _ => { start_position.1 += paragraph_indentation as f32; }
-            };
             let text_block = letter.0.clone();
             let mut text_block_detail = letter.1.clone();
             text_block_detail.b_width = b_width.into();
-            text_block_detail.position = (start_position.0.into(), start_position.1.into());
             text_block_detail.base_line_to_top = base_line_to_top;
             text_block_detail.base_line_to_bottom = base_line_to_bottom;
+            if let &WritingMode::HorizontalTB = writing_mode {
+                if text_block_detail.is_rtl() {
+                    let glyph_width = letter.1.get_spacing(font_size as f32) - kerning;
+                    text_block_detail.glyph_offset = b_width - glyph_width;
+                }
+            }
             flat_data.push((text_block, text_block_detail));
-            match writing_mode {
-                &WritingMode::HorizontalTB => { start_position.0 += b_width as f32; }
-                _ => { start_position.1 += b_width as f32; }
-            };
             w_index += 1;
         });
         l_index += 1;
     });
+
+    let order = match writing_mode {
+        &WritingMode::HorizontalTB => {
+            let mut levels: Vec<u8> = flat_data.iter().map(|(_, d)| d.bidi_level).collect();
+            // L1：行尾空白使用段落层级
+            for (index, (b, d)) in flat_data.iter().enumerate().rev() {
+                if !b.text.chars().all(|c| c.is_whitespace()) { break; }
+                levels[index] = d.paragraph_level;
+            }
+            bidi::reorder(&levels)
+        }
+        _ => (0..flat_data.len()).collect()
+    };
+    match writing_mode {
+        &WritingMode::HorizontalTB if is_rtl_paragraph => { start_position.0 -= paragraph_indentation; }
+        &WritingMode::HorizontalTB => { start_position.0 += paragraph_indentation; }
+        _ => { start_position.1 += paragraph_indentation; }
+    };
+    let mut flat_data: Vec<(TextBlock, TextBlockDetail)> = order.iter().map(|index| flat_data[*index].clone()).collect();
+    for (_, text_block_detail) in flat_data.iter_mut() {
+        text_block_detail.position = (start_position.0.into(), start_position.1.into());
+        match writing_mode {
+            &WritingMode::HorizontalTB => { start_position.0 += text_block_detail.b_width as f32; }
+            _ => { start_position.1 += text_block_detail.b_width as f32; }
+        };
+    }
     offset += match writing_mode {
         &WritingMode::HorizontalTB => {
             base_line_to_top + if index == 0 { 0f32 } else { base_line_to_bottom }
@@ -428,4 +1079,236 @@ fn compute_glyph_position<'a>(line_data: &Vec<Word<'a>>, option: (f32, f32, Stri
         }
     };
     (flat_data, (width, height, text_align, offset))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 每个字宽度为字号的字形
+    fn get_test_glyph() -> Glyph {
+        Glyph { advance_width: 1000, units_per_em: 1000, ascender: 800, descender: -200, ..Default::default() }
+    }
+
+    fn get_words<'a>(text: &str, glyph: &'a Glyph, line_break: &str) -> Vec<Word<'a>> {
+        let letters = text.chars().map(|c| {
+            let block = TextBlock { text: c.to_string(), font_size: 10f32, ..Default::default() };
+            (block, TextBlockDetail::default(glyph))
+        }).collect();
+        Word::pick_words(letters, line_break)
+    }
+
+    /// 只有 `chars` 中的字符有字形的字体
+    struct TestFont(HashMap<char, Box<Glyph>>);
+
+    impl TestFont {
+        fn new(chars: &str) -> Self {
+            TestFont(chars.chars().map(|c| {
+                let advance_width = if is_default_ignorable(c) { 0 } else { 1000 };
+                (c, Box::new(Glyph { char_code: Some(c as u32), glyph_id: c as u32, advance_width, font_name: "test".to_string(), ..get_test_glyph() }))
+            }).collect())
+        }
+    }
+
+    impl MergedFont for TestFont {
+        fn char_to_glyph<'a>(&'a self, _font_name: String, _variations: &Variations, c: char) -> Option<&'a Box<Glyph>> {
+            self.0.get(&c)
+        }
+    }
+
+    /// 绘制的字形，以字形序号表示的字符
+    fn get_drawn_text(commands: &[CommandSegment]) -> String {
+        commands.iter().filter_map(|command| match command {
+            CommandSegment::Use(_, glyph_id, _) => std::char::from_u32(*glyph_id),
+            _ => None
+        }).collect()
+    }
+
+    /// 按基线位置分行的绘制结果
+    fn get_drawn_lines(commands: &[CommandSegment]) -> Vec<String> {
+        let mut lines = Vec::<(f32, String)>::new();
+        let mut y = 0f32;
+        for command in commands {
+            match command {
+                CommandSegment::Transform(transform, _) => y = transform.f,
+                CommandSegment::Use(_, glyph_id, _) => {
+                    let c = std::char::from_u32(*glyph_id).unwrap();
+                    match lines.last_mut() {
+                        Some((line_y, text)) if *line_y == y => text.push(c),
+                        _ => lines.push((y, c.to_string()))
+                    }
+                }
+                _ => {}
+            }
+        }
+        lines.into_iter().map(|(_, text)| text).collect()
+    }
+
+    fn layout_lines(text: &str, width: f32, paragraph: &str) -> Vec<String> {
+        let source = format!(r#"{{"width": {}, "height": 100, "paragraph": {{{}"contents": [{{"blocks": [{{"text": {}, "fontSize": 10}}]}}]}}}}"#, width, paragraph, serde_json::to_string(text).unwrap());
+        let text_data = TextData::parse(&source).unwrap();
+        let font = TestFont::new("abc -\u{ad}\u{200b}\u{200d}");
+        let result = compute_render_command(&text_data, &font).unwrap();
+        get_drawn_lines(&result.commands.1)
+    }
+
+    fn get_line_texts(lines: &[Vec<Word>]) -> Vec<String> {
+        lines.iter().map(|line| line.iter().flat_map(|word| word.iter().map(|(b, _)| b.text.clone())).collect()).collect()
+    }
+
+    #[test]
+    fn test_kinsoku_optimal_wrap() {
+        let glyph = get_test_glyph();
+        let words = get_words("中中中中字。aa a aa aaaa aa a", &glyph, "normal");
+        let wrap = WrapOption { limit: 50f32, optimal: true, shrink: false };
+        let lines = wrap.wrap(&words);
+        assert_eq!(get_line_texts(&lines)[..2], ["中中中中", "字。aa "]);
+        // 句号悬挂到第一行末尾，其余部分仍按最优断行
+        let lines = get_line_texts(&apply_kinsoku(lines, &wrap, "normal"));
+        let rest = get_line_texts(&wrap.wrap(&get_words("aa a aa aaaa aa a", &glyph, "normal")));
+        assert_eq!(lines[0], "中中中中字。");
+        assert_eq!(lines[1..], rest[..]);
+        let greedy = WrapOption { optimal: false, ..wrap };
+        assert_ne!(get_line_texts(&greedy.wrap(&get_words("aa a aa aaaa aa a", &glyph, "normal"))), rest);
+        // strict 不悬挂，整个词移到下一行
+        let words = get_words("中中中中字。aa", &glyph, "strict");
+        let lines = apply_kinsoku(wrap.wrap(&words), &wrap, "strict");
+        assert_eq!(get_line_texts(&lines), vec!["中中中中", "字。aa"]);
+    }
+
+    #[test]
+    fn test_hyphen_glyph() {
+        // 字体中没有连字符时正常排版，只是不断词
+        let text_data = TextData::parse(r#"{"width": 60, "height": 100, "paragraph": {"contents": [{"blocks": [{"text": "exten\u00adsive", "fontSize": 10}]}]}}"#).unwrap();
+        let result = compute_render_command(&text_data, &TestFont::new("extnsiv\u{ad}")).unwrap();
+        assert_eq!(get_drawn_text(&result.commands.1), "extensive");
+        let result = compute_render_command(&text_data, &TestFont::new("extnsiv\u{ad}-")).unwrap();
+        assert_eq!(get_drawn_text(&result.commands.1), "exten-sive");
+    }
+
+    #[test]
+    fn test_format_characters() {
+        // 软连字符是普通的断行机会，在此断开时显示连字符，不断开时不显示
+        assert_eq!(layout_lines("a bb\u{ad}cccc", 60f32, ""), vec!["a bb-", "cccc"]);
+        assert_eq!(layout_lines("ab\u{ad}c", 40f32, ""), vec!["abc"]);
+        assert_eq!(layout_lines("a bb\u{ad}cccc", 60f32, r#""hyphens": "none", "#), vec!["a ", "bbcccc"]);
+        // 零宽空格可以断行，零宽连接符不断行
+        assert_eq!(layout_lines("a aa\u{200b}bb", 40f32, ""), vec!["a aa\u{200b}", "bb"]);
+        assert_eq!(layout_lines("a aa\u{200d}bb", 40f32, ""), vec!["a ", "aa\u{200d}bb"]);
+    }
+
+    #[test]
+    fn test_apply_align() {
+        let glyph = get_test_glyph();
+        let shift = |align: &str, writing_mode: WritingMode, content: (f32, f32)| {
+            let mut letters = vec![(TextBlock::default(), TextBlockDetail::default(&glyph))];
+            let size = apply_align(&mut letters, align, &writing_mode, content, (100f32, 100f32));
+            (letters[0].1.position, size)
+        };
+        assert_eq!(shift("top", WritingMode::HorizontalTB, (100f32, 12f32)), ((0f32, 0f32), (100f32, 100f32)));
+        assert_eq!(shift("middle", WritingMode::HorizontalTB, (100f32, 12f32)), ((0f32, 44f32), (100f32, 100f32)));
+        assert_eq!(shift("bottom", WritingMode::HorizontalTB, (100f32, 12f32)), ((0f32, 88f32), (100f32, 100f32)));
+        // 竖排沿水平方向对齐，top 为首行一侧
+        assert_eq!(shift("top", WritingMode::VerticalRL, (12f32, 100f32)), ((88f32, 0f32), (100f32, 100f32)));
+        assert_eq!(shift("bottom", WritingMode::VerticalRL, (12f32, 100f32)), ((0f32, 0f32), (100f32, 100f32)));
+        assert_eq!(shift("middle", WritingMode::VerticalLR, (12f32, 100f32)), ((44f32, 0f32), (100f32, 100f32)));
+        assert_eq!(shift("bottom", WritingMode::VerticalLR, (12f32, 100f32)), ((88f32, 0f32), (100f32, 100f32)));
+        // 内容超出框时不移动
+        assert_eq!(shift("bottom", WritingMode::HorizontalTB, (100f32, 120f32)), ((0f32, 0f32), (100f32, 120f32)));
+    }
+
+    fn layout_resizing(text: &str, resizing: &str, size: (f32, f32)) -> RenderResult {
+        let source = format!(r#"{{"width": {}, "height": {}, "paragraph": {{"resizing": "{}", "contents": [{{"blocks": [{{"text": "{}", "fontSize": 10}}]}}]}}}}"#, size.0, size.1, resizing, text);
+        compute_render_command(&TextData::parse(&source).unwrap(), &TestFont::new("a ")).unwrap()
+    }
+
+    #[test]
+    fn test_resizing() {
+        // fixed 不改变文字框，放不下时报告溢出
+        let result = layout_resizing("aaaa aaaa", "fixed", (50f32, 10f32));
+        assert_eq!((result.size, result.scale, result.overflow), ((50f32, 10f32), 1f32, true));
+        let result = layout_resizing("aaaa aaaa", "fixed", (50f32, 30f32));
+        assert_eq!((result.scale, result.overflow), (1f32, false));
+        assert_eq!(get_drawn_lines(&result.commands.1), vec!["aaaa ", "aaaa"]);
+        // auto-width 不折行，宽度取最长的一行
+        let result = layout_resizing("aaaa aaaa", "auto-width", (50f32, 10f32));
+        assert_eq!(get_drawn_lines(&result.commands.1), vec!["aaaa aaaa"]);
+        assert_eq!((result.size.0, result.overflow), (90f32, false));
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let result = layout_resizing("aaaa aaaa", "shrink-to-fit", (50f32, 30f32));
+        assert_eq!((result.scale, result.overflow), (1f32, false));
+        // 框越小缩放比例越小，缩放后放得下
+        let mut last_scale = 1f32;
+        for height in [20f32, 10f32, 5f32, 2f32].iter() {
+            let result = layout_resizing("aaaa aaaa", "shrink-to-fit", (50f32, *height));
+            assert!(result.scale < last_scale && result.scale >= MIN_SCALE);
+            assert!(!result.overflow);
+            assert_eq!(result.size, (50f32, *height));
+            last_scale = result.scale;
+        }
+        // 最小比例也放不下时停在 MIN_SCALE 并报告溢出
+        let result = layout_resizing("aaaa aaaa", "shrink-to-fit", (50f32, 0.1f32));
+        assert_eq!((result.scale, result.overflow), (MIN_SCALE, true));
+    }
+
+    #[test]
+    fn test_synthetic_face() {
+        let face = FontFace { font_name: "a".to_string(), weight: 400, italic: false };
+        assert!(face.is_synthetic_bold(600) && !face.is_synthetic_bold(500));
+        assert!(face.is_synthetic_oblique(true) && !face.is_synthetic_oblique(false));
+        let bold = FontFace { weight: 700, italic: true, ..face };
+        assert!(!bold.is_synthetic_bold(900) && !bold.is_synthetic_oblique(true));
+
+        // 没有粗体与斜体字体时加粗轮廓并倾斜绘制，加粗的轮廓单独缓存
+        let source = r#"{"width": 100, "height": 100, "paragraph": {"contents": [{"blocks": [{"text": "a", "fontSize": 10, "fontWeight": 700, "fontStyle": "italic"}]}]}}"#;
+        let result = compute_render_command(&TextData::parse(source).unwrap(), &TestFont::new("a")).unwrap();
+        let keys: Vec<&(String, u32)> = result.commands.0.keys().collect();
+        assert_eq!(keys, vec![&("test#synthetic-bold".to_string(), 'a' as u32)]);
+        let skew = result.commands.1.iter().find_map(|command| match command {
+            CommandSegment::Transform(transform, false) => Some(transform.c),
+            _ => None
+        });
+        assert!(skew.unwrap() < 0f32);
+    }
+
+    /// 汉字段追加回退字体 `cjk`，其中只有 `cjk` 中的字符
+    struct FallbackFont(TestFont, TestFont);
+
+    impl MergedFont for FallbackFont {
+        fn char_to_glyph<'a>(&'a self, font_name: String, variations: &Variations, c: char) -> Option<&'a Box<Glyph>> {
+            let fallback = if font_name.ends_with(",cjk") { self.1.char_to_glyph(font_name.clone(), variations, c) } else { None };
+            fallback.or_else(|| self.0.char_to_glyph(font_name, variations, c))
+        }
+
+        fn match_script_face(&self, font_name: &str, script: &str, _lang: &str) -> String {
+            if script == "Hani" { format!("{},cjk", font_name) } else { font_name.to_string() }
+        }
+    }
+
+    fn get_missing(source: &str, font: &impl MergedFont) -> Vec<(usize, usize, usize, char)> {
+        find_missing_glyphs(&TextData::parse(source).unwrap(), font).into_iter()
+            .map(|missing| (missing.content_index, missing.block_index, missing.offset, std::char::from_u32(missing.char_code).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_missing_offsets() {
+        let font = FallbackFont(TestFont::new("abc\n"), TestFont::new("中"));
+        // 序号按字符计算，跨越文字段时继续累加，换行符不报告
+        let source = r#"{"width": 100, "height": 100, "paragraph": {"contents": [
+            {"blocks": [{"text": "ab"}]},
+            {"blocks": [{"text": "😀x"}, {"text": "a中b文\nc字x"}]}
+        ]}}"#;
+        assert_eq!(get_missing(source, &font), vec![(1, 0, 0, '😀'), (1, 0, 1, 'x'), (1, 1, 3, '文'), (1, 1, 6, '字'), (1, 1, 7, 'x')]);
+        // 回退字体只用于对应文字的段，拉丁字母不会从中取字形
+        let font = FallbackFont(TestFont::new("中"), TestFont::new("a"));
+        let source = r#"{"width": 100, "height": 100, "paragraph": {"contents": [{"blocks": [{"text": "中a中"}]}]}}"#;
+        assert_eq!(get_missing(source, &font), vec![(0, 0, 1, 'a')]);
+        // 不可见的格式字符不报告，也计入序号
+        let font = TestFont::new("a");
+        let source = r#"{"width": 100, "height": 100, "paragraph": {"contents": [{"blocks": [{"text": "a\u200db文"}]}]}}"#;
+        assert_eq!(get_missing(source, &font), vec![(0, 0, 2, 'b'), (0, 0, 3, '文')]);
+    }
+}
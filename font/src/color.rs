@@ -0,0 +1,629 @@
+use crate::core::open_type_like::glyph::{ColorGlyph, ColorLayer, GlyphBitmap, GradientExtend, LayerColor, LayerPaint};
+use crate::core::open_type_like::path::PathData;
+use crate::core::open_type_like::transform::Transform;
+use crate::kern::{find_table, read_u16, read_u32};
+
+/// COLRv1 中 Paint 的最大嵌套层数，避免循环引用
+const MAX_PAINT_DEPTH: usize = 64;
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u24(data: &[u8], offset: usize) -> Option<usize> {
+    let r = data.get(offset..offset + 3)?;
+    Some((r[0] as usize) << 16 | (r[1] as usize) << 8 | r[2] as usize)
+}
+
+fn read_f2dot14(data: &[u8], offset: usize) -> Option<f32> {
+    read_i16(data, offset).map(|value| value as f32 / 16384f32)
+}
+
+fn read_fixed(data: &[u8], offset: usize) -> Option<f32> {
+    read_u32(data, offset).map(|value| value as i32 as f32 / 65536f32)
+}
+
+fn read_point(data: &[u8], offset: usize) -> Option<(f32, f32)> {
+    Some((read_i16(data, offset)? as f32, read_i16(data, offset + 2)? as f32))
+}
+
+fn to_alpha(alpha: f32) -> u8 {
+    (alpha.clamp(0f32, 1f32) * 255f32).round() as u8
+}
+
+/// 围绕 (x, y) 应用变换
+fn around_center(ts: Transform, x: f32, y: f32) -> Transform {
+    let mut result = Transform::new_translate(x, y);
+    result.append(&ts);
+    result.translate(-x, -y);
+    result
+}
+
+/// COLRv1 中各种变换 Paint 的子 Paint 与变换，角度以 180° 为单位
+fn get_paint_transform(data: &[u8], paint: usize) -> Option<(usize, Transform)> {
+    let format = *data.get(paint)?;
+    let child = paint + read_u24(data, paint + 1)?;
+    let f2dot14 = |offset: usize| read_f2dot14(data, paint + offset);
+    let fword = |offset: usize| read_i16(data, paint + offset).map(|value| value as f32);
+    let skew = |x: f32, y: f32| Transform::new(1f32, (y * std::f32::consts::PI).tan(), -(x * std::f32::consts::PI).tan(), 1f32, 0f32, 0f32);
+    let ts = match format {
+        12 | 13 => {
+            let affine = paint + read_u24(data, paint + 4)?;
+            let value = |i: usize| read_fixed(data, affine + i * 4);
+            Transform::new(value(0)?, value(1)?, value(2)?, value(3)?, value(4)?, value(5)?)
+        }
+        14 | 15 => Transform::new_translate(fword(4)?, fword(6)?),
+        16 | 17 => Transform::new_scale(f2dot14(4)?, f2dot14(6)?),
+        18 | 19 => around_center(Transform::new_scale(f2dot14(4)?, f2dot14(6)?), fword(8)?, fword(10)?),
+        20 | 21 => Transform::new_scale(f2dot14(4)?, f2dot14(4)?),
+        22 | 23 => around_center(Transform::new_scale(f2dot14(4)?, f2dot14(4)?), fword(6)?, fword(8)?),
+        24 | 25 => Transform::new_rotate(f2dot14(4)? * 180f32),
+        26 | 27 => around_center(Transform::new_rotate(f2dot14(4)? * 180f32), fword(6)?, fword(8)?),
+        28 | 29 => skew(f2dot14(4)?, f2dot14(6)?),
+        30 | 31 => around_center(skew(f2dot14(4)?, f2dot14(6)?), fword(8)?, fword(10)?),
+        _ => return None
+    };
+    Some((child, ts))
+}
+
+/// 色标位置超出 0 到 1 时换算到 0 到 1，返回原来的起止位置
+fn normalize_stops(stops: &mut [(f32, LayerColor)]) -> Option<(f32, f32)> {
+    let min = stops.first()?.0;
+    let max = stops.last()?.0;
+    if max - min <= 0f32 {
+        return None;
+    }
+    for (offset, _) in stops.iter_mut() {
+        *offset = (*offset - min) / (max - min);
+    }
+    Some((min, max))
+}
+
+fn lerp((x1, y1): (f32, f32), (x2, y2): (f32, f32), t: f32) -> (f32, f32) {
+    (x1 + (x2 - x1) * t, y1 + (y2 - y1) * t)
+}
+
+/// 字体中彩色字形相关表的偏移
+pub struct ColorFont {
+    colr: Option<usize>,
+    cpal: Option<usize>,
+    /// `CBLC` 与 `CBDT`
+    bitmap: Option<(usize, usize)>,
+    sbix: Option<usize>,
+    num_glyphs: u32,
+    units_per_em: f32,
+}
+
+impl ColorFont {
+    /// 没有 COLR、CBDT/CBLC 与 sbix 表时返回 `None`
+    pub fn new(data: &[u8], face_offset: usize) -> Option<Self> {
+        let table = |tag: &[u8; 4]| find_table(data, face_offset, tag).map(|(offset, _)| offset);
+        let colr = table(b"COLR");
+        let bitmap = match (table(b"CBLC"), table(b"CBDT")) {
+            (Some(cblc), Some(cbdt)) => Some((cblc, cbdt)),
+            _ => None
+        };
+        let sbix = table(b"sbix");
+        if colr.is_none() && bitmap.is_none() && sbix.is_none() {
+            return None;
+        }
+        Some(ColorFont {
+            colr,
+            cpal: table(b"CPAL"),
+            bitmap,
+            sbix,
+            num_glyphs: read_u16(data, table(b"maxp")? + 4)? as u32,
+            units_per_em: read_u16(data, table(b"head")? + 18)? as f32,
+        })
+    }
+
+    /// 优先使用 COLR 图层，其次为 CBDT 与 sbix 中最大的位图，`outline` 返回字体单位的字形轮廓
+    pub fn get_color_glyph(&self, data: &[u8], glyph_id: u32, outline: &dyn Fn(u32) -> PathData) -> Option<ColorGlyph> {
+        if let Some(layers) = self.colr.and_then(|colr| self.get_layers(data, colr, glyph_id, outline)) {
+            return Some(ColorGlyph::Layers(layers));
+        }
+        let bitmap = self.bitmap.and_then(|(cblc, cbdt)| self.get_cbdt_bitmap(data, cblc, cbdt, glyph_id))
+            .or_else(|| self.sbix.and_then(|sbix| self.get_sbix_bitmap(data, sbix, glyph_id, true)))?;
+        Some(ColorGlyph::Bitmap(bitmap))
+    }
+
+    /// 使用第一个调色板，0xFFFF 或超出范围时为文字颜色
+    fn get_color(&self, data: &[u8], palette_index: u16, alpha: f32) -> LayerColor {
+        let color = self.cpal.filter(|_| palette_index != 0xFFFF).and_then(|cpal| {
+            if palette_index >= read_u16(data, cpal + 2)? {
+                return None;
+            }
+            let records = cpal + read_u32(data, cpal + 8)? as usize;
+            let first = read_u16(data, cpal + 12)? as usize;
+            let record = records + (first + palette_index as usize) * 4;
+            let bgra = data.get(record..record + 4)?;
+            Some(LayerColor::Rgba(bgra[2], bgra[1], bgra[0], to_alpha(bgra[3] as f32 / 255f32 * alpha)))
+        });
+        color.unwrap_or(LayerColor::Foreground(to_alpha(alpha)))
+    }
+
+    fn get_layers(&self, data: &[u8], colr: usize, glyph_id: u32, outline: &dyn Fn(u32) -> PathData) -> Option<Vec<ColorLayer>> {
+        if let Some(paint) = self.find_base_paint(data, colr, glyph_id) {
+            let mut layers = vec![];
+            self.collect_paint(data, colr, paint, Transform::default(), 0, outline, &mut layers);
+            return Some(layers).filter(|layers| !layers.is_empty());
+        }
+        let count = read_u16(data, colr + 2)? as usize;
+        let base_records = colr + read_u32(data, colr + 4)? as usize;
+        let layer_records = colr + read_u32(data, colr + 8)? as usize;
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let mid = (low + high) / 2;
+            let record = base_records + mid * 6;
+            let id = read_u16(data, record)? as u32;
+            if id == glyph_id {
+                let first = read_u16(data, record + 2)? as usize;
+                let num = read_u16(data, record + 4)? as usize;
+                return (first..first + num).map(|i| {
+                    let layer = layer_records + i * 4;
+                    Some(ColorLayer {
+                        path: outline(read_u16(data, layer)? as u32),
+                        paint: LayerPaint::Solid(self.get_color(data, read_u16(data, layer + 2)?, 1f32)),
+                    })
+                }).collect();
+            } else if id < glyph_id {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        None
+    }
+
+    /// COLRv1 中字形对应的 Paint
+    fn find_base_paint(&self, data: &[u8], colr: usize, glyph_id: u32) -> Option<usize> {
+        if read_u16(data, colr)? == 0 {
+            return None;
+        }
+        let list = match read_u32(data, colr + 14)? {
+            0 => return None,
+            offset => colr + offset as usize
+        };
+        let count = read_u32(data, list)? as usize;
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let mid = (low + high) / 2;
+            let record = list + 4 + mid * 6;
+            let id = read_u16(data, record)? as u32;
+            if id == glyph_id {
+                return Some(list + read_u32(data, record + 2)? as usize);
+            } else if id < glyph_id {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        None
+    }
+
+    /// 把 Paint 展开为图层，`transform` 为外层变换的累积，混合模式均按覆盖处理
+    #[allow(clippy::too_many_arguments)]
+    fn collect_paint(&self, data: &[u8], colr: usize, paint: usize, transform: Transform, depth: usize,
+                     outline: &dyn Fn(u32) -> PathData, layers: &mut Vec<ColorLayer>) -> Option<()> {
+        if depth > MAX_PAINT_DEPTH {
+            return None;
+        }
+        let collect = |child: usize, transform: Transform, layers: &mut Vec<ColorLayer>| {
+            self.collect_paint(data, colr, child, transform, depth + 1, outline, layers);
+        };
+        match *data.get(paint)? {
+            1 => {
+                let count = *data.get(paint + 1)? as usize;
+                let first = read_u32(data, paint + 2)? as usize;
+                let list = colr + read_u32(data, colr + 18)? as usize;
+                for i in first..first + count {
+                    collect(list + read_u32(data, list + 4 + i * 4)? as usize, transform, layers);
+                }
+            }
+            10 => {
+                let child = paint + read_u24(data, paint + 1)?;
+                let glyph_id = read_u16(data, paint + 4)? as u32;
+                match self.get_fill(data, child, transform, depth + 1) {
+                    Some(fill) => {
+                        let mut path = outline(glyph_id);
+                        path.transform(transform);
+                        layers.push(ColorLayer { path, paint: fill });
+                    }
+                    // 字形内不是单一填充时忽略这一层裁剪
+                    None => collect(child, transform, layers)
+                }
+            }
+            11 => {
+                let base = self.find_base_paint(data, colr, read_u16(data, paint + 1)? as u32)?;
+                collect(base, transform, layers);
+            }
+            12..=31 => {
+                let (child, ts) = get_paint_transform(data, paint)?;
+                let mut transform = transform;
+                transform.append(&ts);
+                collect(child, transform, layers);
+            }
+            32 => {
+                let source = paint + read_u24(data, paint + 1)?;
+                let backdrop = paint + read_u24(data, paint + 5)?;
+                collect(backdrop, transform, layers);
+                collect(source, transform, layers);
+            }
+            _ => ()
+        }
+        Some(())
+    }
+
+    /// 纯色与渐变，扫描渐变以中间的颜色近似
+    fn get_fill(&self, data: &[u8], paint: usize, transform: Transform, depth: usize) -> Option<LayerPaint> {
+        if depth > MAX_PAINT_DEPTH {
+            return None;
+        }
+        let format = *data.get(paint)?;
+        match format {
+            2 | 3 => Some(LayerPaint::Solid(self.get_color(data, read_u16(data, paint + 1)?, read_f2dot14(data, paint + 3)?))),
+            4 | 5 => {
+                let (mut stops, extend) = self.get_color_line(data, paint + read_u24(data, paint + 1)?, format == 5)?;
+                let (p0, p1, p2) = (read_point(data, paint + 4)?, read_point(data, paint + 8)?, read_point(data, paint + 12)?);
+                // 渐变方向与 p0p2 垂直，终点为 p1 在该方向上的投影
+                let (nx, ny) = (p2.1 - p0.1, p0.0 - p2.0);
+                let length = nx * nx + ny * ny;
+                let p3 = if length == 0f32 {
+                    p1
+                } else {
+                    let k = ((p1.0 - p0.0) * nx + (p1.1 - p0.1) * ny) / length;
+                    (p0.0 + nx * k, p0.1 + ny * k)
+                };
+                match normalize_stops(&mut stops) {
+                    Some((min, max)) => Some(LayerPaint::LinearGradient { start: lerp(p0, p3, min), end: lerp(p0, p3, max), stops, extend, transform }),
+                    None => Some(LayerPaint::Solid(stops.last()?.1))
+                }
+            }
+            6 | 7 => {
+                let (mut stops, extend) = self.get_color_line(data, paint + read_u24(data, paint + 1)?, format == 7)?;
+                let (c0, r0) = (read_point(data, paint + 4)?, read_u16(data, paint + 8)? as f32);
+                let (c1, r1) = (read_point(data, paint + 10)?, read_u16(data, paint + 14)? as f32);
+                match normalize_stops(&mut stops) {
+                    Some((min, max)) => Some(LayerPaint::RadialGradient {
+                        start: lerp(c0, c1, min),
+                        start_radius: (r0 + (r1 - r0) * min).max(0f32),
+                        end: lerp(c0, c1, max),
+                        end_radius: (r0 + (r1 - r0) * max).max(0f32),
+                        stops,
+                        extend,
+                        transform,
+                    }),
+                    None => Some(LayerPaint::Solid(stops.last()?.1))
+                }
+            }
+            8 | 9 => {
+                let (stops, _) = self.get_color_line(data, paint + read_u24(data, paint + 1)?, format == 9)?;
+                Some(LayerPaint::Solid(stops.get(stops.len() / 2)?.1))
+            }
+            12..=31 => {
+                let (child, ts) = get_paint_transform(data, paint)?;
+                let mut transform = transform;
+                transform.append(&ts);
+                self.get_fill(data, child, transform, depth + 1)
+            }
+            _ => None
+        }
+    }
+
+    fn get_color_line(&self, data: &[u8], offset: usize, is_var: bool) -> Option<(Vec<(f32, LayerColor)>, GradientExtend)> {
+        let extend = match *data.get(offset)? {
+            1 => GradientExtend::Repeat,
+            2 => GradientExtend::Reflect,
+            _ => GradientExtend::Pad
+        };
+        let count = read_u16(data, offset + 1)? as usize;
+        let size = if is_var { 10 } else { 6 };
+        let mut stops = (0..count).map(|i| {
+            let stop = offset + 3 + i * size;
+            Some((read_f2dot14(data, stop)?, self.get_color(data, read_u16(data, stop + 2)?, read_f2dot14(data, stop + 4)?)))
+        }).collect::<Option<Vec<(f32, LayerColor)>>>()?;
+        if stops.is_empty() {
+            return None;
+        }
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Some((stops, extend))
+    }
+
+    /// CBDT 中包含该字形的最大尺寸的 PNG
+    fn get_cbdt_bitmap(&self, data: &[u8], cblc: usize, cbdt: usize, glyph_id: u32) -> Option<GlyphBitmap> {
+        let num_sizes = read_u32(data, cblc + 4)? as usize;
+        let mut best: Option<(u8, usize)> = None;
+        for i in 0..num_sizes {
+            let size = cblc + 8 + i * 48;
+            let start = read_u16(data, size + 40)? as u32;
+            let end = read_u16(data, size + 42)? as u32;
+            let ppem = *data.get(size + 45)?;
+            if glyph_id >= start && glyph_id <= end && best.map(|(best, _)| ppem > best).unwrap_or(true) {
+                best = Some((ppem, size));
+            }
+        }
+        let (ppem, size) = best?;
+        let array = cblc + read_u32(data, size)? as usize;
+        let count = read_u32(data, size + 8)? as usize;
+        let entry = (0..count).map(|i| array + i * 8).find(|entry| {
+            let first = read_u16(data, *entry).unwrap_or(1) as u32;
+            let last = read_u16(data, *entry + 2).unwrap_or(0) as u32;
+            glyph_id >= first && glyph_id <= last
+        })?;
+        let index = (glyph_id - read_u16(data, entry)? as u32) as usize;
+        let subtable = array + read_u32(data, entry + 4)? as usize;
+        let index_format = read_u16(data, subtable)?;
+        let image_format = read_u16(data, subtable + 2)?;
+        let image_data = cbdt + read_u32(data, subtable + 4)? as usize;
+        let (offset, big_metrics) = match index_format {
+            1 | 3 => {
+                let (start, end) = if index_format == 1 {
+                    (read_u32(data, subtable + 8 + index * 4)? as usize, read_u32(data, subtable + 12 + index * 4)? as usize)
+                } else {
+                    (read_u16(data, subtable + 8 + index * 2)? as usize, read_u16(data, subtable + 10 + index * 2)? as usize)
+                };
+                if end <= start {
+                    return None;
+                }
+                (image_data + start, None)
+            }
+            2 => (image_data + read_u32(data, subtable + 8)? as usize * index, Some(subtable + 12)),
+            4 => {
+                let num_glyphs = read_u32(data, subtable + 8)? as usize;
+                let pair = (0..num_glyphs).map(|i| subtable + 12 + i * 4)
+                    .find(|pair| read_u16(data, *pair).map(|id| id as u32) == Some(glyph_id))?;
+                (image_data + read_u16(data, pair + 2)? as usize, None)
+            }
+            5 => {
+                let num_glyphs = read_u32(data, subtable + 20)? as usize;
+                let position = (0..num_glyphs).find(|i| read_u16(data, subtable + 24 + i * 2).map(|id| id as u32) == Some(glyph_id))?;
+                (image_data + read_u32(data, subtable + 8)? as usize * position, Some(subtable + 12))
+            }
+            _ => return None
+        };
+        // 高、宽、水平方向的 bearingX 与 bearingY，以及 PNG 数据的位置
+        let (metrics, png) = match image_format {
+            17 => (offset, offset + 9),
+            18 => (offset, offset + 12),
+            19 => (big_metrics?, offset + 4),
+            _ => return None
+        };
+        let length = read_u32(data, png - 4)? as usize;
+        let height = *data.get(metrics)? as f32;
+        let width = *data.get(metrics + 1)? as f32;
+        let bearing_x = *data.get(metrics + 2)? as i8 as f32;
+        let bearing_y = *data.get(metrics + 3)? as i8 as f32;
+        let scale = self.units_per_em / ppem.max(1) as f32;
+        Some(GlyphBitmap {
+            format: "png".to_string(),
+            data: data.get(png..png + length)?.to_vec(),
+            x: bearing_x * scale,
+            y: (bearing_y - height) * scale,
+            width: width * scale,
+            height: height * scale,
+        })
+    }
+
+    /// sbix 中包含该字形的最大尺寸的 PNG，`dupe` 指向其他字形
+    fn get_sbix_bitmap(&self, data: &[u8], sbix: usize, glyph_id: u32, follow_dupe: bool) -> Option<GlyphBitmap> {
+        if glyph_id >= self.num_glyphs {
+            return None;
+        }
+        let count = read_u32(data, sbix + 4)? as usize;
+        let mut best: Option<(u16, usize, usize)> = None;
+        for i in 0..count {
+            let strike = sbix + read_u32(data, sbix + 8 + i * 4)? as usize;
+            let ppem = read_u16(data, strike)?;
+            let start = read_u32(data, strike + 4 + glyph_id as usize * 4)? as usize;
+            let end = read_u32(data, strike + 8 + glyph_id as usize * 4)? as usize;
+            if end > start + 8 && best.map(|(best, _, _)| ppem > best).unwrap_or(true) {
+                best = Some((ppem, strike + start, strike + end));
+            }
+        }
+        let (ppem, start, end) = best?;
+        let origin_x = read_i16(data, start)? as f32;
+        let origin_y = read_i16(data, start + 2)? as f32;
+        let image = data.get(start + 8..end)?;
+        match data.get(start + 4..start + 8)? {
+            b"png " => (),
+            b"dupe" if follow_dupe => return self.get_sbix_bitmap(data, sbix, read_u16(image, 0)? as u32, false),
+            _ => return None
+        }
+        // PNG 的 IHDR 中的宽高
+        let width = read_u32(image, 16)? as f32;
+        let height = read_u32(image, 20)? as f32;
+        let scale = self.units_per_em / ppem.max(1) as f32;
+        Some(GlyphBitmap {
+            format: "png".to_string(),
+            data: image.to_vec(),
+            x: origin_x * scale,
+            y: origin_y * scale,
+            width: width * scale,
+            height: height * scale,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ColorFont;
+    use crate::core::open_type_like::glyph::{ColorGlyph, GlyphBitmap, LayerColor, LayerPaint};
+    use crate::core::open_type_like::path::PathData;
+
+    fn push_u16(data: &mut Vec<u8>, values: &[i32]) {
+        for value in values {
+            data.extend_from_slice(&(*value as u16).to_be_bytes());
+        }
+    }
+
+    fn push_u32(data: &mut Vec<u8>, values: &[u32]) {
+        for value in values {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    /// 每 em 1024 单位、共 4 个字形的字体，附加给定的表
+    fn build_font(tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&1024u16.to_be_bytes());
+        let mut maxp = vec![0, 0, 0x50, 0];
+        push_u16(&mut maxp, &[4]);
+        let mut tables = tables.to_vec();
+        tables.push((b"head", head));
+        tables.push((b"maxp", maxp));
+        let mut data = vec![0, 1, 0, 0];
+        push_u16(&mut data, &[tables.len() as i32, 0, 0, 0]);
+        let mut offset = 12 + tables.len() * 16;
+        for (tag, table) in tables.iter() {
+            data.extend_from_slice(*tag);
+            push_u32(&mut data, &[0, offset as u32, table.len() as u32]);
+            offset += table.len();
+        }
+        for (_, table) in tables.iter() {
+            data.extend_from_slice(table);
+        }
+        data
+    }
+
+    /// 轮廓只有一个起点，横坐标为字形序号
+    fn outline(glyph_id: u32) -> PathData {
+        let mut path = PathData::new();
+        path.move_to(glyph_id as f32, 0f32);
+        path
+    }
+
+    fn get_glyph(data: &[u8], glyph_id: u32) -> Option<ColorGlyph> {
+        ColorFont::new(data, 0)?.get_color_glyph(data, glyph_id, &outline)
+    }
+
+    fn get_bitmap(data: &[u8], glyph_id: u32) -> GlyphBitmap {
+        match get_glyph(data, glyph_id) {
+            Some(ColorGlyph::Bitmap(bitmap)) => bitmap,
+            glyph => panic!("应为位图：{:?}", glyph),
+        }
+    }
+
+    /// 一个颜色的调色板，BGRA 为 (0x33, 0x66, 0x99, 0x80)
+    fn get_cpal() -> Vec<u8> {
+        let mut cpal = vec![];
+        push_u16(&mut cpal, &[0, 1, 1, 1]);
+        push_u32(&mut cpal, &[14]);
+        push_u16(&mut cpal, &[0]);
+        cpal.extend_from_slice(&[0x33, 0x66, 0x99, 0x80]);
+        cpal
+    }
+
+    /// 字形 1、2 为 v0 图层，字形 3 为 v1 中平移后的 PaintGlyph 与半透明的 PaintSolid
+    fn get_colr() -> Vec<u8> {
+        let mut colr = vec![];
+        push_u16(&mut colr, &[1, 2]);
+        push_u32(&mut colr, &[34, 46]);
+        push_u16(&mut colr, &[3]);
+        push_u32(&mut colr, &[58, 0, 0, 0, 0]);
+        push_u16(&mut colr, &[1, 0, 2, 2, 2, 1]);
+        push_u16(&mut colr, &[10, 0, 11, 0xFFFF, 12, 5]);
+        push_u32(&mut colr, &[1]);
+        push_u16(&mut colr, &[3]);
+        push_u32(&mut colr, &[10]);
+        colr.extend_from_slice(&[14, 0, 0, 8]);
+        push_u16(&mut colr, &[100, 0]);
+        colr.extend_from_slice(&[10, 0, 0, 6]);
+        push_u16(&mut colr, &[13]);
+        colr.push(2);
+        push_u16(&mut colr, &[0, 0x2000]);
+        colr
+    }
+
+    #[test]
+    fn test_colr() {
+        let data = build_font(&[(b"COLR", get_colr()), (b"CPAL", get_cpal())]);
+        let layers = |glyph_id: u32| match get_glyph(&data, glyph_id) {
+            Some(ColorGlyph::Layers(layers)) => layers.iter().map(|layer| {
+                let color = match layer.paint {
+                    LayerPaint::Solid(color) => color,
+                    _ => panic!("应为纯色"),
+                };
+                (String::from(&layer.path), color)
+            }).collect::<Vec<(String, LayerColor)>>(),
+            glyph => panic!("应为图层：{:?}", glyph),
+        };
+        // 0xFFFF 与超出调色板的序号都为文字颜色
+        assert_eq!(layers(1), vec![
+            ("M 10.0 0.0".to_string(), LayerColor::Rgba(0x99, 0x66, 0x33, 0x80)),
+            ("M 11.0 0.0".to_string(), LayerColor::Foreground(255)),
+        ]);
+        assert_eq!(layers(2), vec![("M 12.0 0.0".to_string(), LayerColor::Foreground(255))]);
+        // v1 的变换作用于轮廓，透明度与调色板中的相乘
+        assert_eq!(layers(3), vec![("M 113.0 0.0".to_string(), LayerColor::Rgba(0x99, 0x66, 0x33, 64))]);
+        assert!(get_glyph(&data, 0).is_none());
+        // 截断的表不会越界
+        let mut colr = get_colr();
+        colr.truncate(40);
+        let data = build_font(&[(b"COLR", colr)]);
+        assert!(get_glyph(&data, 1).is_none());
+        assert!(get_glyph(&data, 3).is_none());
+    }
+
+    #[test]
+    fn test_cbdt() {
+        // 两个尺寸共用一个索引子表，ppem 128 只有字形 1，ppem 64 有字形 1 与 2
+        let mut cblc = vec![];
+        push_u32(&mut cblc, &[0x30000, 2]);
+        for (ppem, end) in [(64u8, 2), (128u8, 1)].iter() {
+            push_u32(&mut cblc, &[104, 16, 1, 0]);
+            cblc.extend_from_slice(&[0; 24]);
+            push_u16(&mut cblc, &[1, *end]);
+            cblc.extend_from_slice(&[*ppem, *ppem, 32, 1]);
+        }
+        push_u16(&mut cblc, &[1, 2]);
+        push_u32(&mut cblc, &[8]);
+        push_u16(&mut cblc, &[1, 17]);
+        push_u32(&mut cblc, &[4, 0, 13, 26]);
+        let mut cbdt = vec![0, 3, 0, 0];
+        for image in [b"png1", b"png2"].iter() {
+            cbdt.extend_from_slice(&[16, 12, 1, 14, 14]);
+            push_u32(&mut cbdt, &[4]);
+            cbdt.extend_from_slice(*image);
+        }
+        let data = build_font(&[(b"CBLC", cblc), (b"CBDT", cbdt)]);
+        let bitmap = get_bitmap(&data, 1);
+        assert_eq!((bitmap.format.as_str(), bitmap.data.as_slice()), ("png", &b"png1"[..]));
+        assert_eq!((bitmap.x, bitmap.y, bitmap.width, bitmap.height), (8f32, -16f32, 96f32, 128f32));
+        let bitmap = get_bitmap(&data, 2);
+        assert_eq!(bitmap.data, b"png2");
+        assert_eq!((bitmap.x, bitmap.y, bitmap.width, bitmap.height), (16f32, -32f32, 192f32, 256f32));
+        assert!(get_glyph(&data, 3).is_none());
+    }
+
+    #[test]
+    fn test_sbix() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        push_u32(&mut png, &[32, 32]);
+        let mut sbix = vec![];
+        push_u16(&mut sbix, &[1, 0]);
+        push_u32(&mut sbix, &[1, 12]);
+        push_u16(&mut sbix, &[64, 72]);
+        push_u32(&mut sbix, &[24, 24, 56, 66, 66]);
+        push_u16(&mut sbix, &[2, -3]);
+        sbix.extend_from_slice(b"png ");
+        sbix.extend_from_slice(&png);
+        push_u16(&mut sbix, &[0, 0]);
+        sbix.extend_from_slice(b"dupe");
+        push_u16(&mut sbix, &[1]);
+        let data = build_font(&[(b"sbix", sbix)]);
+        let bitmap = get_bitmap(&data, 1);
+        assert_eq!(bitmap.data, png);
+        assert_eq!((bitmap.x, bitmap.y, bitmap.width, bitmap.height), (32f32, -48f32, 512f32, 512f32));
+        // dupe 使用字形 1 的图片
+        assert_eq!(get_bitmap(&data, 2).data, png);
+        assert!(get_glyph(&data, 3).is_none());
+        assert!(get_glyph(&data, 4).is_none());
+    }
+
+    #[test]
+    fn test_no_color_table() {
+        assert!(ColorFont::new(&build_font(&[]), 0).is_none());
+        // 只有 CBLC 没有 CBDT 时不使用位图
+        assert!(ColorFont::new(&build_font(&[(b"CBLC", vec![0; 8])]), 0).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::kern::{find_table, read_u16, read_u32};
+
+/// 字体族名、样式名、字重与是否为斜体，来自 `name`、`OS/2` 与 `head` 表
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceStyle {
+    pub family: String,
+    pub subfamily: String,
+    pub weight: u16,
+    pub italic: bool,
+    /// `OS/2` 的 ulCodePageRange1 与 ulCodePageRange2，版本 0 的字体为 0
+    pub code_pages: u64,
+}
+
+impl FaceStyle {
+    pub fn new(data: &[u8], face_offset: usize) -> Self {
+        let mac_style = find_table(data, face_offset, b"head").and_then(|(head, _)| read_u16(data, head + 44)).unwrap_or(0);
+        let (weight, italic, code_pages) = match find_table(data, face_offset, b"OS/2") {
+            Some((os2, _)) => {
+                let weight = read_u16(data, os2 + 4).filter(|weight| *weight > 0).unwrap_or(400);
+                // fsSelection 第 0 位为 italic，第 9 位为 oblique
+                let selection = read_u16(data, os2 + 62).unwrap_or(0);
+                let code_pages = match read_u16(data, os2) {
+                    Some(version) if version >= 1 => {
+                        let low = read_u32(data, os2 + 78).unwrap_or(0) as u64;
+                        let high = read_u32(data, os2 + 82).unwrap_or(0) as u64;
+                        low | high << 32
+                    }
+                    _ => 0
+                };
+                (weight, selection & 0x0201 != 0 || mac_style & 0x02 != 0, code_pages)
+            }
+            None => (if mac_style & 0x01 != 0 { 700 } else { 400 }, mac_style & 0x02 != 0, 0)
+        };
+        FaceStyle {
+            family: get_name(data, face_offset, 16, 1).unwrap_or_default(),
+            subfamily: get_name(data, face_offset, 17, 2).unwrap_or_default(),
+            weight,
+            italic,
+            code_pages,
+        }
+    }
+
+    /// 是否声明支持 `OS/2` ulCodePageRange 中的该位
+    pub fn has_code_page(&self, bit: u32) -> bool {
+        self.code_pages & (1 << bit) != 0
+    }
+
+    /// 族名与样式名，如 `Noto Sans CJK SC Bold`，没有族名时返回 `None`
+    pub fn get_full_name(&self) -> Option<String> {
+        if self.family.is_empty() {
+            None
+        } else if self.subfamily.is_empty() {
+            Some(self.family.clone())
+        } else {
+            Some(format!("{} {}", self.family, self.subfamily))
+        }
+    }
+
+    /// 按 CSS 字体匹配规则排序，先比较斜体，再比较字重，值越小越优先
+    pub fn get_rank(&self, weight: u16, italic: bool) -> (bool, u8, i32) {
+        let (weight, face) = (weight as i32, self.weight as i32);
+        let distance = (weight - face).abs();
+        let order = if (400..=500).contains(&weight) {
+            // 400 到 500 之间先找到 500 为止更粗的，再找更细的，最后找超过 500 的
+            if face >= weight && face <= 500 { 0 } else if face < weight { 1 } else { 2 }
+        } else if weight < 400 {
+            if face <= weight { 0 } else { 1 }
+        } else if face >= weight { 0 } else { 1 };
+        (self.italic != italic, order, distance)
+    }
+}
+
+/// 文字与语言对应的 ulCodePageRange 位，汉字按语言区分简繁与日韩，没有对应时返回 `None`
+pub fn get_code_page(script: &str, lang: &str) -> Option<u32> {
+    let mut tags = lang.split(&['-', '_'][..]);
+    let primary = tags.next().unwrap_or("").to_lowercase();
+    let is_traditional = tags.any(|tag| ["hant", "tw", "hk", "mo"].contains(&tag.to_lowercase().as_str()));
+    match (script, primary.as_str()) {
+        ("Hira", _) | ("Kana", _) | ("Hani", "ja") => Some(17),
+        ("Hang", _) | ("Hani", "ko") => Some(19),
+        ("Hani", "zh") | ("Bopo", "zh") if is_traditional => Some(20),
+        ("Hani", "zh") => Some(18),
+        ("Bopo", _) => Some(20),
+        ("Latn", "vi") => Some(8),
+        ("Latn", "tr") | ("Latn", "az") => Some(4),
+        ("Latn", "cs") | ("Latn", "pl") | ("Latn", "hu") | ("Latn", "sk") | ("Latn", "sl") | ("Latn", "hr") | ("Latn", "ro") => Some(1),
+        ("Latn", "lt") | ("Latn", "lv") | ("Latn", "et") => Some(7),
+        ("Latn", _) => Some(0),
+        ("Cyrl", _) => Some(2),
+        ("Grek", _) => Some(3),
+        ("Hebr", _) => Some(5),
+        ("Arab", _) => Some(6),
+        ("Thai", _) => Some(16),
+        _ => None
+    }
+}
+
+/// 优先使用排版用的名字（族名为 nameID 16，样式名为 nameID 17），其次为 nameID 1 或 2
+fn get_name(data: &[u8], face_offset: usize, preferred_id: u16, fallback_id: u16) -> Option<String> {
+    let (name, _) = find_table(data, face_offset, b"name")?;
+    let count = read_u16(data, name + 2)? as usize;
+    let storage = name + read_u16(data, name + 4)? as usize;
+    let mut result: Option<((u16, bool), String)> = None;
+    for i in 0..count {
+        let record = name + 6 + i * 12;
+        let platform = read_u16(data, record)?;
+        let language = read_u16(data, record + 4)?;
+        let name_id = read_u16(data, record + 6)?;
+        if name_id != preferred_id && name_id != fallback_id { continue; }
+        let length = read_u16(data, record + 8)? as usize;
+        let offset = storage + read_u16(data, record + 10)? as usize;
+        let bytes = match data.get(offset..offset + length) {
+            Some(bytes) => bytes,
+            None => continue
+        };
+        let value = match platform {
+            0 | 3 => {
+                let units: Vec<u16> = bytes.chunks(2).filter(|c| c.len() == 2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+                String::from_utf16_lossy(&units)
+            }
+            1 => bytes.iter().map(|b| *b as char).collect(),
+            _ => continue
+        };
+        // 排版用的名字优先，同一 nameID 中英文名优先
+        let rank = (if name_id == preferred_id { 0 } else { 1 }, !(language == 0x0409 || (platform == 1 && language == 0)));
+        if result.as_ref().map(|(best, _)| rank < *best).unwrap_or(true) {
+            result = Some((rank, value));
+        }
+    }
+    result.map(|(_, value)| value).filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_code_page, FaceStyle};
+
+    /// 按匹配顺序排列的字重
+    fn get_order(weights: &[u16], italic_faces: &[u16], weight: u16, italic: bool) -> Vec<(u16, bool)> {
+        let mut faces: Vec<FaceStyle> = weights.iter().map(|weight| (*weight, false))
+            .chain(italic_faces.iter().map(|weight| (*weight, true)))
+            .map(|(weight, italic)| FaceStyle { family: "a".to_string(), subfamily: "".to_string(), weight, italic, code_pages: 0 })
+            .collect();
+        faces.sort_by_key(|face| face.get_rank(weight, italic));
+        faces.iter().map(|face| (face.weight, face.italic)).collect()
+    }
+
+    fn get_weights(faces: Vec<(u16, bool)>) -> Vec<u16> {
+        faces.into_iter().map(|(weight, _)| weight).collect()
+    }
+
+    #[test]
+    fn test_rank() {
+        let weights = [100, 300, 400, 500, 600, 700, 900];
+        assert_eq!(get_weights(get_order(&weights, &[], 400, false)), vec![400, 500, 300, 100, 600, 700, 900]);
+        assert_eq!(get_weights(get_order(&weights, &[], 450, false)), vec![500, 400, 300, 100, 600, 700, 900]);
+        assert_eq!(get_weights(get_order(&weights, &[], 300, false)), vec![300, 100, 400, 500, 600, 700, 900]);
+        assert_eq!(get_weights(get_order(&weights, &[], 700, false)), vec![700, 900, 600, 500, 400, 300, 100]);
+        // 先匹配斜体，再匹配字重
+        assert_eq!(get_order(&[700], &[400], 700, true), vec![(400, true), (700, false)]);
+        assert_eq!(get_order(&[400], &[700], 700, false), vec![(400, false), (700, true)]);
+    }
+
+    #[test]
+    fn test_code_page() {
+        // 汉字按语言区分简繁与日韩
+        assert_eq!(get_code_page("Hani", "zh-CN"), Some(18));
+        assert_eq!(get_code_page("Hani", "zh-Hant"), Some(20));
+        assert_eq!(get_code_page("Hani", "zh_TW"), Some(20));
+        assert_eq!(get_code_page("Hani", "ja"), Some(17));
+        assert_eq!(get_code_page("Hani", "ko-KR"), Some(19));
+        assert_eq!(get_code_page("Hani", ""), None);
+        assert_eq!(get_code_page("Kana", ""), Some(17));
+        assert_eq!(get_code_page("Latn", "VI"), Some(8));
+        assert_eq!(get_code_page("Latn", "en"), Some(0));
+        assert_eq!(get_code_page("Zsye", "en"), None);
+    }
+}
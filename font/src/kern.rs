@@ -0,0 +1,333 @@
+const LOOKUP_TYPE_PAIR: u16 = 2;
+const LOOKUP_TYPE_EXTENSION: u16 = 9;
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let r = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([r[0], r[1]]))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let r = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([r[0], r[1], r[2], r[3]]))
+}
+
+/// 在表目录中查找表的位置
+pub fn find_table(data: &[u8], face_offset: usize, tag: &[u8; 4]) -> Option<(usize, usize)> {
+    let num_tables = read_u16(data, face_offset + 4)? as usize;
+    for i in 0..num_tables {
+        let record = face_offset + 12 + i * 16;
+        if data.get(record..record + 4)? == tag {
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            return Some((offset, length));
+        }
+    }
+    None
+}
+
+fn value_record_size(value_format: u16) -> usize {
+    (value_format & 0xff).count_ones() as usize * 2
+}
+
+/// ValueRecord 中的 XAdvance
+fn value_record_x_advance(data: &[u8], offset: usize, value_format: u16) -> Option<i16> {
+    if value_format & 0x0004 == 0 { return Some(0); }
+    let skip = (value_format & 0x0003).count_ones() as usize * 2;
+    Some(read_u16(data, offset + skip)? as i16)
+}
+
+fn coverage_index(data: &[u8], offset: usize, glyph_id: u16) -> Option<usize> {
+    match read_u16(data, offset)? {
+        1 => {
+            let count = read_u16(data, offset + 2)? as usize;
+            let (mut low, mut high) = (0usize, count);
+            while low < high {
+                let mid = (low + high) / 2;
+                let g = read_u16(data, offset + 4 + mid * 2)?;
+                if g == glyph_id {
+                    return Some(mid);
+                } else if g < glyph_id {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            None
+        }
+        2 => {
+            let count = read_u16(data, offset + 2)? as usize;
+            for i in 0..count {
+                let record = offset + 4 + i * 6;
+                let start = read_u16(data, record)?;
+                let end = read_u16(data, record + 2)?;
+                if glyph_id >= start && glyph_id <= end {
+                    let start_index = read_u16(data, record + 4)? as usize;
+                    return Some(start_index + (glyph_id - start) as usize);
+                }
+            }
+            None
+        }
+        _ => None
+    }
+}
+
+fn class_of(data: &[u8], offset: usize, glyph_id: u16) -> u16 {
+    let class = || -> Option<u16> {
+        match read_u16(data, offset)? {
+            1 => {
+                let start = read_u16(data, offset + 2)?;
+                let count = read_u16(data, offset + 4)?;
+                if glyph_id < start || glyph_id - start >= count { return Some(0); }
+                read_u16(data, offset + 6 + (glyph_id - start) as usize * 2)
+            }
+            2 => {
+                let count = read_u16(data, offset + 2)? as usize;
+                for i in 0..count {
+                    let record = offset + 4 + i * 6;
+                    let start = read_u16(data, record)?;
+                    let end = read_u16(data, record + 2)?;
+                    if glyph_id >= start && glyph_id <= end {
+                        return read_u16(data, record + 4);
+                    }
+                }
+                Some(0)
+            }
+            _ => Some(0)
+        }
+    };
+    class().unwrap_or(0)
+}
+
+/// `kern` 表格式 0 子表中的水平字偶距，`kern` 为表的偏移
+pub(crate) fn get_kern_value(data: &[u8], kern: usize, left: u32, right: u32) -> Option<i32> {
+    if read_u16(data, kern)? != 0 { return None; }
+    let count = read_u16(data, kern + 2)? as usize;
+    let mut sub_table = kern + 4;
+    for _ in 0..count {
+        let length = read_u16(data, sub_table + 2)? as usize;
+        let coverage = read_u16(data, sub_table + 4)?;
+        // 格式 0 的水平字偶距
+        if coverage >> 8 == 0 && coverage & 0x0F == 0x01 {
+            let pair_count = read_u16(data, sub_table + 6)? as usize;
+            let key = (left << 16) | right;
+            let (mut low, mut high) = (0usize, pair_count);
+            while low < high {
+                let mid = (low + high) / 2;
+                let pair = sub_table + 14 + mid * 6;
+                let value = read_u32(data, pair)?;
+                if value < key {
+                    low = mid + 1;
+                } else if value > key {
+                    high = mid;
+                } else {
+                    return Some(read_u16(data, pair + 4)? as i16 as i32);
+                }
+            }
+        }
+        sub_table += length;
+    }
+    None
+}
+
+/// GPOS 中 `kern` 特性引用的 PairPos 子表，保存在字体数据中的绝对偏移
+#[derive(Debug, Clone, Default)]
+pub struct PairKerning {
+    lookups: Vec<Vec<usize>>,
+}
+
+impl PairKerning {
+    pub fn new(data: &[u8], face_offset: usize) -> Self {
+        PairKerning {
+            lookups: Self::parse(data, face_offset).unwrap_or(vec![]),
+        }
+    }
+
+    fn parse(data: &[u8], face_offset: usize) -> Option<Vec<Vec<usize>>> {
+        let (gpos, _) = find_table(data, face_offset, b"GPOS")?;
+        let feature_list = gpos + read_u16(data, gpos + 6)? as usize;
+        let lookup_list = gpos + read_u16(data, gpos + 8)? as usize;
+
+        let mut lookup_indices = Vec::<u16>::new();
+        let feature_count = read_u16(data, feature_list)? as usize;
+        for i in 0..feature_count {
+            let record = feature_list + 2 + i * 6;
+            if data.get(record..record + 4)? != b"kern" { continue; }
+            let feature = feature_list + read_u16(data, record + 4)? as usize;
+            let count = read_u16(data, feature + 2)? as usize;
+            for j in 0..count {
+                let index = read_u16(data, feature + 4 + j * 2)?;
+                if !lookup_indices.contains(&index) {
+                    lookup_indices.push(index);
+                }
+            }
+        }
+        lookup_indices.sort();
+
+        let mut lookups = Vec::<Vec<usize>>::new();
+        let lookup_count = read_u16(data, lookup_list)?;
+        for index in lookup_indices {
+            if index >= lookup_count { continue; }
+            let lookup = lookup_list + read_u16(data, lookup_list + 2 + index as usize * 2)? as usize;
+            let lookup_type = read_u16(data, lookup)?;
+            let sub_table_count = read_u16(data, lookup + 4)? as usize;
+            let mut sub_tables = Vec::<usize>::new();
+            for j in 0..sub_table_count {
+                let mut sub_table = lookup + read_u16(data, lookup + 6 + j * 2)? as usize;
+                if lookup_type == LOOKUP_TYPE_EXTENSION {
+                    if read_u16(data, sub_table + 2)? != LOOKUP_TYPE_PAIR { continue; }
+                    sub_table += read_u32(data, sub_table + 4)? as usize;
+                } else if lookup_type != LOOKUP_TYPE_PAIR {
+                    continue;
+                }
+                sub_tables.push(sub_table);
+            }
+            if !sub_tables.is_empty() {
+                lookups.push(sub_tables);
+            }
+        }
+        Some(lookups)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lookups.is_empty()
+    }
+
+    /// 返回左侧字形 XAdvance 的调整值，字体单位
+    pub fn get_kerning(&self, data: &[u8], left: u16, right: u16) -> i32 {
+        let mut kerning = 0i32;
+        for sub_tables in self.lookups.iter() {
+            for sub_table in sub_tables.iter() {
+                if let Some(value) = Self::get_pair_value(data, *sub_table, left, right) {
+                    kerning += value as i32;
+                    break;
+                }
+            }
+        }
+        kerning
+    }
+
+    fn get_pair_value(data: &[u8], sub_table: usize, left: u16, right: u16) -> Option<i16> {
+        let format = read_u16(data, sub_table)?;
+        let coverage = sub_table + read_u16(data, sub_table + 2)? as usize;
+        let coverage_index = coverage_index(data, coverage, left)?;
+        let value_format1 = read_u16(data, sub_table + 4)?;
+        let value_format2 = read_u16(data, sub_table + 6)?;
+        let size1 = value_record_size(value_format1);
+        let size2 = value_record_size(value_format2);
+        match format {
+            1 => {
+                let pair_set_count = read_u16(data, sub_table + 8)? as usize;
+                if coverage_index >= pair_set_count { return None; }
+                let pair_set = sub_table + read_u16(data, sub_table + 10 + coverage_index * 2)? as usize;
+                let count = read_u16(data, pair_set)? as usize;
+                let record_size = 2 + size1 + size2;
+                let (mut low, mut high) = (0usize, count);
+                while low < high {
+                    let mid = (low + high) / 2;
+                    let record = pair_set + 2 + mid * record_size;
+                    let second = read_u16(data, record)?;
+                    if second == right {
+                        return value_record_x_advance(data, record + 2, value_format1);
+                    } else if second < right {
+                        low = mid + 1;
+                    } else {
+                        high = mid;
+                    }
+                }
+                None
+            }
+            2 => {
+                let class_def1 = sub_table + read_u16(data, sub_table + 8)? as usize;
+                let class_def2 = sub_table + read_u16(data, sub_table + 10)? as usize;
+                let class1_count = read_u16(data, sub_table + 12)?;
+                let class2_count = read_u16(data, sub_table + 14)?;
+                let class1 = class_of(data, class_def1, left);
+                let class2 = class_of(data, class_def2, right);
+                if class1 >= class1_count || class2 >= class2_count { return None; }
+                let record = sub_table + 16
+                    + (class1 as usize * class2_count as usize + class2 as usize) * (size1 + size2);
+                value_record_x_advance(data, record, value_format1)
+            }
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_kern_value, PairKerning};
+
+    fn push_u16(data: &mut Vec<u8>, values: &[i32]) {
+        for value in values {
+            data.extend_from_slice(&(*value as u16).to_be_bytes());
+        }
+    }
+
+    /// 只有 GPOS 表的字体，`kern` 特性引用一个 PairPos 子表
+    fn gpos_font(sub_table: &[u8]) -> Vec<u8> {
+        let mut data = vec![0, 1, 0, 0, 0, 1, 0, 16, 0, 0, 0, 0];
+        data.extend_from_slice(b"GPOS");
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 28]);
+        data.extend_from_slice(&(36 + sub_table.len() as u32).to_be_bytes());
+        // 头部、FeatureList 与 LookupList
+        push_u16(&mut data, &[1, 0, 0, 10, 24]);
+        data.extend_from_slice(&[0, 1]);
+        data.extend_from_slice(b"kern");
+        push_u16(&mut data, &[8, 0, 1, 0]);
+        push_u16(&mut data, &[1, 4, 2, 0, 1, 8]);
+        data.extend_from_slice(sub_table);
+        data
+    }
+
+    #[test]
+    fn test_pair_pos_format1() {
+        let mut sub_table = vec![];
+        // 覆盖表格式 1 只有字形 10，第二个字形 20 与 30 的 XAdvance
+        push_u16(&mut sub_table, &[1, 12, 0x0004, 0, 1, 18]);
+        push_u16(&mut sub_table, &[1, 1, 10]);
+        push_u16(&mut sub_table, &[2, 20, -50, 30, 40]);
+        let data = gpos_font(&sub_table);
+        let kerning = PairKerning::new(&data, 0);
+        assert!(!kerning.is_empty());
+        assert_eq!(kerning.get_kerning(&data, 10, 20), -50);
+        assert_eq!(kerning.get_kerning(&data, 10, 30), 40);
+        assert_eq!(kerning.get_kerning(&data, 10, 25), 0);
+        assert_eq!(kerning.get_kerning(&data, 11, 20), 0);
+    }
+
+    #[test]
+    fn test_pair_pos_format2() {
+        let mut sub_table = vec![];
+        // ValueRecord 含 XPlacement 与 XAdvance，两个类各 2 个
+        push_u16(&mut sub_table, &[2, 32, 0x0005, 0, 42, 58, 2, 2]);
+        push_u16(&mut sub_table, &[0, 0, 0, 0, 0, 0, 7, -80]);
+        // 覆盖表格式 2：字形 5 到 9
+        push_u16(&mut sub_table, &[2, 1, 5, 9, 0]);
+        // ClassDef 格式 1：字形 6、7 为类 1
+        push_u16(&mut sub_table, &[1, 5, 5, 0, 1, 1, 0, 0]);
+        // ClassDef 格式 2：字形 20 到 29 为类 1
+        push_u16(&mut sub_table, &[2, 1, 20, 29, 1]);
+        let data = gpos_font(&sub_table);
+        let kerning = PairKerning::new(&data, 0);
+        assert_eq!(kerning.get_kerning(&data, 6, 25), -80);
+        assert_eq!(kerning.get_kerning(&data, 7, 20), -80);
+        assert_eq!(kerning.get_kerning(&data, 5, 25), 0);
+        assert_eq!(kerning.get_kerning(&data, 6, 30), 0);
+        assert_eq!(kerning.get_kerning(&data, 4, 25), 0);
+    }
+
+    #[test]
+    fn test_kern_table() {
+        let mut data = vec![];
+        // 版本 0 的 kern 表，一个格式 0 的水平子表
+        push_u16(&mut data, &[0, 1]);
+        push_u16(&mut data, &[0, 14 + 2 * 6, 0x0001, 2, 12, 1, 0]);
+        push_u16(&mut data, &[1, 2, -30, 1, 5, 12]);
+        assert_eq!(get_kern_value(&data, 0, 1, 2), Some(-30));
+        assert_eq!(get_kern_value(&data, 0, 1, 5), Some(12));
+        assert_eq!(get_kern_value(&data, 0, 2, 1), None);
+        // 垂直子表不参与
+        data[9] = 0x00;
+        assert_eq!(get_kern_value(&data, 0, 1, 2), None);
+    }
+}
@@ -2,7 +2,13 @@ extern crate core;
 
 pub mod ttf;
 pub mod woff;
-// pub mod otf;
+pub mod woff2;
+pub mod kern;
+pub mod face;
+pub mod variation;
+pub mod otf;
+pub mod color;
+pub mod subset;
 // pub mod font2;
 
 pub mod check {
@@ -16,19 +22,35 @@ pub mod check {
     const SFNT_VERSION_OPEN_TYPE: u32 = 0x4F54544F;
     // wOFF
     const SFNT_VERSION_WOFF: u32 = 0x774f4646;
+    // wOF2
+    const SFNT_VERSION_WOFF2: u32 = 0x774f4632;
+    // ttcf
+    const SFNT_VERSION_COLLECTION: u32 = 0x74746366;
 
     fn get_data(data: &[u8], offset: usize) -> Option<u32> {
-        let r = data.get(offset..offset + 4)?;
+        let r = data.get(offset..offset.checked_add(4)?)?;
         Some(u32::from_be_bytes([r[0], r[1], r[2], r[3]]))
     }
 
-    pub fn check_type(data: &[u8]) -> Option<(String, bool)> {
-        let signature = get_data(data, 0)?;
+    fn get_sfnt_type(signature: u32) -> Option<String> {
         if signature == SFNT_VERSION_TRUE_TYPE1 || signature == SFNT_VERSION_TRUE_TYPE2 || signature == SFNT_VERSION_TRUE_TYPE3 {
-            Some(("ttf".to_string(), false))
+            Some("ttf".to_string())
         } else if signature == SFNT_VERSION_OPEN_TYPE {
-            Some(("otf".to_string(), false))
-        } else if signature == SFNT_VERSION_WOFF {
+            Some("otf".to_string())
+        } else {
+            None
+        }
+    }
+
+    pub fn check_type(data: &[u8]) -> Option<(String, bool)> {
+        let signature = get_data(data, 0)?;
+        if let Some(typ) = get_sfnt_type(signature) {
+            Some((typ, false))
+        } else if signature == SFNT_VERSION_COLLECTION {
+            // 字体集合按第一个字体的类型处理，偏移为 0 或指向另一个集合头时不合法
+            let offset = get_face_offset(data, 0).filter(|offset| *offset > 0)?;
+            Some((get_sfnt_type(get_data(data, offset)?)?, false))
+        } else if signature == SFNT_VERSION_WOFF || signature == SFNT_VERSION_WOFF2 {
             let tag = get_data(data, 4)?;
             if tag == SFNT_VERSION_TRUE_TYPE1 {
                 Some(("ttf".to_string(), true))
@@ -41,4 +63,72 @@ pub mod check {
             None
         }
     }
+
+    pub fn is_woff2(data: &[u8]) -> bool {
+        get_data(data, 0) == Some(SFNT_VERSION_WOFF2)
+    }
+
+    pub fn is_collection(data: &[u8]) -> bool {
+        get_data(data, 0) == Some(SFNT_VERSION_COLLECTION)
+    }
+
+    /// 字体集合中字体的数量，单个字体为 1
+    pub fn get_face_count(data: &[u8]) -> u32 {
+        if is_collection(data) {
+            get_data(data, 8).unwrap_or(0)
+        } else {
+            1
+        }
+    }
+
+    /// 第 `index` 个字体的表目录在数据中的偏移，单个字体只有 0
+    pub fn get_face_offset(data: &[u8], index: u32) -> Option<usize> {
+        if is_collection(data) {
+            if index >= get_face_count(data) {
+                return None;
+            }
+            get_data(data, 12 + index as usize * 4).map(|offset| offset as usize)
+        } else if index == 0 {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{check_type, get_face_count, get_face_offset};
+
+        /// 字体集合头，`offsets` 为各字体的表目录偏移
+        fn get_collection(offsets: &[u32], face: &[u8]) -> Vec<u8> {
+            let mut data = b"ttcf".to_vec();
+            data.extend_from_slice(&0x00010000u32.to_be_bytes());
+            data.extend_from_slice(&(offsets.len() as u32).to_be_bytes());
+            for offset in offsets.iter() {
+                data.extend_from_slice(&offset.to_be_bytes());
+            }
+            data.extend_from_slice(face);
+            data
+        }
+
+        #[test]
+        fn test_check_type() {
+            assert_eq!(check_type(b"OTTO\0\0"), Some(("otf".to_string(), false)));
+            assert_eq!(check_type(b"wOFF\0\x01\0\0"), Some(("ttf".to_string(), true)));
+            assert_eq!(check_type(b"abc"), None);
+            let data = get_collection(&[20, 20], b"OTTO");
+            assert_eq!(check_type(&data), Some(("otf".to_string(), false)));
+            assert_eq!((get_face_count(&data), get_face_offset(&data, 1), get_face_offset(&data, 2)), (2, Some(20), None));
+        }
+
+        #[test]
+        fn test_malformed_collection() {
+            // 指向自身、指向另一个集合头或超出数据的偏移都不合法
+            assert_eq!(check_type(&get_collection(&[0], b"")), None);
+            assert_eq!(check_type(&get_collection(&[16], &get_collection(&[0], b""))), None);
+            assert_eq!(check_type(&get_collection(&[u32::MAX], b"")), None);
+            assert_eq!(check_type(&get_collection(&[], b"")), None);
+            assert_eq!(check_type(b"ttcf\0\x01\0\0"), None);
+        }
+    }
 }
@@ -1,6 +1,983 @@
-use crate::core::open_type_like::glyph::Glyph;
 use crate::core::open_type_like::path::PathData;
-use crate::core::typesetting::MergedFont;
-use std::borrow::Cow;
-use std::collections::HashMap;
+use crate::kern::{find_table, get_kern_value, read_u16, read_u32};
+use crate::variation::get_region_scalars;
 
+/// Type 2 charstring 参数栈的最大深度，CFF2 为 513
+const MAX_STACK: usize = 513;
+/// 子程序嵌套的最大层数
+const MAX_SUBR_DEPTH: usize = 10;
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+/// `OTTO` 字体中 stb_truetype 不能读取的部分：cmap、水平度量、`kern` 表与 CFF 轮廓，
+/// 只有 CBDT 或 sbix 位图而没有轮廓的字体也由此读取
+pub struct OtfFont {
+    cmap: Option<usize>,
+    hmtx: usize,
+    h_metric_count: usize,
+    kern: Option<usize>,
+    pub units_per_em: u16,
+    /// `hhea` 中的 ascender、descender 与 lineGap
+    pub v_metrics: (i32, i32, i32),
+    cff: Option<Cff>,
+}
+
+impl OtfFont {
+    /// 缺少 `CFF ` 或 `CFF2` 表且没有彩色位图，以及缺少必需的度量表时返回 `None`
+    pub fn new(data: &[u8], face_offset: usize) -> Option<Self> {
+        let (head, _) = find_table(data, face_offset, b"head")?;
+        let (hhea, _) = find_table(data, face_offset, b"hhea")?;
+        let (hmtx, _) = find_table(data, face_offset, b"hmtx")?;
+        let cff = match (find_table(data, face_offset, b"CFF2"), find_table(data, face_offset, b"CFF ")) {
+            (Some((offset, length)), _) => Some(Cff::new(data, offset, length, true)?),
+            (None, Some((offset, length))) => Some(Cff::new(data, offset, length, false)?),
+            _ if find_table(data, face_offset, b"CBDT").is_some() || find_table(data, face_offset, b"sbix").is_some() => None,
+            _ => return None
+        };
+        Some(OtfFont {
+            cmap: find_cmap(data, face_offset),
+            hmtx,
+            h_metric_count: read_u16(data, hhea + 34)? as usize,
+            kern: find_table(data, face_offset, b"kern").map(|(offset, _)| offset),
+            units_per_em: read_u16(data, head + 18)?,
+            v_metrics: (read_i16(data, hhea + 4)? as i32, read_i16(data, hhea + 6)? as i32, read_i16(data, hhea + 8)? as i32),
+            cff,
+        })
+    }
+
+    /// 字符对应的字形，没有时返回 0
+    pub fn find_glyph_index(&self, data: &[u8], c: u32) -> u32 {
+        self.cmap.and_then(|cmap| get_cmap_glyph(data, cmap, c)).unwrap_or(0)
+    }
+
+    /// 前进宽度与左侧轴承
+    pub fn get_h_metrics(&self, data: &[u8], glyph_id: u32) -> (i32, i32) {
+        let index = glyph_id as usize;
+        let count = self.h_metric_count.max(1);
+        let advance = read_u16(data, self.hmtx + index.min(count - 1) * 4).unwrap_or(0) as i32;
+        let lsb = if index < count {
+            read_i16(data, self.hmtx + index * 4 + 2)
+        } else {
+            read_i16(data, self.hmtx + count * 4 + (index - count) * 2)
+        };
+        (advance, lsb.unwrap_or(0) as i32)
+    }
+
+    /// `kern` 表格式 0 中的字偶距
+    pub fn get_kern_advance(&self, data: &[u8], left: u32, right: u32) -> i32 {
+        self.kern.and_then(|kern| get_kern_value(data, kern, left, right)).unwrap_or(0)
+    }
+
+    /// CFF 轮廓，`coords` 为 CFF2 可变字体归一化后的坐标
+    pub fn get_glyph_path(&self, data: &[u8], glyph_id: u32, coords: &[f32]) -> Option<PathData> {
+        self.cff.as_ref()?.get_glyph_path(data, glyph_id, coords)
+    }
+}
+
+/// 优先使用完整 Unicode 的子表，其次为 BMP
+fn find_cmap(data: &[u8], face_offset: usize) -> Option<usize> {
+    let (cmap, _) = find_table(data, face_offset, b"cmap")?;
+    let count = read_u16(data, cmap + 2)? as usize;
+    let mut best: Option<(u8, usize)> = None;
+    for i in 0..count {
+        let record = cmap + 4 + i * 8;
+        let platform = read_u16(data, record)?;
+        let encoding = read_u16(data, record + 2)?;
+        let offset = cmap + read_u32(data, record + 4)? as usize;
+        let rank = match (platform, encoding) {
+            (3, 10) | (0, 4) | (0, 6) => 0,
+            (3, 1) | (0, _) => 1,
+            _ => continue
+        };
+        if ![0, 4, 6, 12, 13].contains(&read_u16(data, offset)?) { continue; }
+        if best.map(|(r, _)| rank < r).unwrap_or(true) {
+            best = Some((rank, offset));
+        }
+    }
+    best.map(|(_, offset)| offset)
+}
+
+fn get_cmap_glyph(data: &[u8], offset: usize, c: u32) -> Option<u32> {
+    match read_u16(data, offset)? {
+        0 => {
+            if c > 0xFF { return None; }
+            data.get(offset + 6 + c as usize).map(|glyph| *glyph as u32)
+        }
+        4 => {
+            if c > 0xFFFF { return None; }
+            let seg_count = read_u16(data, offset + 6)? as usize / 2;
+            let ends = offset + 14;
+            let starts = ends + seg_count * 2 + 2;
+            let deltas = starts + seg_count * 2;
+            let range_offsets = deltas + seg_count * 2;
+            let (mut low, mut high) = (0usize, seg_count);
+            while low < high {
+                let mid = (low + high) / 2;
+                if (read_u16(data, ends + mid * 2)? as u32) < c { low = mid + 1; } else { high = mid; }
+            }
+            if low >= seg_count { return None; }
+            let start = read_u16(data, starts + low * 2)? as u32;
+            if c < start { return None; }
+            let delta = read_u16(data, deltas + low * 2)? as u32;
+            let range_offset = read_u16(data, range_offsets + low * 2)? as usize;
+            if range_offset == 0 {
+                return Some((c + delta) & 0xFFFF);
+            }
+            let glyph = read_u16(data, range_offsets + low * 2 + range_offset + (c - start) as usize * 2)? as u32;
+            if glyph == 0 { None } else { Some((glyph + delta) & 0xFFFF) }
+        }
+        6 => {
+            let first = read_u16(data, offset + 6)? as u32;
+            let count = read_u16(data, offset + 8)? as u32;
+            if c < first || c >= first + count { return None; }
+            read_u16(data, offset + 10 + (c - first) as usize * 2).map(|glyph| glyph as u32)
+        }
+        format @ 12 | format @ 13 => {
+            let count = read_u32(data, offset + 12)? as usize;
+            let (mut low, mut high) = (0usize, count);
+            while low < high {
+                let mid = (low + high) / 2;
+                let group = offset + 16 + mid * 12;
+                if read_u32(data, group + 4)? < c {
+                    low = mid + 1;
+                } else if read_u32(data, group)? > c {
+                    high = mid;
+                } else {
+                    let glyph = read_u32(data, group + 8)?;
+                    return Some(if format == 12 { glyph + c - read_u32(data, group)? } else { glyph });
+                }
+            }
+            None
+        }
+        _ => None
+    }
+}
+
+/// INDEX 结构，偏移相对于 CFF 表
+#[derive(Debug, Clone, Copy)]
+struct Index {
+    count: usize,
+    off_size: usize,
+    offsets: usize,
+    base: usize,
+}
+
+impl Index {
+    /// 返回 INDEX 及其后第一个字节的位置，CFF2 中数量为 4 字节
+    fn new(data: &[u8], offset: usize, cff2: bool) -> Option<(Index, usize)> {
+        let (count, header) = if cff2 {
+            (read_u32(data, offset)? as usize, 4)
+        } else {
+            (read_u16(data, offset)? as usize, 2)
+        };
+        if count == 0 {
+            let index = Index { count, off_size: 1, offsets: offset + header, base: offset + header };
+            return Some((index, offset + header));
+        }
+        let off_size = *data.get(offset + header)? as usize;
+        if !(1..=4).contains(&off_size) { return None; }
+        let offsets = offset + header + 1;
+        let index = Index { count, off_size, offsets, base: offsets + (count + 1) * off_size - 1 };
+        let end = index.base + index.read_offset(data, count)?;
+        Some((index, end))
+    }
+
+    fn read_offset(&self, data: &[u8], i: usize) -> Option<usize> {
+        let start = self.offsets + i * self.off_size;
+        Some(data.get(start..start + self.off_size)?.iter().fold(0usize, |value, b| (value << 8) | *b as usize))
+    }
+
+    fn get<'a>(&self, data: &'a [u8], i: usize) -> Option<&'a [u8]> {
+        if i >= self.count { return None; }
+        let start = self.base + self.read_offset(data, i)?;
+        let end = self.base + self.read_offset(data, i + 1)?;
+        data.get(start..end)
+    }
+}
+
+/// 子程序编号的偏移量
+fn get_subr_bias(count: usize) -> i32 {
+    if count < 1240 { 107 } else if count < 33900 { 1131 } else { 32768 }
+}
+
+/// DICT 中的操作符与操作数，两字节操作符记为 1200 加第二个字节，实数不需要具体的值，记为 0
+fn parse_dict(dict: &[u8]) -> Vec<(u16, Vec<i32>)> {
+    let mut result = Vec::<(u16, Vec<i32>)>::new();
+    let mut operands = Vec::<i32>::new();
+    let mut i = 0usize;
+    while let Some(b0) = dict.get(i).cloned() {
+        i += 1;
+        match b0 {
+            12 => {
+                let b1 = dict.get(i).cloned().unwrap_or(0);
+                i += 1;
+                result.push((1200 + b1 as u16, std::mem::take(&mut operands)));
+            }
+            0..=24 => result.push((b0 as u16, std::mem::take(&mut operands))),
+            28 => {
+                operands.push(read_i16(dict, i).unwrap_or(0) as i32);
+                i += 2;
+            }
+            29 => {
+                operands.push(read_u32(dict, i).unwrap_or(0) as i32);
+                i += 4;
+            }
+            30 => {
+                while let Some(b) = dict.get(i) {
+                    i += 1;
+                    if b & 0x0F == 0x0F || b >> 4 == 0x0F { break; }
+                }
+                operands.push(0);
+            }
+            32..=246 => operands.push(b0 as i32 - 139),
+            247..=250 => {
+                operands.push((b0 as i32 - 247) * 256 + dict.get(i).cloned().unwrap_or(0) as i32 + 108);
+                i += 1;
+            }
+            251..=254 => {
+                operands.push(-(b0 as i32 - 251) * 256 - dict.get(i).cloned().unwrap_or(0) as i32 - 108);
+                i += 1;
+            }
+            _ => break
+        }
+    }
+    result
+}
+
+fn get_dict_operands(dict: &[(u16, Vec<i32>)], operator: u16) -> Option<&[i32]> {
+    dict.iter().find(|(op, _)| *op == operator).map(|(_, operands)| operands.as_slice())
+}
+
+/// Private DICT 中的局部子程序与默认的 vsindex
+#[derive(Debug, Clone, Copy, Default)]
+struct Private {
+    subrs: Option<Index>,
+    vsindex: usize,
+}
+
+impl Private {
+    fn new(cff: &[u8], operands: &[i32], cff2: bool) -> Option<Self> {
+        let (size, offset) = match operands {
+            [size, offset] => (*size as usize, *offset as usize),
+            _ => return None
+        };
+        let dict = parse_dict(cff.get(offset..offset + size)?);
+        let subrs = get_dict_operands(&dict, 19)
+            .and_then(|operands| operands.first())
+            .and_then(|subrs| Index::new(cff, offset + *subrs as usize, cff2))
+            .map(|(index, _)| index);
+        let vsindex = get_dict_operands(&dict, 22).and_then(|operands| operands.first()).cloned().unwrap_or(0);
+        Some(Private { subrs, vsindex: vsindex.max(0) as usize })
+    }
+}
+
+/// DICT 中每一项的操作符与包括操作数的原始字节
+fn split_dict(dict: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut result = Vec::<(u16, &[u8])>::new();
+    let (mut start, mut i) = (0usize, 0usize);
+    while let Some(b0) = dict.get(i).cloned() {
+        i += 1;
+        match b0 {
+            12 => {
+                let b1 = dict.get(i).cloned().unwrap_or(0);
+                i = (i + 1).min(dict.len());
+                result.push((1200 + b1 as u16, &dict[start..i]));
+                start = i;
+            }
+            0..=24 => {
+                result.push((b0 as u16, &dict[start..i]));
+                start = i;
+            }
+            28 => i += 2,
+            29 => i += 4,
+            30 => {
+                while let Some(b) = dict.get(i) {
+                    i += 1;
+                    if b & 0x0F == 0x0F || b >> 4 == 0x0F { break; }
+                }
+            }
+            32..=246 => {}
+            247..=254 => i += 1,
+            _ => break
+        }
+    }
+    result
+}
+
+/// 去掉这些操作符原有的项，再按 5 字节整数写入新的操作数，操作数为空的只去掉
+fn rewrite_dict(dict: &[u8], operators: &[(u16, Vec<usize>)]) -> Vec<u8> {
+    let mut result = Vec::<u8>::new();
+    for (operator, raw) in split_dict(dict) {
+        if !operators.iter().any(|(op, _)| *op == operator) { result.extend_from_slice(raw); }
+    }
+    for (operator, operands) in operators.iter().filter(|(_, operands)| !operands.is_empty()) {
+        for operand in operands {
+            result.push(29);
+            result.extend_from_slice(&(*operand as u32).to_be_bytes());
+        }
+        if *operator >= 1200 {
+            result.extend_from_slice(&[12, (*operator - 1200) as u8]);
+        } else {
+            result.push(*operator as u8);
+        }
+    }
+    result
+}
+
+/// 写入 INDEX，CFF2 中数量为 4 字节
+fn build_index(items: &[&[u8]], cff2: bool) -> Vec<u8> {
+    let mut result = Vec::<u8>::new();
+    if cff2 {
+        result.extend_from_slice(&(items.len() as u32).to_be_bytes());
+    } else {
+        result.extend_from_slice(&(items.len() as u16).to_be_bytes());
+    }
+    if items.is_empty() { return result; }
+    let end = items.iter().map(|item| item.len()).sum::<usize>() + 1;
+    let off_size = match end {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x10000..=0xFF_FFFF => 3,
+        _ => 4
+    };
+    result.push(off_size as u8);
+    let mut offset = 1usize;
+    result.extend_from_slice(&(offset as u32).to_be_bytes()[4 - off_size..]);
+    for item in items {
+        offset += item.len();
+        result.extend_from_slice(&(offset as u32).to_be_bytes()[4 - off_size..]);
+    }
+    items.iter().for_each(|item| result.extend_from_slice(item));
+    result
+}
+
+/// 每个字形的 SID，CID 字体中为 CID，不支持 Expert 与 ExpertSubset 预定义字符集
+fn get_charset(cff: &[u8], offset: usize, num_glyphs: usize) -> Option<Vec<u16>> {
+    if offset == 0 { return Some((0..num_glyphs).map(|id| id as u16).collect()); }
+    if offset <= 2 { return None; }
+    let format = *cff.get(offset)?;
+    let mut ids = vec![0u16];
+    let mut i = offset + 1;
+    while ids.len() < num_glyphs {
+        match format {
+            0 => {
+                ids.push(read_u16(cff, i)?);
+                i += 2;
+            }
+            1 | 2 => {
+                let first = read_u16(cff, i)?;
+                let left = if format == 1 { *cff.get(i + 2)? as u16 } else { read_u16(cff, i + 2)? };
+                i += if format == 1 { 3 } else { 4 };
+                ids.extend((0..=left as u32).map(|k| first.wrapping_add(k as u16)));
+            }
+            _ => return None
+        }
+    }
+    ids.truncate(num_glyphs);
+    Some(ids)
+}
+
+/// 标准编码中 161 之后的字符，(编码, SID)
+const STANDARD_ENCODING: [(u8, u16); 54] = [
+    (161, 96), (162, 97), (163, 98), (164, 99), (165, 100), (166, 101), (167, 102), (168, 103), (169, 104),
+    (170, 105), (171, 106), (172, 107), (173, 108), (174, 109), (175, 110), (177, 111), (178, 112), (179, 113),
+    (180, 114), (182, 115), (183, 116), (184, 117), (185, 118), (186, 119), (187, 120), (188, 121), (189, 122),
+    (191, 123), (193, 124), (194, 125), (195, 126), (196, 127), (197, 128), (198, 129), (199, 130), (200, 131),
+    (202, 132), (203, 133), (205, 134), (206, 135), (207, 136), (208, 137), (225, 138), (227, 139), (232, 140),
+    (233, 141), (234, 142), (235, 143), (241, 144), (245, 145), (248, 146), (249, 147), (250, 148), (251, 149),
+];
+
+/// seac 的参数为标准编码
+fn get_standard_sid(code: u8) -> Option<u16> {
+    match code {
+        32..=126 => Some(code as u16 - 31),
+        _ => STANDARD_ENCODING.iter().find(|(c, _)| *c == code).map(|(_, sid)| *sid)
+    }
+}
+
+/// Private DICT 紧接着写入局部子程序，返回两者的数据与 DICT 的长度
+fn subset_private(cff: &[u8], operands: &[i32], cff2: bool) -> Option<(Vec<u8>, usize)> {
+    let (size, offset) = match operands {
+        [size, offset] => (*size as usize, *offset as usize),
+        _ => return None
+    };
+    let dict = cff.get(offset..offset + size)?;
+    let subrs = get_dict_operands(&parse_dict(dict), 19)
+        .and_then(|operands| operands.first())
+        .map(|subrs| offset + *subrs as usize)
+        .and_then(|start| Some((start, Index::new(cff, start, cff2)?.1)))
+        .and_then(|(start, end)| cff.get(start..end));
+    match subrs {
+        Some(subrs) => {
+            let size = rewrite_dict(dict, &[(19, vec![0])]).len();
+            let mut private = rewrite_dict(dict, &[(19, vec![size])]);
+            private.extend_from_slice(subrs);
+            Some((private, size))
+        }
+        None => {
+            let private = rewrite_dict(dict, &[(19, vec![])]);
+            let size = private.len();
+            Some((private, size))
+        }
+    }
+}
+
+/// `CFF ` 或 `CFF2` 表，CID 字体与 CFF2 按 FDSelect 选择 Private DICT
+#[derive(Debug, Clone)]
+pub(crate) struct Cff {
+    table: (usize, usize),
+    cff2: bool,
+    charset: usize,
+    char_strings: Index,
+    global_subrs: Index,
+    privates: Vec<Private>,
+    fd_select: Option<usize>,
+    var_store: Option<usize>,
+}
+
+impl Cff {
+    fn new(data: &[u8], offset: usize, length: usize, cff2: bool) -> Option<Self> {
+        let cff = data.get(offset..offset + length)?;
+        let header_size = *cff.get(2)? as usize;
+        let (top, global_subrs) = if cff2 {
+            let top_size = read_u16(cff, 3)? as usize;
+            (cff.get(header_size..header_size + top_size)?, header_size + top_size)
+        } else {
+            let (_, next) = Index::new(cff, header_size, false)?;
+            let (top, next) = Index::new(cff, next, false)?;
+            let (_, next) = Index::new(cff, next, false)?;
+            (top.get(cff, 0)?, next)
+        };
+        let (global_subrs, _) = Index::new(cff, global_subrs, cff2)?;
+        let top = parse_dict(top);
+        let (char_strings, _) = Index::new(cff, *get_dict_operands(&top, 17)?.first()? as usize, cff2)?;
+
+        let privates = match get_dict_operands(&top, 1236).and_then(|operands| operands.first()) {
+            Some(fd_array) => {
+                let (fd_array, _) = Index::new(cff, *fd_array as usize, cff2)?;
+                (0..fd_array.count).map(|i| {
+                    let dict = parse_dict(fd_array.get(cff, i)?);
+                    get_dict_operands(&dict, 18).and_then(|operands| Private::new(cff, operands, cff2))
+                }).map(|private| private.unwrap_or_default()).collect()
+            }
+            None => vec![get_dict_operands(&top, 18).and_then(|operands| Private::new(cff, operands, cff2)).unwrap_or_default()]
+        };
+        let fd_select = get_dict_operands(&top, 1237).and_then(|operands| operands.first()).map(|offset| *offset as usize);
+        // VariationStore 前两个字节为长度
+        let var_store = get_dict_operands(&top, 24).and_then(|operands| operands.first()).map(|offset| *offset as usize + 2);
+        let charset = get_dict_operands(&top, 15).and_then(|operands| operands.first()).cloned().unwrap_or(0);
+        Some(Cff { table: (offset, length), cff2, charset: charset.max(0) as usize, char_strings, global_subrs, privates, fd_select, var_store })
+    }
+
+    /// 字体中的 `CFF2` 或 `CFF ` 表
+    pub(crate) fn from_face(data: &[u8], face_offset: usize) -> Option<Self> {
+        match (find_table(data, face_offset, b"CFF2"), find_table(data, face_offset, b"CFF ")) {
+            (Some((offset, length)), _) => Cff::new(data, offset, length, true),
+            (None, Some((offset, length))) => Cff::new(data, offset, length, false),
+            _ => None
+        }
+    }
+
+    pub(crate) fn tag(&self) -> [u8; 4] {
+        if self.cff2 { *b"CFF2" } else { *b"CFF " }
+    }
+
+    /// seac 引用的基字与重音的字形序号，CID 字体与 CFF2 没有 seac
+    pub(crate) fn get_components(&self, data: &[u8], glyph_id: u32) -> Vec<u32> {
+        let cff = match data.get(self.table.0..self.table.0 + self.table.1) {
+            Some(cff) if !self.cff2 => cff,
+            _ => return vec![]
+        };
+        let (base, accent) = match self.execute(cff, glyph_id, &[]).and_then(|context| context.seac) {
+            Some(seac) => seac,
+            None => return vec![]
+        };
+        let charset = get_charset(cff, self.charset, self.char_strings.count).unwrap_or_default();
+        [base, accent].iter()
+            .filter_map(|code| get_standard_sid(*code))
+            .filter_map(|sid| charset.iter().position(|id| *id == sid))
+            .map(|glyph_id| glyph_id as u32)
+            .collect()
+    }
+
+    /// 只包含这些字形的表，新字形按参数的顺序编号，子程序、字符串与 VariationStore 全部保留，
+    /// DICT 中的偏移都写成 5 字节整数，先按占位的 0 计算长度再写入
+    pub(crate) fn subset(&self, data: &[u8], glyph_ids: &[u32]) -> Option<Vec<u8>> {
+        let cff = data.get(self.table.0..self.table.0 + self.table.1)?;
+        let cff2 = self.cff2;
+        let header_size = *cff.get(2)? as usize;
+        let (top, names, strings, global_start) = if cff2 {
+            let top_size = read_u16(cff, 3)? as usize;
+            (cff.get(header_size..header_size + top_size)?, vec![], &[][..], header_size + top_size)
+        } else {
+            let (names, next) = Index::new(cff, header_size, false)?;
+            let (top, strings) = Index::new(cff, next, false)?;
+            let (_, global_start) = Index::new(cff, strings, false)?;
+            (top.get(cff, 0)?, build_index(&[names.get(cff, 0)?], false), cff.get(strings..global_start)?, global_start)
+        };
+        let (_, global_end) = Index::new(cff, global_start, cff2)?;
+        let global_subrs = cff.get(global_start..global_end)?;
+        let top_dict = parse_dict(top);
+        let var_store = match self.var_store {
+            Some(offset) => cff.get(offset - 2..offset + read_u16(cff, offset - 2)? as usize)?,
+            None => &[]
+        };
+
+        let charset = if cff2 {
+            vec![]
+        } else {
+            let ids = get_charset(cff, self.charset, self.char_strings.count)?;
+            let mut charset = vec![0u8];
+            for glyph_id in glyph_ids.iter().skip(1) {
+                charset.extend_from_slice(&ids.get(*glyph_id as usize)?.to_be_bytes());
+            }
+            charset
+        };
+        let fd_select = match self.fd_select {
+            Some(_) => std::iter::once(Some(0u8))
+                .chain(glyph_ids.iter().map(|glyph_id| self.get_fd_index(cff, *glyph_id as usize).map(|fd| fd as u8)))
+                .collect::<Option<Vec<u8>>>()?,
+            None => vec![]
+        };
+        let char_strings = glyph_ids.iter()
+            .map(|glyph_id| self.char_strings.get(cff, *glyph_id as usize))
+            .collect::<Option<Vec<&[u8]>>>()?;
+        let char_strings = build_index(&char_strings, cff2);
+        // CID 字体与 CFF2 的 Private DICT 由 FDArray 中的 Font DICT 引用，否则由 Top DICT 引用
+        let font_dicts = match get_dict_operands(&top_dict, 1236).and_then(|operands| operands.first()) {
+            Some(fd_array) => {
+                let (fd_array, _) = Index::new(cff, *fd_array as usize, cff2)?;
+                (0..fd_array.count).map(|i| fd_array.get(cff, i)).collect::<Option<Vec<&[u8]>>>()?
+            }
+            None => vec![]
+        };
+        let privates = if font_dicts.is_empty() {
+            vec![get_dict_operands(&top_dict, 18).and_then(|operands| subset_private(cff, operands, cff2))]
+        } else {
+            font_dicts.iter().map(|dict| get_dict_operands(&parse_dict(dict), 18).and_then(|operands| subset_private(cff, operands, cff2))).collect()
+        };
+
+        let private_operands = |private: &Option<(Vec<u8>, usize)>, offset: usize| -> Vec<(u16, Vec<usize>)> {
+            vec![(18, private.iter().flat_map(|(_, size)| vec![*size, offset]).collect())]
+        };
+        let build_fd_array = |offsets: &[usize]| -> Vec<u8> {
+            let dicts: Vec<Vec<u8>> = font_dicts.iter().zip(privates.iter().zip(offsets.iter()))
+                .map(|(dict, (private, offset))| rewrite_dict(dict, &private_operands(private, *offset)))
+                .collect();
+            build_index(&dicts.iter().map(|dict| dict.as_slice()).collect::<Vec<&[u8]>>(), cff2)
+        };
+        let build_top = |offsets: &[usize; 6]| -> Vec<u8> {
+            let [charset_offset, fd_select_offset, char_strings_offset, fd_array_offset, private_offset, var_store_offset] = *offsets;
+            let mut operators = vec![(17, vec![char_strings_offset])];
+            if !cff2 { operators.push((15, vec![charset_offset])); }
+            if !fd_select.is_empty() { operators.push((1237, vec![fd_select_offset])); }
+            if font_dicts.is_empty() {
+                operators.extend(private_operands(&privates[0], private_offset));
+            } else {
+                operators.push((1236, vec![fd_array_offset]));
+            }
+            if !var_store.is_empty() { operators.push((24, vec![var_store_offset])); }
+            // 编码由 cmap 代替，去掉后为标准编码
+            operators.push((16, vec![]));
+            rewrite_dict(top, &operators)
+        };
+
+        let top_size = build_top(&[0; 6]).len();
+        let mut offset = header_size + names.len() + strings.len() + global_subrs.len() + if cff2 {
+            top_size
+        } else {
+            build_index(&[&vec![0; top_size][..]], false).len()
+        };
+        let var_store_offset = offset;
+        offset += var_store.len();
+        let charset_offset = offset;
+        offset += charset.len();
+        let fd_select_offset = offset;
+        offset += fd_select.len();
+        let char_strings_offset = offset;
+        offset += char_strings.len();
+        let fd_array_offset = offset;
+        if !font_dicts.is_empty() {
+            offset += build_fd_array(&vec![0; privates.len()]).len();
+        }
+        let mut private_offsets = Vec::<usize>::new();
+        for private in privates.iter() {
+            private_offsets.push(offset);
+            offset += private.as_ref().map(|(private, _)| private.len()).unwrap_or(0);
+        }
+
+        let top = build_top(&[charset_offset, fd_select_offset, char_strings_offset, fd_array_offset, private_offsets[0], var_store_offset]);
+        let mut result = Vec::<u8>::with_capacity(offset);
+        result.extend_from_slice(cff.get(0..header_size)?);
+        if cff2 {
+            result.get_mut(3..5)?.copy_from_slice(&(top.len() as u16).to_be_bytes());
+            result.extend_from_slice(&top);
+        } else {
+            result.extend_from_slice(&names);
+            result.extend_from_slice(&build_index(&[&top], false));
+            result.extend_from_slice(strings);
+        }
+        result.extend_from_slice(global_subrs);
+        result.extend_from_slice(var_store);
+        result.extend_from_slice(&charset);
+        result.extend_from_slice(&fd_select);
+        result.extend_from_slice(&char_strings);
+        if !font_dicts.is_empty() {
+            result.extend_from_slice(&build_fd_array(&private_offsets));
+        }
+        for (private, _) in privates.iter().flatten() {
+            result.extend_from_slice(private);
+        }
+        Some(result)
+    }
+
+    fn get_fd_index(&self, cff: &[u8], glyph_id: usize) -> Option<usize> {
+        let offset = match self.fd_select {
+            Some(offset) => offset,
+            None => return Some(0)
+        };
+        match *cff.get(offset)? {
+            0 => cff.get(offset + 1 + glyph_id).map(|fd| *fd as usize),
+            3 | 4 => {
+                let long = cff[offset] == 4;
+                let (count, ranges, size) = if long {
+                    (read_u32(cff, offset + 1)? as usize, offset + 5, 6)
+                } else {
+                    (read_u16(cff, offset + 1)? as usize, offset + 3, 3)
+                };
+                let first = |i: usize| if long { read_u32(cff, ranges + i * size).map(|v| v as usize) } else { read_u16(cff, ranges + i * size).map(|v| v as usize) };
+                for i in 0..count {
+                    if first(i)? <= glyph_id && glyph_id < first(i + 1)? {
+                        return if long { read_u16(cff, ranges + i * size + 4).map(|fd| fd as usize) } else { cff.get(ranges + i * size + 2).map(|fd| *fd as usize) };
+                    }
+                }
+                None
+            }
+            _ => None
+        }
+    }
+
+    fn get_glyph_path(&self, data: &[u8], glyph_id: u32, coords: &[f32]) -> Option<PathData> {
+        let cff = data.get(self.table.0..self.table.0 + self.table.1)?;
+        Some(self.execute(cff, glyph_id, coords)?.path)
+    }
+
+    fn execute<'a>(&'a self, cff: &'a [u8], glyph_id: u32, coords: &'a [f32]) -> Option<CharStringContext<'a>> {
+        let char_string = self.char_strings.get(cff, glyph_id as usize)?;
+        let private = self.get_fd_index(cff, glyph_id as usize).and_then(|fd| self.privates.get(fd)).cloned().unwrap_or_default();
+        let mut context = CharStringContext {
+            cff: self,
+            data: cff,
+            private,
+            coords,
+            scalars: None,
+            vsindex: private.vsindex,
+            stack: Vec::new(),
+            path: PathData::new(),
+            point: (0f32, 0f32),
+            stem_count: 0,
+            width_parsed: self.cff2,
+            is_open: false,
+            seac: None,
+        };
+        context.execute(char_string, 0)?;
+        context.close_contour();
+        Some(context)
+    }
+}
+
+/// 执行 Type 2 charstring 时的状态
+struct CharStringContext<'a> {
+    cff: &'a Cff,
+    data: &'a [u8],
+    private: Private,
+    coords: &'a [f32],
+    scalars: Option<Vec<f32>>,
+    vsindex: usize,
+    stack: Vec<f32>,
+    path: PathData,
+    point: (f32, f32),
+    stem_count: usize,
+    width_parsed: bool,
+    is_open: bool,
+    seac: Option<(u8, u8)>,
+}
+
+impl<'a> CharStringContext<'a> {
+    /// CFF 中第一个清空栈的操作符前可能多一个宽度参数，宽度从 `hmtx` 读取，这里只去掉
+    fn skip_width(&mut self, has_width: bool) {
+        if !self.width_parsed {
+            self.width_parsed = true;
+            if has_width && !self.stack.is_empty() { self.stack.remove(0); }
+        }
+    }
+
+    fn close_contour(&mut self) {
+        if self.is_open {
+            self.path.close();
+            self.is_open = false;
+        }
+    }
+
+    fn move_to(&mut self, dx: f32, dy: f32) {
+        self.close_contour();
+        self.point = (self.point.0 + dx, self.point.1 + dy);
+        self.path.move_to(self.point.0, self.point.1);
+        self.is_open = true;
+    }
+
+    fn ensure_open(&mut self) {
+        if !self.is_open {
+            self.path.move_to(self.point.0, self.point.1);
+            self.is_open = true;
+        }
+    }
+
+    fn line_to(&mut self, dx: f32, dy: f32) {
+        self.ensure_open();
+        self.point = (self.point.0 + dx, self.point.1 + dy);
+        self.path.line_to(self.point.0, self.point.1);
+    }
+
+    fn curve_to(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+        self.ensure_open();
+        let (x1, y1) = (self.point.0 + dx1, self.point.1 + dy1);
+        let (x2, y2) = (x1 + dx2, y1 + dy2);
+        self.point = (x2 + dx3, y2 + dy3);
+        self.path.curve_to(self.point.0, self.point.1, x1, y1, x2, y2);
+    }
+
+    /// CFF2 的 blend，按当前 vsindex 对应区域的权重合并默认值与变化量
+    fn blend(&mut self) -> Option<()> {
+        let count = self.stack.pop()? as usize;
+        if self.scalars.is_none() {
+            let store = self.cff.var_store?;
+            self.scalars = Some(get_region_scalars(self.data, store, self.vsindex, self.coords)?);
+        }
+        let scalars = self.scalars.as_ref()?;
+        let region_count = scalars.len();
+        let base = self.stack.len().checked_sub(count * (region_count + 1))?;
+        for i in 0..count {
+            let deltas = base + count + i * region_count;
+            let delta: f32 = scalars.iter().enumerate().map(|(j, scalar)| self.stack[deltas + j] * scalar).sum();
+            self.stack[base + i] += delta;
+        }
+        self.stack.truncate(base + count);
+        Some(())
+    }
+
+    /// 返回 `true` 表示遇到 endchar
+    fn execute(&mut self, code: &[u8], depth: usize) -> Option<bool> {
+        if depth > MAX_SUBR_DEPTH { return None; }
+        let mut i = 0usize;
+        while let Some(b0) = code.get(i).cloned() {
+            i += 1;
+            if self.stack.len() > MAX_STACK { return None; }
+            match b0 {
+                // hstem vstem hstemhm vstemhm
+                1 | 3 | 18 | 23 => {
+                    self.skip_width(self.stack.len() % 2 == 1);
+                    self.stem_count += self.stack.len() / 2;
+                    self.stack.clear();
+                }
+                // hintmask cntrmask，之前的参数为隐含的 vstem
+                19 | 20 => {
+                    self.skip_width(self.stack.len() % 2 == 1);
+                    self.stem_count += self.stack.len() / 2;
+                    self.stack.clear();
+                    i += self.stem_count.div_ceil(8);
+                }
+                // rmoveto
+                21 => {
+                    self.skip_width(self.stack.len() > 2);
+                    let (dx, dy) = (*self.stack.first()?, *self.stack.get(1)?);
+                    self.move_to(dx, dy);
+                    self.stack.clear();
+                }
+                // hmoveto
+                22 => {
+                    self.skip_width(self.stack.len() > 1);
+                    let dx = *self.stack.first()?;
+                    self.move_to(dx, 0f32);
+                    self.stack.clear();
+                }
+                // vmoveto
+                4 => {
+                    self.skip_width(self.stack.len() > 1);
+                    let dy = *self.stack.first()?;
+                    self.move_to(0f32, dy);
+                    self.stack.clear();
+                }
+                // rlineto
+                5 => {
+                    let stack = std::mem::take(&mut self.stack);
+                    for pair in stack.chunks_exact(2) {
+                        self.line_to(pair[0], pair[1]);
+                    }
+                }
+                // hlineto vlineto，水平与竖直交替
+                6 | 7 => {
+                    let stack = std::mem::take(&mut self.stack);
+                    let mut horizontal = b0 == 6;
+                    for value in stack {
+                        if horizontal { self.line_to(value, 0f32); } else { self.line_to(0f32, value); }
+                        horizontal = !horizontal;
+                    }
+                }
+                // rrcurveto
+                8 => {
+                    let stack = std::mem::take(&mut self.stack);
+                    for s in stack.chunks_exact(6) {
+                        self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+                    }
+                }
+                // rcurveline
+                24 => {
+                    let stack = std::mem::take(&mut self.stack);
+                    if stack.len() < 8 { return None; }
+                    let (curves, line) = stack.split_at(stack.len() - 2);
+                    for s in curves.chunks_exact(6) {
+                        self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+                    }
+                    self.line_to(line[0], line[1]);
+                }
+                // rlinecurve
+                25 => {
+                    let stack = std::mem::take(&mut self.stack);
+                    if stack.len() < 8 { return None; }
+                    let (lines, s) = stack.split_at(stack.len() - 6);
+                    for pair in lines.chunks_exact(2) {
+                        self.line_to(pair[0], pair[1]);
+                    }
+                    self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+                }
+                // vvcurveto hhcurveto，参数为奇数时第一个为起点处另一方向的偏移
+                26 | 27 => {
+                    let mut stack = std::mem::take(&mut self.stack);
+                    let mut first = if stack.len() % 2 == 1 { stack.remove(0) } else { 0f32 };
+                    for s in stack.chunks_exact(4) {
+                        if b0 == 26 {
+                            self.curve_to(first, s[0], s[1], s[2], 0f32, s[3]);
+                        } else {
+                            self.curve_to(s[0], first, s[1], s[2], s[3], 0f32);
+                        }
+                        first = 0f32;
+                    }
+                }
+                // vhcurveto hvcurveto，起点切线方向交替，最后一条曲线可能多一个终点处的偏移
+                30 | 31 => {
+                    let stack = std::mem::take(&mut self.stack);
+                    let mut horizontal = b0 == 31;
+                    let mut k = 0usize;
+                    while stack.len() - k >= 4 {
+                        let s = &stack[k..k + 4];
+                        let last = if stack.len() - k == 5 { stack[k + 4] } else { 0f32 };
+                        if horizontal {
+                            self.curve_to(s[0], 0f32, s[1], s[2], last, s[3]);
+                        } else {
+                            self.curve_to(0f32, s[0], s[1], s[2], s[3], last);
+                        }
+                        horizontal = !horizontal;
+                        k += 4;
+                    }
+                }
+                // callsubr callgsubr
+                10 | 29 => {
+                    let subrs = if b0 == 10 { self.private.subrs? } else { self.cff.global_subrs };
+                    let index = self.stack.pop()? as i32 + get_subr_bias(subrs.count);
+                    if index < 0 { return None; }
+                    let subr = subrs.get(self.data, index as usize)?;
+                    if self.execute(subr, depth + 1)? { return Some(true); }
+                }
+                // return
+                11 => return Some(false),
+                // endchar，带 4 个参数的 seac 只记录基字与重音的标准编码，不绘制
+                14 => {
+                    self.skip_width(self.stack.len() == 1 || self.stack.len() == 5);
+                    if let [_, _, base, accent] = self.stack[..] {
+                        self.seac = Some((base as u8, accent as u8));
+                    }
+                    self.close_contour();
+                    self.stack.clear();
+                    return Some(true);
+                }
+                // vsindex
+                15 => {
+                    self.vsindex = self.stack.pop()?.max(0f32) as usize;
+                    self.scalars = None;
+                    self.stack.clear();
+                }
+                // blend
+                16 => self.blend()?,
+                12 => {
+                    let b1 = *code.get(i)?;
+                    i += 1;
+                    let s = std::mem::take(&mut self.stack);
+                    match b1 {
+                        // hflex
+                        34 if s.len() >= 7 => {
+                            self.curve_to(s[0], 0f32, s[1], s[2], s[3], 0f32);
+                            self.curve_to(s[4], 0f32, s[5], -s[2], s[6], 0f32);
+                        }
+                        // flex
+                        35 if s.len() >= 12 => {
+                            self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+                            self.curve_to(s[6], s[7], s[8], s[9], s[10], s[11]);
+                        }
+                        // hflex1
+                        36 if s.len() >= 9 => {
+                            self.curve_to(s[0], s[1], s[2], s[3], s[4], 0f32);
+                            self.curve_to(s[5], 0f32, s[6], s[7], s[8], -(s[1] + s[3] + s[7]));
+                        }
+                        // flex1，最后一个参数按起点到终点的主方向决定是 x 还是 y
+                        37 if s.len() >= 11 => {
+                            let dx: f32 = s[0..10].iter().step_by(2).sum();
+                            let dy: f32 = s[1..10].iter().step_by(2).sum();
+                            let (dx6, dy6) = if dx.abs() > dy.abs() { (s[10], -dy) } else { (-dx, s[10]) };
+                            self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+                            self.curve_to(s[6], s[7], s[8], s[9], dx6, dy6);
+                        }
+                        _ => {}
+                    }
+                }
+                28 => {
+                    self.stack.push(read_i16(code, i)? as f32);
+                    i += 2;
+                }
+                32..=246 => self.stack.push(b0 as f32 - 139f32),
+                247..=250 => {
+                    self.stack.push((b0 as f32 - 247f32) * 256f32 + *code.get(i)? as f32 + 108f32);
+                    i += 1;
+                }
+                251..=254 => {
+                    self.stack.push(-(b0 as f32 - 251f32) * 256f32 - *code.get(i)? as f32 - 108f32);
+                    i += 1;
+                }
+                255 => {
+                    self.stack.push(read_u32(code, i)? as i32 as f32 / 65536f32);
+                    i += 4;
+                }
+                _ => self.stack.clear()
+            }
+        }
+        Some(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_subr_bias, parse_dict};
+
+    #[test]
+    fn test_parse_dict() {
+        // 1000 CharStrings，-1000 12 3 两字节操作符，28 短整数与实数
+        let dict = [0xFA, 0x7C, 17, 0xFE, 0x7C, 12, 3, 28, 0x12, 0x34, 30, 0x1A, 0x2F, 18];
+        let result = parse_dict(&dict);
+        assert_eq!(result[0], (17, vec![1000]));
+        assert_eq!(result[1], (1203, vec![-1000]));
+        assert_eq!(result[2], (18, vec![0x1234, 0]));
+        assert_eq!((get_subr_bias(100), get_subr_bias(2000), get_subr_bias(40000)), (107, 1131, 32768));
+    }
+}
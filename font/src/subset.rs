@@ -0,0 +1,570 @@
+use crate::core::data::text_data::TextData;
+use crate::core::open_type_like::hyphenation::HYPHEN;
+use crate::core::typesetting::MergedFont;
+use crate::kern::{find_table, read_u16, read_u32};
+use crate::otf::Cff;
+use crate::ttf::FontCache;
+use crate::woff2::{build_sfnt, pad4, push_u16, push_u32};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// 直接复制的表，提示程序与 `name`、`OS/2` 不引用字形序号
+const COPY_TABLES: [&[u8; 4]; 6] = [b"OS/2", b"cvt ", b"fpgm", b"gasp", b"name", b"prep"];
+
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+/// 无法生成子集的原因，参数为字体名
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubsetError {
+    /// 字体未加载
+    NotLoaded(String),
+    /// 字体中的表缺失或损坏
+    Malformed(String),
+}
+
+impl fmt::Display for SubsetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubsetError::NotLoaded(name) => write!(f, "字体 {:?} 未加载", name),
+            SubsetError::Malformed(name) => write!(f, "字体 {:?} 的数据无法子集化", name),
+        }
+    }
+}
+
+impl std::error::Error for SubsetError {}
+
+/// 文字数据中使用该字体族的文字块用到的字符，`font_family` 为空时包括所有文字块，需要断词时包括连字符
+pub fn get_used_chars(text_data: &TextData, font_family: &str) -> Vec<u32> {
+    let mut chars = Vec::<u32>::new();
+    for content in text_data.paragraph.paragraph_content.iter() {
+        for block in content.blocks.iter() {
+            if !font_family.is_empty() && !block.get_font_families().iter().any(|family| family == font_family) { continue; }
+            chars.extend(block.text.chars().filter(|c| !c.is_control()).map(|c| c as u32));
+        }
+    }
+    if text_data.paragraph.hyphens != "none" {
+        chars.push(HYPHEN as u32);
+    }
+    chars.sort_unstable();
+    chars.dedup();
+    chars
+}
+
+/// 按文字数据中第一个使用该字体族的文字块的字重与样式选择字体，生成只包含用到的字符的字体
+pub fn subset_text_data<Data: std::ops::Deref<Target=[u8]>>(font_cache: &FontCache<Data>, font_family: &str, text_data: &TextData) -> Result<Vec<u8>, SubsetError> {
+    let mut blocks = text_data.paragraph.paragraph_content.iter().flat_map(|content| content.blocks.iter());
+    let block = blocks.find(|block| block.get_font_families().iter().any(|family| family == font_family));
+    let (weight, italic) = block.map(|block| (block.font_weight, block.is_italic())).unwrap_or((400, false));
+    let face_name = font_cache.match_face(font_family, weight, italic).font_name;
+    let face_name = face_name.split(',').next().unwrap_or_default();
+    subset_face(font_cache, face_name, &get_used_chars(text_data, font_family))
+}
+
+/// 生成只包含这些字符及 `.notdef` 的字体，组合字形与 seac 引用的字形一并保留，
+/// TrueType 可变字体输出默认实例，CFF 轮廓输出 `OTTO` 字体并保留全部子程序，`GSUB`/`GPOS` 等排版表不保留
+pub fn subset_face<Data: std::ops::Deref<Target=[u8]>>(font_cache: &FontCache<Data>, font_name: &str, chars: &[u32]) -> Result<Vec<u8>, SubsetError> {
+    let font = font_cache.get_font(font_name).ok_or_else(|| SubsetError::NotLoaded(font_name.to_string()))?;
+    let (data, face_offset) = font.get_face_data();
+    let mapping: Vec<(u32, u32)> = chars.iter()
+        .map(|c| (*c, font.find_glyph_index(*c)))
+        .filter(|(_, glyph_id)| *glyph_id != 0)
+        .collect();
+    subset(data, face_offset, &mapping).ok_or_else(|| SubsetError::Malformed(font_name.to_string()))
+}
+
+struct Source<'a> {
+    data: &'a [u8],
+    glyf: usize,
+    loca: usize,
+    long_loca: bool,
+    num_glyphs: u32,
+}
+
+impl<'a> Source<'a> {
+    fn new(data: &'a [u8], face_offset: usize) -> Option<Self> {
+        let (head, _) = find_table(data, face_offset, b"head")?;
+        let (maxp, _) = find_table(data, face_offset, b"maxp")?;
+        Some(Source {
+            data,
+            glyf: find_table(data, face_offset, b"glyf")?.0,
+            loca: find_table(data, face_offset, b"loca")?.0,
+            long_loca: read_u16(data, head + 50)? != 0,
+            num_glyphs: read_u16(data, maxp + 4)? as u32,
+        })
+    }
+
+    /// 字形在 `glyf` 中的数据，空字形为空切片
+    fn glyph(&self, glyph_id: u32) -> Option<&'a [u8]> {
+        if glyph_id >= self.num_glyphs { return None; }
+        let (start, end) = if self.long_loca {
+            let offset = self.loca + glyph_id as usize * 4;
+            (read_u32(self.data, offset)? as usize, read_u32(self.data, offset + 4)? as usize)
+        } else {
+            let offset = self.loca + glyph_id as usize * 2;
+            (read_u16(self.data, offset)? as usize * 2, read_u16(self.data, offset + 2)? as usize * 2)
+        };
+        if end <= start { return Some(&[]); }
+        self.data.get(self.glyf + start..self.glyf + end)
+    }
+}
+
+/// 组合字形中各部件的字形序号在字形数据中的位置
+fn get_components(glyph: &[u8]) -> Vec<usize> {
+    let mut components = Vec::<usize>::new();
+    if glyph.len() < 10 || read_u16(glyph, 0).map(|contours| contours as i16 >= 0).unwrap_or(true) {
+        return components;
+    }
+    let mut offset = 10usize;
+    while let Some(flags) = read_u16(glyph, offset) {
+        if offset + 4 > glyph.len() { break; }
+        components.push(offset + 2);
+        offset += 4 + if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & WE_HAVE_A_SCALE != 0 {
+            offset += 2;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            offset += 4;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            offset += 8;
+        }
+        if flags & MORE_COMPONENTS == 0 { break; }
+    }
+    components
+}
+
+/// 字形轮廓，TrueType 的 `glyf` 或 `CFF `/`CFF2`
+enum Outlines<'a> {
+    TrueType(Source<'a>),
+    Cff(Cff),
+}
+
+/// 由 (字符, 字形序号) 生成子集，新字形按原序号排列
+pub fn subset(data: &[u8], face_offset: usize, mapping: &[(u32, u32)]) -> Option<Vec<u8>> {
+    let outlines = match Source::new(data, face_offset) {
+        Some(source) => Outlines::TrueType(source),
+        None => Outlines::Cff(Cff::from_face(data, face_offset)?),
+    };
+    let table = |tag: &[u8; 4]| find_table(data, face_offset, tag).and_then(|(offset, length)| data.get(offset..offset + length));
+    let max_glyphs = read_u16(table(b"maxp")?, 4)? as u32;
+
+    // 组合字形与 seac 的部件递归加入
+    let mut old_ids = BTreeSet::<u32>::new();
+    let mut pending: Vec<u32> = std::iter::once(0).chain(mapping.iter().map(|(_, glyph_id)| *glyph_id)).collect();
+    while let Some(glyph_id) = pending.pop() {
+        if !old_ids.insert(glyph_id) { continue; }
+        let components = match &outlines {
+            Outlines::TrueType(source) => {
+                let glyph = source.glyph(glyph_id)?;
+                get_components(glyph).iter().map(|offset| read_u16(glyph, *offset).map(|id| id as u32)).collect::<Option<Vec<u32>>>()?
+            }
+            Outlines::Cff(cff) => cff.get_components(data, glyph_id),
+        };
+        pending.extend(components.into_iter().filter(|component| *component < max_glyphs));
+    }
+    let new_ids: BTreeMap<u32, u16> = old_ids.iter().enumerate().map(|(new_id, old_id)| (*old_id, new_id as u16)).collect();
+
+    let (flavor, mut tables) = match &outlines {
+        Outlines::TrueType(source) => {
+            let mut glyf = Vec::<u8>::new();
+            let mut loca = Vec::<u8>::new();
+            for old_id in old_ids.iter() {
+                push_u32(&mut loca, glyf.len() as u32);
+                let mut glyph = source.glyph(*old_id)?.to_vec();
+                for offset in get_components(&glyph) {
+                    let component = read_u16(&glyph, offset)? as u32;
+                    let new_id = *new_ids.get(&component)?;
+                    glyph[offset..offset + 2].copy_from_slice(&new_id.to_be_bytes());
+                }
+                glyf.extend_from_slice(&glyph);
+                pad4(&mut glyf);
+            }
+            push_u32(&mut loca, glyf.len() as u32);
+            (0x0001_0000, vec![(*b"glyf", glyf), (*b"loca", loca)])
+        }
+        Outlines::Cff(cff) => {
+            let glyph_ids: Vec<u32> = old_ids.iter().cloned().collect();
+            (0x4F54_544F, vec![(cff.tag(), cff.subset(data, &glyph_ids)?)])
+        }
+    };
+
+    let num_glyphs = old_ids.len() as u16;
+    let hhea = table(b"hhea")?;
+    let hmtx = table(b"hmtx")?;
+    let num_h_metrics = read_u16(hhea, 34)? as usize;
+    let mut new_hmtx = Vec::<u8>::new();
+    for old_id in old_ids.iter() {
+        let old_id = *old_id as usize;
+        let advance = read_u16(hmtx, old_id.min(num_h_metrics.max(1) - 1) * 4)?;
+        let lsb = if old_id < num_h_metrics {
+            read_u16(hmtx, old_id * 4 + 2)?
+        } else {
+            read_u16(hmtx, num_h_metrics * 4 + (old_id - num_h_metrics) * 2).unwrap_or(0)
+        };
+        push_u16(&mut new_hmtx, advance);
+        push_u16(&mut new_hmtx, lsb);
+    }
+
+    let mut new_head = table(b"head")?.to_vec();
+    new_head.get_mut(8..12)?.copy_from_slice(&[0, 0, 0, 0]);
+    if let Outlines::TrueType(_) = outlines {
+        new_head.get_mut(50..52)?.copy_from_slice(&1u16.to_be_bytes());
+    }
+    let mut new_hhea = hhea.to_vec();
+    new_hhea.get_mut(34..36)?.copy_from_slice(&num_glyphs.to_be_bytes());
+    let mut new_maxp = table(b"maxp")?.to_vec();
+    new_maxp.get_mut(4..6)?.copy_from_slice(&num_glyphs.to_be_bytes());
+    // post 使用 3.0 版，不保存字形名
+    let mut new_post = table(b"post").and_then(|post| post.get(0..32)).map(|post| post.to_vec()).unwrap_or(vec![0; 32]);
+    new_post[0..4].copy_from_slice(&0x0003_0000u32.to_be_bytes());
+
+    let mut glyph_mapping: Vec<(u32, u16)> = mapping.iter().filter_map(|(c, glyph_id)| Some((*c, *new_ids.get(glyph_id)?))).collect();
+    glyph_mapping.sort_unstable();
+    glyph_mapping.dedup_by_key(|(c, _)| *c);
+
+    tables.extend(vec![
+        (*b"cmap", build_cmap(&glyph_mapping)),
+        (*b"head", new_head),
+        (*b"hhea", new_hhea),
+        (*b"hmtx", new_hmtx),
+        (*b"maxp", new_maxp),
+        (*b"post", new_post),
+    ]);
+    for tag in COPY_TABLES.iter() {
+        if let Some(table) = table(tag) {
+            let mut table = table.to_vec();
+            // OS/2 中的首末字符改为子集的范围
+            if *tag == b"OS/2" && table.len() >= 68 {
+                let first = glyph_mapping.first().map(|(c, _)| (*c).min(0xFFFF) as u16).unwrap_or(0);
+                let last = glyph_mapping.last().map(|(c, _)| (*c).min(0xFFFF) as u16).unwrap_or(0);
+                table[64..66].copy_from_slice(&first.to_be_bytes());
+                table[66..68].copy_from_slice(&last.to_be_bytes());
+            }
+            tables.push((**tag, table));
+        }
+    }
+    tables.sort_by_key(|(tag, _)| *tag);
+    let size_hint = tables.iter().map(|(_, table)| table.len() + 20).sum::<usize>() + 12;
+    Some(build_sfnt(flavor, &tables, size_hint))
+}
+
+/// 字符与字形序号都连续的一段
+fn get_ranges(mapping: &[(u32, u16)]) -> Vec<(u32, u32, u16)> {
+    let mut ranges = Vec::<(u32, u32, u16)>::new();
+    for (c, glyph_id) in mapping.iter() {
+        match ranges.last_mut() {
+            Some((start, end, start_id)) if *end + 1 == *c && (*start_id as u32 + (*c - *start)) == *glyph_id as u32 => *end = *c,
+            _ => ranges.push((*c, *c, *glyph_id))
+        }
+    }
+    ranges
+}
+
+/// 基本多文种平面的字符写入格式 4 (3, 1)，所有字符写入格式 12 (3, 10)
+fn build_cmap(mapping: &[(u32, u16)]) -> Vec<u8> {
+    let ranges = get_ranges(mapping);
+
+    let mut segments: Vec<(u16, u16, u16)> = ranges.iter()
+        .filter(|(start, _, _)| *start <= 0xFFFF)
+        .map(|(start, end, start_id)| (*start as u16, (*end).min(0xFFFE) as u16, start_id.wrapping_sub(*start as u16)))
+        .collect();
+    segments.push((0xFFFF, 0xFFFF, 1));
+    let seg_count = segments.len();
+    let mut entry_selector = 0u16;
+    while (2usize << entry_selector) <= seg_count {
+        entry_selector += 1;
+    }
+    let search_range = 2u16 << entry_selector;
+    let mut format4 = Vec::<u8>::new();
+    let length = 16 + seg_count * 8;
+    if length <= 0xFFFF {
+        push_u16(&mut format4, 4);
+        push_u16(&mut format4, length as u16);
+        push_u16(&mut format4, 0);
+        push_u16(&mut format4, seg_count as u16 * 2);
+        push_u16(&mut format4, search_range);
+        push_u16(&mut format4, entry_selector);
+        push_u16(&mut format4, (seg_count as u16 * 2).saturating_sub(search_range));
+        segments.iter().for_each(|(_, end, _)| push_u16(&mut format4, *end));
+        push_u16(&mut format4, 0);
+        segments.iter().for_each(|(start, _, _)| push_u16(&mut format4, *start));
+        segments.iter().for_each(|(_, _, delta)| push_u16(&mut format4, *delta));
+        segments.iter().for_each(|_| push_u16(&mut format4, 0));
+    }
+
+    let mut format12 = Vec::<u8>::new();
+    push_u16(&mut format12, 12);
+    push_u16(&mut format12, 0);
+    push_u32(&mut format12, 16 + ranges.len() as u32 * 12);
+    push_u32(&mut format12, 0);
+    push_u32(&mut format12, ranges.len() as u32);
+    for (start, end, start_id) in ranges.iter() {
+        push_u32(&mut format12, *start);
+        push_u32(&mut format12, *end);
+        push_u32(&mut format12, *start_id as u32);
+    }
+
+    let mut subtables = Vec::<(u16, &[u8])>::new();
+    if !format4.is_empty() {
+        subtables.push((1, &format4));
+    }
+    subtables.push((10, &format12));
+    let mut cmap = Vec::<u8>::new();
+    push_u16(&mut cmap, 0);
+    push_u16(&mut cmap, subtables.len() as u16);
+    let mut offset = 4 + subtables.len() * 8;
+    for (encoding, subtable) in subtables.iter() {
+        push_u16(&mut cmap, 3);
+        push_u16(&mut cmap, *encoding);
+        push_u32(&mut cmap, offset as u32);
+        offset += subtable.len();
+    }
+    for (_, subtable) in subtables.iter() {
+        cmap.extend_from_slice(subtable);
+    }
+    cmap
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::open_type_like::glyph::Variations;
+    use crate::ttf::Font;
+    use crate::woff::decompress_woff;
+
+    const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+
+    /// 每 em 1000 单位的字体，第 i 个字形前进宽度为 100 * (i + 1)、左侧轴承为 i
+    fn build_font(flavor: u32, mut tables: Vec<([u8; 4], Vec<u8>)>, num_glyphs: u16, mapping: &[(u32, u16)]) -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        head[50..52].copy_from_slice(&1u16.to_be_bytes());
+        let mut hhea = vec![0; 36];
+        hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+        hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+        hhea[34..36].copy_from_slice(&num_glyphs.to_be_bytes());
+        let mut maxp = vec![0, 0, 0x50, 0];
+        push_u16(&mut maxp, num_glyphs);
+        let mut hmtx = vec![];
+        for i in 0..num_glyphs {
+            push_u16(&mut hmtx, 100 * (i + 1));
+            push_u16(&mut hmtx, i);
+        }
+        tables.extend(vec![(*b"cmap", build_cmap(mapping)), (*b"head", head), (*b"hhea", hhea), (*b"hmtx", hmtx), (*b"maxp", maxp)]);
+        tables.sort_by_key(|(tag, _)| *tag);
+        build_sfnt(flavor, &tables, 0)
+    }
+
+    /// 左下角在原点的正方形
+    fn simple_glyph(size: i16) -> Vec<u8> {
+        let mut glyph = vec![];
+        [1, 0, 0, size, size, 3, 0].iter().for_each(|value| push_u16(&mut glyph, *value as u16));
+        glyph.extend_from_slice(&[1, 1, 1, 1]);
+        [0, size, 0, -size, 0, 0, size, 0].iter().for_each(|value| push_u16(&mut glyph, *value as u16));
+        glyph
+    }
+
+    /// 部件为 (字形序号, 横向偏移, 纵向偏移)
+    fn composite_glyph(components: &[(u16, i16, i16)]) -> Vec<u8> {
+        let mut glyph = vec![];
+        [-1i16, 0, 0, 0, 0].iter().for_each(|value| push_u16(&mut glyph, *value as u16));
+        for (i, (glyph_id, dx, dy)) in components.iter().enumerate() {
+            let more = if i + 1 < components.len() { MORE_COMPONENTS } else { 0 };
+            push_u16(&mut glyph, ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES | more);
+            push_u16(&mut glyph, *glyph_id);
+            push_u16(&mut glyph, *dx as u16);
+            push_u16(&mut glyph, *dy as u16);
+        }
+        glyph
+    }
+
+    /// 字形 1、2 为正方形，3 引用 2，4 引用 3 与 2，A 对应字形 1，B 对应字形 4
+    fn build_composite_font() -> Vec<u8> {
+        let glyphs = [vec![], simple_glyph(50), simple_glyph(100), composite_glyph(&[(2, 50, 0)]), composite_glyph(&[(3, 0, 50), (2, 200, 0)])];
+        let (mut glyf, mut loca) = (vec![], vec![]);
+        for glyph in glyphs.iter() {
+            push_u32(&mut loca, glyf.len() as u32);
+            glyf.extend_from_slice(glyph);
+            pad4(&mut glyf);
+        }
+        push_u32(&mut loca, glyf.len() as u32);
+        build_font(0x0001_0000, vec![(*b"glyf", glyf), (*b"loca", loca)], 5, &[('A' as u32, 1), ('B' as u32, 4)])
+    }
+
+    #[test]
+    fn test_build_cmap() {
+        let mapping = [(0x41, 1), (0x42, 2), (0x44, 3), (0x1F600, 4)];
+        assert_eq!(get_ranges(&mapping), vec![(0x41, 0x42, 1), (0x44, 0x44, 3), (0x1F600, 0x1F600, 4)]);
+        let cmap = build_cmap(&mapping);
+        assert_eq!(u16::from_be_bytes([cmap[2], cmap[3]]), 2);
+        // 格式 4 有 A-B、D 与结尾三段
+        assert_eq!(u16::from_be_bytes([cmap[20 + 6], cmap[20 + 7]]), 6);
+    }
+
+    #[test]
+    fn test_get_components() {
+        // 两个部件，第一个参数为字，第二个带缩放
+        let glyph = [0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0,
+            0x00, 0x21, 0x00, 0x05, 0, 0, 0, 0,
+            0x00, 0x08, 0x00, 0x07, 0, 0, 0x40, 0x00];
+        assert_eq!(get_components(&glyph), vec![12, 20]);
+    }
+
+    #[test]
+    fn test_subset_composite() {
+        let data = build_composite_font();
+        let result = subset(&data, 0, &[('B' as u32, 4)]).unwrap();
+        // 保留 .notdef 与两层部件，按原序号重新编号
+        let source = Source::new(&result, 0).unwrap();
+        assert_eq!(source.num_glyphs, 4);
+        let components = |glyph_id: u32| {
+            let glyph = source.glyph(glyph_id).unwrap();
+            get_components(glyph).iter().map(|offset| read_u16(glyph, *offset).unwrap()).collect::<Vec<u16>>()
+        };
+        assert_eq!((components(3), components(2), components(1)), (vec![2, 1], vec![1], vec![]));
+
+        let (original, font) = (Font::new(data).unwrap(), Font::new(result).unwrap());
+        assert_eq!((font.find_glyph_index('A' as u32), font.find_glyph_index('B' as u32)), (0, 3));
+        // 部件解析后的轮廓与度量不变
+        let variations = Variations::default();
+        let (old, new) = (original.get_glyph('B' as u32, &variations), font.get_glyph('B' as u32, &variations));
+        assert!(!new.path.is_empty());
+        assert_eq!(String::from(&new.path), String::from(&old.path));
+        assert_eq!((new.advance_width, new.left_side_bearing), (500, 4));
+    }
+
+    #[test]
+    fn test_subset_face() {
+        let mut font_cache = FontCache::new();
+        font_cache.load_font_bytes("happy".to_string(), decompress_woff(include_bytes!("./c_764")).unwrap()).unwrap();
+        let chars: Vec<u32> = "中文a".chars().map(|c| c as u32).collect();
+        let font = Font::new(subset_face(&font_cache, "happy", &chars).unwrap()).unwrap();
+        let original = font_cache.get_font("happy").unwrap();
+        // cmap 指向重新编号的字形，loca 与 hmtx 中的轮廓与度量不变
+        assert_eq!(chars.iter().map(|c| font.find_glyph_index(*c)).collect::<Vec<u32>>(), vec![2, 3, 1]);
+        assert_eq!(font.find_glyph_index('-' as u32), 0);
+        let variations = Variations::default();
+        for c in chars.iter() {
+            let (old, new) = (original.get_glyph(*c, &variations), font.get_glyph(*c, &variations));
+            assert_eq!(String::from(&new.path), String::from(&old.path));
+            assert_eq!((new.advance_width, new.left_side_bearing), (old.advance_width, old.left_side_bearing));
+        }
+        assert_eq!(subset_face(&font_cache, "missing", &chars), Err(SubsetError::NotLoaded("missing".to_string())));
+    }
+
+    /// charstring 中的整数
+    fn number(value: i32) -> Vec<u8> {
+        match value {
+            -107..=107 => vec![(value + 139) as u8],
+            _ => vec![28, (value >> 8) as u8, value as u8]
+        }
+    }
+
+    fn char_string(operations: &[(&[i32], u8)]) -> Vec<u8> {
+        operations.iter().flat_map(|(operands, operator)| operands.iter().flat_map(|value| number(*value)).chain(std::iter::once(*operator))).collect()
+    }
+
+    fn index(items: &[Vec<u8>], cff2: bool) -> Vec<u8> {
+        let mut result = if cff2 { (items.len() as u32).to_be_bytes().to_vec() } else { (items.len() as u16).to_be_bytes().to_vec() };
+        result.push(2);
+        let mut offset = 1u16;
+        push_u16(&mut result, offset);
+        for item in items.iter() {
+            offset += item.len() as u16;
+            push_u16(&mut result, offset);
+        }
+        items.iter().for_each(|item| result.extend_from_slice(item));
+        result
+    }
+
+    fn offset(value: usize) -> Vec<u8> {
+        let mut result = vec![29];
+        push_u32(&mut result, value as u32);
+        result
+    }
+
+    /// 字形 1 调用全局子程序，2 调用局部子程序，3 为 seac，基字为 A、重音为 B，A、B、C 对应字形 1、2、3
+    fn build_cff_font() -> Vec<u8> {
+        let global_subrs = index(&[char_string(&[(&[500, 0, 0, 500, -500, 0], 5), (&[], 11)])], false);
+        let local_subrs = index(&[char_string(&[(&[300, 0, 0, 300], 5), (&[], 11)])], false);
+        let char_strings = index(&[
+            vec![14],
+            char_string(&[(&[100, 100], 21), (&[-107], 29), (&[], 14)]),
+            char_string(&[(&[0, 0], 21), (&[-107], 10), (&[], 14)]),
+            char_string(&[(&[0, 0, 'A' as i32, 'B' as i32], 14)]),
+        ], false);
+        let charset = vec![0, 0, 34, 0, 35, 0, 36];
+        let private = [offset(6), vec![19]].concat();
+        let names = index(&[b"a".to_vec()], false);
+        let top_size = 23;
+        let charset_offset = 4 + names.len() + index(&[vec![0; top_size]], false).len() + 2 + global_subrs.len();
+        let char_strings_offset = charset_offset + charset.len();
+        let private_offset = char_strings_offset + char_strings.len();
+        let top = [offset(charset_offset), vec![15], offset(char_strings_offset), vec![17], offset(private.len()), offset(private_offset), vec![18]].concat();
+        let mut cff = [vec![1, 0, 4, 4], names, index(&[top], false), vec![0, 0], global_subrs, charset, char_strings, private, local_subrs].concat();
+        pad4(&mut cff);
+        build_font(0x4F54_544F, vec![(*b"CFF ", cff)], 4, &[('A' as u32, 1), ('B' as u32, 2), ('C' as u32, 3)])
+    }
+
+    #[test]
+    fn test_subset_cff() {
+        let data = build_cff_font();
+        let mut font_cache = FontCache::new();
+        font_cache.load_font_bytes("cff".to_string(), data.clone()).unwrap();
+        let original = font_cache.get_font("cff").unwrap();
+        let variations = Variations::default();
+
+        // 子程序保留，CharStrings 只剩 .notdef 与 B
+        let result = subset(&data, 0, &[('B' as u32, 2)]).unwrap();
+        assert_eq!(&result[0..4], b"OTTO");
+        assert!(find_table(&result, 0, b"CFF ").unwrap().1 < find_table(&data, 0, b"CFF ").unwrap().1);
+        let font = Font::new(result).unwrap();
+        assert!(font.is_cff());
+        assert_eq!((font.find_glyph_index('A' as u32), font.find_glyph_index('B' as u32)), (0, 1));
+        let (old, new) = (original.get_glyph('B' as u32, &variations), font.get_glyph('B' as u32, &variations));
+        assert!(!new.path.is_empty());
+        assert_eq!(String::from(&new.path), String::from(&old.path));
+        assert_eq!((new.advance_width, new.left_side_bearing), (300, 2));
+
+        let source = r#"{"width": 100, "height": 100, "paragraph": {"contents": [{"blocks": [{"text": "A", "fontFamily": "cff"}]}]}}"#;
+        let font = Font::new(subset_text_data(&font_cache, "cff", &TextData::parse(source).unwrap()).unwrap()).unwrap();
+        let (old, new) = (original.get_glyph('A' as u32, &variations), font.get_glyph('A' as u32, &variations));
+        assert!(!new.path.is_empty());
+        assert_eq!(String::from(&new.path), String::from(&old.path));
+
+        // seac 的部件一并保留，新的字符集仍能找到部件
+        let result = subset(&data, 0, &[('C' as u32, 3)]).unwrap();
+        let (maxp, _) = find_table(&result, 0, b"maxp").unwrap();
+        assert_eq!(read_u16(&result, maxp + 4), Some(4));
+        assert_eq!(Cff::from_face(&result, 0).unwrap().get_components(&result, 3), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_subset_cff2() {
+        let char_strings = index(&[
+            vec![],
+            char_string(&[(&[0, 0], 21), (&[500, 0, 0, 500], 5)]),
+            char_string(&[(&[0, 0], 21), (&[200, 0, -100, 200], 5)]),
+        ], true);
+        let top_size = 13;
+        let char_strings_offset = 5 + top_size + 4;
+        let fd_array_offset = char_strings_offset + char_strings.len();
+        let font_dict = [offset(0), offset(0), vec![18]].concat();
+        let private_offset = fd_array_offset + index(&[font_dict], true).len();
+        let font_dict = [offset(0), offset(private_offset), vec![18]].concat();
+        let top = [offset(char_strings_offset), vec![17], offset(fd_array_offset), vec![12, 36]].concat();
+        let mut cff = [vec![2, 0, 5, 0, top_size as u8], top, vec![0, 0, 0, 0], char_strings, index(&[font_dict], true)].concat();
+        pad4(&mut cff);
+        let data = build_font(0x4F54_544F, vec![(*b"CFF2", cff)], 3, &[('a' as u32, 1), ('b' as u32, 2)]);
+
+        let result = subset(&data, 0, &[('b' as u32, 2)]).unwrap();
+        let (original, font) = (Font::new(data).unwrap(), Font::new(result).unwrap());
+        assert_eq!((font.find_glyph_index('a' as u32), font.find_glyph_index('b' as u32)), (0, 1));
+        let variations = Variations::default();
+        let (old, new) = (original.get_glyph('b' as u32, &variations), font.get_glyph('b' as u32, &variations));
+        assert!(!new.path.is_empty());
+        assert_eq!(String::from(&new.path), String::from(&old.path));
+    }
+}
@@ -1,51 +1,272 @@
-use crate::core::open_type_like::glyph::Glyph;
+use crate::core::open_type_like::glyph::{DecorationMetrics, Glyph, ShapedGlyph, ShapeOption, Variations};
 use crate::core::open_type_like::path::PathData;
-use crate::core::typesetting::MergedFont;
+use crate::core::open_type_like::script::{get_sample, get_script, COMMON};
+use crate::core::data::text_data::split_font_family;
+use crate::core::typesetting::{FontFace, MergedFont};
+use crate::check::{get_face_count, get_face_offset};
+use crate::color::ColorFont;
+use crate::face::{get_code_page, FaceStyle};
+use crate::kern::{find_table, PairKerning};
+use crate::otf::OtfFont;
+use crate::variation::VariableFont;
 use std::collections::HashMap;
+use std::sync::Arc;
 use stb_truetype as stt;
 
-pub struct Font<Data: std::ops::Deref<Target=[u8]>>(stt::FontInfo<Data>);
+/// 字体数据在 stb_truetype 与 rustybuzz 之间以及字体集合的各个字体之间共享
+pub struct FontBytes<Data: std::ops::Deref<Target=[u8]>>(Arc<Data>);
+
+impl<Data: std::ops::Deref<Target=[u8]>> Clone for FontBytes<Data> {
+    fn clone(&self) -> Self {
+        FontBytes(self.0.clone())
+    }
+}
+
+impl<Data: std::ops::Deref<Target=[u8]>> std::ops::Deref for FontBytes<Data> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// TrueType 轮廓由 stb_truetype 读取，CFF 轮廓的字体 stb_truetype 无法加载
+enum Outlines<Data: std::ops::Deref<Target=[u8]>> {
+    TrueType(stt::FontInfo<FontBytes<Data>>),
+    Cff(OtfFont),
+}
+
+pub struct Font<Data: std::ops::Deref<Target=[u8]>> {
+    outlines: Outlines<Data>,
+    data: FontBytes<Data>,
+    index: u32,
+    kerning: PairKerning,
+    decoration: DecorationMetrics,
+    variable: Option<VariableFont>,
+    color: Option<ColorFont>,
+    pub style: FaceStyle,
+}
 
 impl<Data: std::ops::Deref<Target=[u8]>> Font<Data> {
     pub fn new(d: Data) -> Option<Font<Data>> {
-        let font_info = stt::FontInfo::new(d, 0);
-        if let Some(font_info) = font_info {
-            font_info.get_font_name_strings();
-            Some(Font(font_info))
+        Font::from_bytes(FontBytes(Arc::new(d)), 0)
+    }
+
+    /// 字体集合中的第 `index` 个字体，单个字体只有 0
+    pub fn from_bytes(data: FontBytes<Data>, index: u32) -> Option<Font<Data>> {
+        let face_offset = get_face_offset(&data, index)?;
+        let outlines = match stt::FontInfo::new(data.clone(), face_offset) {
+            Some(font_info) => {
+                font_info.get_font_name_strings();
+                Outlines::TrueType(font_info)
+            }
+            None => Outlines::Cff(OtfFont::new(&data, face_offset)?)
+        };
+        let kerning = PairKerning::new(&data, face_offset);
+        let style = FaceStyle::new(&data, face_offset);
+        let variable = VariableFont::new(&data, face_offset);
+        let color = ColorFont::new(&data, face_offset);
+        let mut font = Font { outlines, data, index, kerning, decoration: DecorationMetrics::default(), variable, color, style };
+        font.decoration = get_decoration_metrics(&font.data, face_offset, font.units_per_em() as i32);
+        Some(font)
+    }
+
+    /// 字体数据与字体表目录的偏移
+    pub(crate) fn get_face_data(&self) -> (&[u8], usize) {
+        (&self.data, get_face_offset(&self.data, self.index).unwrap_or(0))
+    }
+
+    /// CFF 或 CFF2 轮廓的字体
+    pub fn is_cff(&self) -> bool {
+        matches!(self.outlines, Outlines::Cff(_))
+    }
+
+    pub fn units_per_em(&self) -> u16 {
+        match &self.outlines {
+            Outlines::TrueType(info) => info.units_per_em(),
+            Outlines::Cff(otf) => otf.units_per_em,
+        }
+    }
+
+    /// 字符对应的字形，没有时返回 0
+    pub fn find_glyph_index(&self, c: u32) -> u32 {
+        match &self.outlines {
+            Outlines::TrueType(info) => info.find_glyph_index(c),
+            Outlines::Cff(otf) => otf.find_glyph_index(&self.data, c),
+        }
+    }
+
+    fn get_v_metrics(&self) -> stt::VMetrics {
+        match &self.outlines {
+            Outlines::TrueType(info) => info.get_v_metrics(),
+            Outlines::Cff(otf) => {
+                let (ascent, descent, line_gap) = otf.v_metrics;
+                stt::VMetrics { ascent, descent, line_gap }
+            }
+        }
+    }
+
+    fn get_glyph_h_metrics(&self, index: u32) -> stt::HMetrics {
+        match &self.outlines {
+            Outlines::TrueType(info) => info.get_glyph_h_metrics(index),
+            Outlines::Cff(otf) => {
+                let (advance_width, left_side_bearing) = otf.get_h_metrics(&self.data, index);
+                stt::HMetrics { advance_width, left_side_bearing }
+            }
+        }
+    }
+
+    /// 字偶距，GPOS 中没有 `kern` 特性时使用 `kern` 表
+    pub fn get_kerning(&self, left: u32, right: u32) -> i32 {
+        if !self.kerning.is_empty() {
+            self.kerning.get_kerning(&self.data, left as u16, right as u16)
         } else {
-            None
+            match &self.outlines {
+                Outlines::TrueType(info) => info.get_glyph_kern_advance(left, right),
+                Outlines::Cff(otf) => otf.get_kern_advance(&self.data, left, right),
+            }
         }
     }
 
-    pub fn get_glyph(&self, char_code: u32) -> Glyph {
+    /// 只保留字体中存在的轴，不是可变字体时为空
+    pub fn get_variations(&self, variations: &Variations) -> Variations {
+        self.variable.as_ref().map(|variable| variable.filter(variations)).unwrap_or_default()
+    }
+
+    /// 可变字体中该轴的取值范围
+    pub fn get_axis_range(&self, tag: &[u8; 4]) -> Option<(f32, f32)> {
+        let axis = self.variable.as_ref()?.axes.iter().find(|axis| &axis.tag == tag)?;
+        Some((axis.min, axis.max))
+    }
+
+    pub fn get_glyph(&self, char_code: u32, variations: &Variations) -> Glyph {
         let index = self.find_glyph_index(char_code);
         if index == 0 {
             return Glyph::get_none();
         }
+        let mut glyph = self.get_glyph_by_id(index, variations);
+        glyph.char_code = Some(char_code);
+        glyph
+    }
+
+    /// `variations` 应为 `get_variations` 过滤后的取值，为空时使用默认轮廓
+    pub fn get_glyph_by_id(&self, index: u32, variations: &Variations) -> Glyph {
         let stt::VMetrics { ascent: ascender, descent: descender, line_gap: _ } = self.get_v_metrics();
-        let path_vertex = self.get_glyph_shape(index).unwrap_or(vec![]);
-        let stt::HMetrics { advance_width, left_side_bearing } = self.get_glyph_h_metrics(index);
+        let stt::HMetrics { mut advance_width, left_side_bearing } = self.get_glyph_h_metrics(index);
         let units_per_em = self.units_per_em() as i32;
+        let coords = match &self.variable {
+            Some(variable) if !variations.is_empty() => variable.get_coords(&self.data, variations),
+            _ => vec![]
+        };
+        let (path, delta) = self.get_outline(index, &coords);
+        advance_width += delta.round() as i32;
+        let color = self.color.as_ref().and_then(|color| color.get_color_glyph(&self.data, index, &|id| self.get_outline(id, &coords).0));
         Glyph {
-            path: vertex_to_path_data(&path_vertex),
+            path,
             advance_width,
             units_per_em,
             ascender,
             descender,
             left_side_bearing,
-            char_code: Some(char_code),
+            char_code: None,
+            glyph_id: index,
+            font_name: "".to_string(),
+            decoration: self.decoration,
+            variations: variations.clone(),
+            color,
         }
     }
-}
 
-impl<Data: std::ops::Deref<Target=[u8]>> std::ops::Deref for Font<Data> {
-    type Target = stt::FontInfo<Data>;
+    /// 字形轮廓与前进宽度的变化量，`coords` 为空时使用默认轮廓
+    fn get_outline(&self, index: u32, coords: &[f32]) -> (PathData, f32) {
+        match &self.outlines {
+            Outlines::TrueType(info) => {
+                let varied = self.variable.as_ref().filter(|_| !coords.is_empty()).and_then(|variable| variable.get_glyph(&self.data, index, coords));
+                varied.unwrap_or_else(|| (vertex_to_path_data(&info.get_glyph_shape(index).unwrap_or(vec![])), 0f32))
+            }
+            Outlines::Cff(otf) => {
+                // CFF2 的前进宽度只能来自 HVAR
+                let delta = self.variable.as_ref().filter(|_| !coords.is_empty())
+                    .and_then(|variable| variable.get_advance_delta(&self.data, index, coords)).unwrap_or(0f32);
+                (otf.get_glyph_path(&self.data, index, coords).unwrap_or_default(), delta)
+            }
+        }
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    /// 使用 GSUB/GPOS 排版，字形簇按原文顺序排列
+    pub fn shape(&self, text: &str, option: &ShapeOption) -> Option<Vec<ShapedGlyph>> {
+        let mut face = rustybuzz::Face::from_slice(&self.data, self.index)?;
+        let variations = self.get_variations(&option.variations);
+        let values: Vec<rustybuzz::Variation> = variations.0.iter()
+            .map(|(tag, value)| rustybuzz::Variation { tag: rustybuzz::Tag::from_bytes(tag), value: *value as f32 / 65536f32 })
+            .collect();
+        face.set_variations(&values);
+        // 没有 HVAR 表时前进宽度的变化来自 gvar 的幻点
+        let coords = match &self.variable {
+            Some(variable) if !variations.is_empty() && !variable.has_advance_variations() => Some(variable.get_coords(&self.data, &variations)),
+            _ => None
+        };
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        if let Some(rtl) = option.rtl {
+            buffer.set_direction(if rtl { rustybuzz::Direction::RightToLeft } else { rustybuzz::Direction::LeftToRight });
+        }
+        let is_rtl = buffer.direction() == rustybuzz::Direction::RightToLeft;
+        let mut features = Vec::<rustybuzz::Feature>::new();
+        if !option.kerning {
+            features.push(rustybuzz::Feature::new(rustybuzz::Tag::from_bytes(b"kern"), 0, ..));
+        }
+        let glyph_buffer = rustybuzz::shape(&face, &features, buffer);
+        let mut clusters = Vec::<Vec<ShapedGlyph>>::new();
+        for (info, pos) in glyph_buffer.glyph_infos().iter().zip(glyph_buffer.glyph_positions()) {
+            let advance_delta = match (&self.variable, &coords) {
+                (Some(variable), Some(coords)) if pos.x_advance != 0 => variable.get_phantom_advance_delta(&self.data, info.codepoint, coords).round() as i32,
+                _ => 0
+            };
+            let glyph = ShapedGlyph {
+                glyph_id: info.codepoint,
+                cluster: info.cluster as usize,
+                x_advance: pos.x_advance + advance_delta,
+                y_advance: pos.y_advance,
+                x_offset: pos.x_offset,
+                y_offset: pos.y_offset,
+            };
+            match clusters.last_mut() {
+                Some(last) if last[0].cluster == glyph.cluster => last.push(glyph),
+                _ => clusters.push(vec![glyph])
+            }
+        }
+        // 从右到左的文字输出为视觉顺序，字形簇内部保持视觉顺序
+        if is_rtl { clusters.reverse(); }
+        Some(clusters.concat())
     }
 }
 
+/// `post` 表中的下划线与 `OS/2` 表中的删除线，缺少或粗细为 0 时使用估计值
+fn get_decoration_metrics(data: &[u8], face_offset: usize, units_per_em: i32) -> DecorationMetrics {
+    let mut metrics = DecorationMetrics::fallback(units_per_em);
+    let read_i16 = |offset: usize| data.get(offset..offset + 2).map(|r| i16::from_be_bytes([r[0], r[1]]) as i32);
+    if let Some((post, _)) = find_table(data, face_offset, b"post") {
+        if let (Some(position), Some(thickness)) = (read_i16(post + 8), read_i16(post + 10)) {
+            if thickness > 0 {
+                metrics.underline_position = position;
+                metrics.underline_thickness = thickness;
+            }
+        }
+    }
+    if let Some((os2, _)) = find_table(data, face_offset, b"OS/2") {
+        if let (Some(size), Some(position)) = (read_i16(os2 + 26), read_i16(os2 + 28)) {
+            if size > 0 {
+                metrics.strikeout_position = position;
+                metrics.strikeout_size = size;
+            }
+        }
+    }
+    metrics
+}
+
 fn vertex_to_path_data(vvs: &Vec<stt::Vertex>) -> PathData {
     let mut path_data = PathData::new();
     for item in vvs {
@@ -69,8 +290,17 @@ pub struct FontMap<Data: std::ops::Deref<Target=[u8]>>(HashMap<String, Box<Font<
 
 pub struct FontCache<Data: std::ops::Deref<Target=[u8]>> {
     font_map: FontMap<Data>,
-    glyph_indexes: HashMap<(String, u32), usize>,
+    glyph_indexes: HashMap<(String, Variations, u32), usize>,
+    glyph_id_indexes: HashMap<(String, Variations, u32), usize>,
     glyph_caches: Vec<Box<Glyph>>,
+    /// 字体族缺字时依次使用的字体族
+    fallbacks: HashMap<String, Vec<String>>,
+    /// 按文字的 ISO 15924 代码回退的字体族，在字体族的回退之后、默认字体之前使用
+    script_fallbacks: HashMap<String, Vec<String>>,
+    /// 加载时的名字对应的字体，字体集合为一起加载的所有字体
+    faces: HashMap<String, Vec<String>>,
+    /// 小写的族名对应的字体名
+    families: HashMap<String, Vec<String>>,
 }
 
 impl<Data: std::ops::Deref<Target=[u8]>> FontCache<Data> {
@@ -78,24 +308,187 @@ impl<Data: std::ops::Deref<Target=[u8]>> FontCache<Data> {
         FontCache {
             font_map: FontMap::new(),
             glyph_indexes: HashMap::new(),
+            glyph_id_indexes: HashMap::new(),
             glyph_caches: vec![],
+            fallbacks: HashMap::new(),
+            script_fallbacks: HashMap::new(),
+            faces: HashMap::new(),
+            families: HashMap::new(),
         }
     }
+    /// 字体集合中的每个字体以族名与样式名注册，第一个字体同时以 `font_name` 注册
     pub fn load_font_bytes(&mut self, font_name: String, data: Data) -> Option<()> {
-        let font = Font::new(data)?;
-        self.font_map.insert(font_name, Box::new(font));
+        self.load_font_face(font_name, data, None)
+    }
+    /// 指定 `index` 时只以 `font_name` 注册字体集合中的该字体
+    pub fn load_font_face(&mut self, font_name: String, data: Data, index: Option<u32>) -> Option<()> {
+        let data = FontBytes(Arc::new(data));
+        let count = get_face_count(&data);
+        if index.is_some() || count <= 1 {
+            let font = Font::from_bytes(data, index.unwrap_or(0))?;
+            self.insert_font(font_name.clone(), font);
+            self.faces.insert(font_name.clone(), vec![font_name]);
+            return Some(());
+        }
+        let font = Font::from_bytes(data.clone(), 0)?;
+        self.insert_font(font_name.clone(), font);
+        // 没有名字或与前面的字体重名时以 `font_name#序号` 注册
+        let mut names = vec![font_name.clone()];
+        for i in 0..count {
+            if let Some(font) = Font::from_bytes(data.clone(), i) {
+                let name = font.style.get_full_name()
+                    .filter(|name| !names.contains(name))
+                    .unwrap_or(format!("{}#{}", font_name, i));
+                names.push(name.clone());
+                self.insert_font(name.clone(), font);
+                self.faces.insert(name.clone(), vec![name]);
+            }
+        }
+        self.faces.insert(font_name, names);
         Some(())
     }
-    pub fn has_glyph(&self, font_name: String, c: u32) -> bool {
-        let result = self.glyph_indexes.get(&(font_name.clone(), c.clone()));
+    /// 注册字体并更新族名索引，同名的旧字体从索引中移除
+    fn insert_font(&mut self, font_name: String, font: Font<Data>) {
+        for names in self.families.values_mut() {
+            names.retain(|name| *name != font_name);
+        }
+        if !font.style.family.is_empty() {
+            self.families.entry(font.style.family.to_lowercase()).or_default().push(font_name.clone());
+        }
+        self.font_map.insert(font_name, Box::new(font));
+    }
+    /// 字体族的回退列表，已选用的字体名包含回退字体，不需要清除缓存
+    pub fn set_fallback(&mut self, font_family: String, fallback: Vec<String>) {
+        self.fallbacks.insert(font_family, fallback);
+    }
+    /// 文字的回退列表，如 `Hani`、`Zsye`，会清除已缓存的字形
+    pub fn set_script_fallback(&mut self, script: String, fallback: Vec<String>) {
+        self.script_fallbacks.insert(script, fallback);
+        self.glyph_indexes.clear();
+        self.glyph_id_indexes.clear();
+        self.glyph_caches.clear();
+    }
+    /// 文字块需要加载的字体族，包括字体族的回退列表
+    pub fn get_font_families(&self, font_family: &str) -> Vec<String> {
+        let mut families = Vec::<String>::new();
+        for family in split_font_family(font_family) {
+            let fallback = self.fallbacks.get(&family).cloned().unwrap_or_default();
+            for family in std::iter::once(family).chain(fallback) {
+                if !families.contains(&family) { families.push(family); }
+            }
+        }
+        families
+    }
+    /// 所有回退列表中的字体族
+    pub fn get_fallback_families(&self) -> Vec<String> {
+        let mut families = Vec::<String>::new();
+        for family in self.fallbacks.values().chain(self.script_fallbacks.values()).flatten() {
+            if !families.contains(family) { families.push(family.clone()); }
+        }
+        families
+    }
+    /// 选用的字体名以逗号连接，第一个字体用于排版
+    fn get_primary_font(&self, font_name: &str) -> Option<(&String, &Font<Data>)> {
+        split_font_family(font_name).into_iter().find_map(|name| self.font_map.get_key_value(&name)).map(|(name, font)| (name, &**font))
+    }
+    /// 依次查找的字体：选用的字体、文字的回退字体、默认字体
+    fn get_fallback_names(&self, font_name: &str, c: u32) -> Vec<String> {
+        let mut names = split_font_family(font_name);
+        let primary = self.get_primary_font(font_name).map(|(_, font)| (font.style.weight, font.style.italic));
+        let (weight, italic) = primary.unwrap_or((400, false));
+        let script = std::char::from_u32(c).map(get_script).unwrap_or_default();
+        for family in self.script_fallbacks.get(script).into_iter().flatten() {
+            if let Some(face) = self.match_single_face(family, weight, italic) {
+                names.push(face.font_name);
+            }
+        }
+        names.push("default".to_string());
+        names
+    }
+    /// 所有回退列表中的字体族，该文字的回退列表在前，其余按文字代码排序，最后为默认字体
+    fn get_script_candidates(&self, script: &str) -> Vec<&str> {
+        let mut scripts: Vec<&String> = self.script_fallbacks.keys().filter(|key| *key != script).collect();
+        scripts.sort();
+        let own = self.script_fallbacks.get(script).into_iter().flatten();
+        let others = scripts.into_iter().filter_map(|key| self.script_fallbacks.get(key)).flatten();
+        own.chain(others).map(|family| family.as_str()).chain(std::iter::once("default")).collect()
+    }
+    /// 字形缓存以字体中存在的轴的取值区分，字体未加载时为空
+    fn get_variations(&self, font_name: &str, variations: &Variations) -> Variations {
+        if let Some(font) = self.font_map.get(font_name) {
+            return font.get_variations(variations);
+        }
+        let fonts: Vec<&Box<Font<Data>>> = split_font_family(font_name).iter().filter_map(|name| self.font_map.get(name)).collect();
+        let values = variations.0.iter()
+            .filter(|(tag, _)| fonts.iter().any(|font| font.get_axis_range(tag).is_some()))
+            .cloned().collect();
+        Variations(values)
+    }
+    /// 优先在以该名字加载的字体中选择，不是加载时的名字时在族名相同的字体中选择，
+    /// 有 `wght` 轴的可变字体由轴调整字重，不再合成粗体
+    fn match_single_face(&self, font_family: &str, weight: u16, italic: bool) -> Option<FontFace> {
+        let names = self.faces.get(font_family).or_else(|| self.families.get(&font_family.to_lowercase()))?;
+        let (name, font) = names.iter()
+            .filter_map(|name| self.font_map.get_key_value(name))
+            .min_by_key(|(name, font)| (font.style.get_rank(weight, italic), *name != font_family, name.to_string()))?;
+        let weight = match font.get_axis_range(b"wght") {
+            Some((min, max)) => (weight as f32).max(min).min(max) as u16,
+            None => font.style.weight
+        };
+        Some(FontFace { font_name: name.to_string(), weight, italic: font.style.italic })
+    }
+    pub fn has_glyph(&self, font_name: String, variations: &Variations, c: u32) -> bool {
+        let variations = self.get_variations(&font_name, variations);
+        let result = self.glyph_indexes.get(&(font_name, variations, c));
         result.is_some()
     }
-    pub fn check_glyph(&mut self, font_name: String, c: u32) {
-        let result = self.glyph_indexes.get(&(font_name.clone(), c.clone()));
+    pub fn check_glyph(&mut self, font_name: String, variations: &Variations, c: u32) {
+        let key = (font_name.clone(), self.get_variations(&font_name, variations), c);
+        let result = self.glyph_indexes.get(&key);
         if result.is_none() {
-            let glyph = self.font_map.char_to_glyph(font_name.clone(), c.clone() as u32);
+            let glyph = match self.font_map.char_to_glyph(&self.get_fallback_names(&font_name, c), variations, c) {
+                Some(glyph) => glyph,
+                None => return
+            };
             self.glyph_caches.push(glyph);
-            self.glyph_indexes.insert((font_name, c), self.glyph_caches.len() - 1);
+            self.glyph_indexes.insert(key, self.glyph_caches.len() - 1);
+        }
+    }
+    /// 双向文字会按层级分段排版，两个方向用到的字形都需要缓存，字体未加载或无法处理时返回 `None`
+    fn shape_both_directions(&self, font_name: String, variations: &Variations, text: &str) -> Option<Vec<ShapedGlyph>> {
+        let mut glyphs = Vec::<ShapedGlyph>::new();
+        for rtl in [false, true].iter() {
+            let option = ShapeOption { rtl: Some(*rtl), variations: variations.clone(), ..Default::default() };
+            glyphs.extend(self.shape(font_name.clone(), &filter_line_break(text), &option)?);
+        }
+        Some(glyphs)
+    }
+    /// 字体未加载或无法处理时为 `false`，需要在加载字体后调用 `check_text`
+    pub fn has_text(&self, font_name: String, variations: &Variations, text: &str) -> bool {
+        let glyphs = match self.shape_both_directions(font_name.clone(), variations, text) {
+            Some(glyphs) => glyphs,
+            None => return false
+        };
+        let variations = self.get_variations(&font_name, variations);
+        glyphs.iter().all(|shaped| {
+            self.glyph_id_indexes.get(&(font_name.clone(), variations.clone(), shaped.glyph_id)).is_some()
+        })
+    }
+    /// 预先缓存文字经过 GSUB/GPOS 处理后用到的字形
+    pub fn check_text(&mut self, font_name: String, variations: &Variations, text: &str) {
+        let glyphs = self.shape_both_directions(font_name.clone(), variations, text).unwrap_or_default();
+        let variations = self.get_variations(&font_name, variations);
+        for shaped in glyphs {
+            let key = (font_name.clone(), variations.clone(), shaped.glyph_id);
+            if self.glyph_id_indexes.get(&key).is_some() { continue; }
+            let (name, font) = match self.get_primary_font(&font_name) {
+                Some(primary) => primary,
+                None => return
+            };
+            let mut glyph = font.get_glyph_by_id(shaped.glyph_id, &font.get_variations(&variations));
+            glyph.font_name = name.clone();
+            self.glyph_caches.push(Box::new(glyph));
+            self.glyph_id_indexes.insert(key, self.glyph_caches.len() - 1);
         }
     }
     pub fn get_glyph_cache_count(&self) -> usize {
@@ -104,6 +497,26 @@ impl<Data: std::ops::Deref<Target=[u8]>> FontCache<Data> {
     pub fn get_font_cache_count(&self) -> usize {
         self.font_map.0.len()
     }
+    pub(crate) fn get_font(&self, font_name: &str) -> Option<&Font<Data>> {
+        self.font_map.get(font_name).map(|font| &**font)
+    }
+    /// 文字块实际使用的字体名，用于预先缓存字形
+    pub fn get_face_name(&self, font_family: &str, weight: u16, italic: bool) -> String {
+        self.match_face(font_family, weight, italic).font_name
+    }
+}
+
+/// 软连字符、零宽空格、零宽连接符、连接符等格式字符不绘制
+fn is_zero_width(c: u32) -> bool {
+    match c {
+        0x00AD | 0x200B | 0x200C | 0x200D | 0x2060 | 0xFEFF => true,
+        _ => false
+    }
+}
+
+/// 排版时换行符不参与计算
+fn filter_line_break(text: &str) -> String {
+    text.chars().filter(|c| *c != 10 as char && *c != 13 as char).collect()
 }
 
 impl<Data: std::ops::Deref<Target=[u8]>> FontMap<Data> {
@@ -129,33 +542,219 @@ impl<Data: std::ops::Deref<Target=[u8]>> std::ops::DerefMut for FontMap<Data> {
 }
 
 impl<Data: std::ops::Deref<Target=[u8]>> FontMap<Data> {
-    fn char_to_glyph(&self, font_name: String, c: u32) -> Box<Glyph> {
-        if c == 8203u32 {
-            return Box::new(Glyph::get_none());
+    /// 依次使用第一个有该字的字体，都缺字时使用第一个已加载的字体，都未加载时返回 `None`，
+    /// 字形记录实际使用的字体名
+    fn char_to_glyph(&self, font_names: &[String], variations: &Variations, c: u32) -> Option<Box<Glyph>> {
+        if is_zero_width(c) {
+            return Some(Box::new(Glyph::get_none()));
         }
-        let mut result = self.get(&font_name);
-        let mut is_default = false;
-        if result.is_none() {
-            result = self.get("default");
-            is_default = true;
-        }
-        let mut font = result.unwrap();
-        let find_index = font.find_glyph_index(c.clone());
-        if find_index == 0 && !is_default {
-            font = self.get("default").unwrap();
-        }
-        let boxed = Box::new(font.get_glyph(c));
-        boxed
+        let mut fonts = font_names.iter().filter_map(|name| self.get_key_value(name));
+        let first = fonts.next()?;
+        let (name, font) = std::iter::once(first).chain(fonts)
+            .find(|(_, font)| font.find_glyph_index(c) != 0)
+            .unwrap_or(first);
+        let mut boxed = Box::new(font.get_glyph(c, &font.get_variations(variations)));
+        boxed.font_name = name.clone();
+        Some(boxed)
     }
 }
 
 impl<Data: std::ops::Deref<Target=[u8]>> MergedFont for FontCache<Data> {
-    fn char_to_glyph<'a>(&'a self, font_name: String, c: char) -> &'a Box<Glyph> {
-        let c = c as u32;
-        let result = self.glyph_indexes.get(&(font_name.clone(), c));
-        &self.glyph_caches.get(*result.unwrap()).unwrap()
+    fn char_to_glyph<'a>(&'a self, font_name: String, variations: &Variations, c: char) -> Option<&'a Box<Glyph>> {
+        let variations = self.get_variations(&font_name, variations);
+        let result = self.glyph_indexes.get(&(font_name, variations, c as u32))?;
+        self.glyph_caches.get(*result)
+    }
+
+    fn shape(&self, font_name: String, text: &str, option: &ShapeOption) -> Option<Vec<ShapedGlyph>> {
+        self.get_primary_font(&font_name)?.1.shape(text, option)
+    }
+
+    fn get_kerning(&self, font_name: String, left: u32, right: u32) -> i32 {
+        self.font_map.get(&font_name).map(|font| font.get_kerning(left, right)).unwrap_or(0)
+    }
+
+    fn id_to_glyph<'a>(&'a self, font_name: String, variations: &Variations, glyph_id: u32) -> Option<&'a Box<Glyph>> {
+        let variations = self.get_variations(&font_name, variations);
+        let result = self.glyph_id_indexes.get(&(font_name, variations, glyph_id))?;
+        self.glyph_caches.get(*result)
+    }
+
+    /// 在选用的字体之后追加支持该文字的回退字体，`OS/2` 中声明了与文字、语言对应的代码页的优先，
+    /// 其次按回退列表的顺序
+    fn match_script_face(&self, font_name: &str, script: &str, lang: &str) -> String {
+        let sample = match get_sample(script) {
+            Some(sample) if script != COMMON => sample as u32,
+            _ => return font_name.to_string()
+        };
+        let mut names = split_font_family(font_name);
+        let primary = self.get_primary_font(font_name).map(|(_, font)| (font.style.weight, font.style.italic));
+        let (weight, italic) = primary.unwrap_or((400, false));
+        let code_page = get_code_page(script, lang);
+        let mut candidates = Vec::<(bool, usize, String)>::new();
+        for (index, family) in self.get_script_candidates(script).into_iter().enumerate() {
+            let face = match self.match_single_face(family, weight, italic) {
+                Some(face) => face,
+                None => continue
+            };
+            if names.contains(&face.font_name) || candidates.iter().any(|(_, _, name)| *name == face.font_name) { continue; }
+            let font = match self.font_map.get(&face.font_name) {
+                Some(font) if font.find_glyph_index(sample) != 0 => font,
+                _ => continue
+            };
+            let mismatch = code_page.map(|bit| !font.style.has_code_page(bit)).unwrap_or(false);
+            candidates.push((mismatch, index, face.font_name));
+        }
+        candidates.sort();
+        names.extend(candidates.into_iter().map(|(_, _, name)| name));
+        names.join(",")
+    }
+
+    /// 字体族与其回退列表中已加载的字体以逗号连接，字重与斜体取第一个字体的
+    fn match_face(&self, font_family: &str, weight: u16, italic: bool) -> FontFace {
+        let faces: Vec<FontFace> = self.get_font_families(font_family).iter()
+            .filter_map(|family| self.match_single_face(family, weight, italic))
+            .collect();
+        match faces.first() {
+            Some(face) => {
+                let mut names = Vec::<&str>::new();
+                for face in faces.iter() {
+                    if !names.contains(&face.font_name.as_str()) { names.push(&face.font_name); }
+                }
+                FontFace { font_name: names.join(","), weight: face.weight, italic: face.italic }
+            }
+            None => {
+                let font_name = split_font_family(font_family).into_iter().next().unwrap_or_default();
+                FontFace { font_name, weight: 400, italic: false }
+            }
+        }
     }
 }
 
 
 
+
+#[cfg(test)]
+mod test {
+    use super::FontCache;
+    use crate::core::open_type_like::glyph::{ShapeOption, Variations};
+    use crate::core::data::text_data::TextData;
+    use crate::core::typesetting::{find_missing_glyphs, FontFace, MergedFont};
+    use crate::woff::decompress_woff;
+
+    fn load_font_cache() -> FontCache<Vec<u8>> {
+        let mut font_cache = FontCache::new();
+        let data = decompress_woff(include_bytes!("./c_764")).unwrap();
+        font_cache.load_font_bytes("happy".to_string(), data).unwrap();
+        font_cache
+    }
+
+    #[test]
+    fn test_check_text() {
+        let mut font_cache = load_font_cache();
+        let variations = Variations::default();
+        // 字体未加载时需要在加载后再缓存
+        assert!(!font_cache.has_text("missing".to_string(), &variations, "中文"));
+        assert!(!font_cache.has_text("happy".to_string(), &variations, "中文"));
+        font_cache.check_text("happy".to_string(), &variations, "中文\n");
+        assert!(font_cache.has_text("happy".to_string(), &variations, "中文"));
+
+        let shaped = font_cache.shape("happy".to_string(), "中文", &ShapeOption::default()).unwrap();
+        let ids: Vec<u32> = shaped.iter().map(|glyph| glyph.glyph_id).collect();
+        assert_eq!(ids, vec![942, 3192]);
+        // 字形以实际使用的字体名与字形序号区分
+        let glyph = font_cache.id_to_glyph("happy".to_string(), &variations, 942).unwrap();
+        assert_eq!((glyph.font_name.as_str(), glyph.glyph_id), ("happy", 942));
+        assert!(font_cache.id_to_glyph("happy".to_string(), &variations, 943).is_none());
+    }
+
+    #[test]
+    fn test_match_face() {
+        let font_cache = load_font_cache();
+        // 只有常规字体时使用该字体，由排版合成粗体与斜体
+        let face = font_cache.match_face("happy", 700, true);
+        assert_eq!(face, FontFace { font_name: "happy".to_string(), weight: 400, italic: false });
+        assert!(face.is_synthetic_bold(700) && face.is_synthetic_oblique(true));
+        // 未加载的字体族跳过，都没有加载时直接使用第一个字体族名
+        assert_eq!(font_cache.match_face("missing, happy", 400, false).font_name, "happy");
+        assert_eq!(font_cache.match_face("missing, other", 400, false), FontFace { font_name: "missing".to_string(), weight: 400, italic: false });
+    }
+
+    #[test]
+    fn test_match_family() {
+        let mut font_cache = load_font_cache();
+        let data = decompress_woff(include_bytes!("./c_764")).unwrap();
+        font_cache.load_font_bytes("bold".to_string(), data).unwrap();
+        font_cache.font_map.0.get_mut("bold").unwrap().style.weight = 700;
+        let family = font_cache.get_font("happy").unwrap().style.family.clone();
+        // 族名相同的字体不会替换以该名字加载的字体
+        assert_eq!(font_cache.match_single_face("happy", 700, false).unwrap().font_name, "happy");
+        assert_eq!(font_cache.match_single_face("bold", 400, false).unwrap().font_name, "bold");
+        // 族名不区分大小写，在族名相同的字体中按样式选择
+        assert_eq!(font_cache.match_single_face(&family, 700, false).unwrap().font_name, "bold");
+        assert_eq!(font_cache.match_single_face(&family.to_uppercase(), 400, false).unwrap().font_name, "happy");
+        assert!(font_cache.match_single_face("missing", 400, false).is_none());
+        // 重新加载时从原来的族名中移除
+        font_cache.load_font_bytes("bold".to_string(), get_collection()).unwrap();
+        assert_eq!(font_cache.families[&family.to_lowercase()].iter().filter(|name| *name == "bold").count(), 1);
+    }
+
+    #[test]
+    fn test_find_missing_glyphs() {
+        let mut font_cache = load_font_cache();
+        let variations = Variations::default();
+        for c in "中가".chars() {
+            font_cache.check_glyph("happy".to_string(), &variations, c as u32);
+        }
+        let source = r#"{"width": 100, "height": 100, "paragraph": {"contents": [{"blocks": [
+            {"text": "中가", "fontFamily": "happy"}, {"text": "中", "fontFamily": "missing"}
+        ]}]}}"#;
+        let text_data = TextData::parse(source).unwrap();
+        let missing: Vec<(usize, u32, String)> = find_missing_glyphs(&text_data, &font_cache).into_iter()
+            .map(|missing| (missing.block_index, missing.char_code, missing.font_family))
+            .collect();
+        // 字体中没有的字与未加载的字体中的字都报告
+        assert_eq!(missing, vec![(0, '가' as u32, "happy".to_string()), (1, '中' as u32, "missing".to_string())]);
+    }
+
+    /// 两个字体都指向同一个表目录的字体集合，表的偏移改为相对集合开头
+    fn get_collection() -> Vec<u8> {
+        let face = decompress_woff(include_bytes!("./c_764")).unwrap();
+        let header_size = 20u32;
+        let mut data = b"ttcf".to_vec();
+        for value in [0x00010000u32, 2, header_size, header_size].iter() {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        let mut face = face;
+        let num_tables = u16::from_be_bytes([face[4], face[5]]) as usize;
+        for i in 0..num_tables {
+            let record = 12 + i * 16 + 8;
+            let offset = u32::from_be_bytes([face[record], face[record + 1], face[record + 2], face[record + 3]]);
+            face[record..record + 4].copy_from_slice(&(offset + header_size).to_be_bytes());
+        }
+        data.extend_from_slice(&face);
+        data
+    }
+
+    #[test]
+    fn test_load_collection() {
+        let mut font_cache = FontCache::new();
+        font_cache.load_font_bytes("collection".to_string(), get_collection()).unwrap();
+        // 第一个字体同时以加载的名字注册，重名的字体以序号区分
+        let full_name = font_cache.get_font("collection").unwrap().style.get_full_name().unwrap();
+        let mut names: Vec<&String> = font_cache.font_map.0.keys().collect();
+        names.sort();
+        assert_eq!(names, vec![&full_name, "collection", "collection#1"]);
+        // 加载时的名字在集合的所有字体中选择，集合中的字体名只对应该字体
+        assert_eq!(font_cache.faces["collection"], vec!["collection".to_string(), full_name.clone(), "collection#1".to_string()]);
+        assert_eq!(font_cache.match_single_face("collection#1", 400, false).unwrap().font_name, "collection#1");
+
+        font_cache.load_font_face("second".to_string(), get_collection(), Some(1)).unwrap();
+        assert!(font_cache.get_font("second").is_some());
+        assert!(font_cache.load_font_face("third".to_string(), get_collection(), Some(2)).is_none());
+        let variations = Variations::default();
+        font_cache.check_glyph("second".to_string(), &variations, '中' as u32);
+        let glyph = font_cache.char_to_glyph("second".to_string(), &variations, '中').unwrap();
+        assert_eq!((glyph.font_name.as_str(), glyph.glyph_id), ("second", 942));
+    }
+}
@@ -0,0 +1,598 @@
+use crate::core::open_type_like::glyph::Variations;
+use crate::core::open_type_like::path::PathData;
+use crate::kern::{find_table, read_u16, read_u32};
+
+/// 组合字形嵌套的最大层数
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_fixed(data: &[u8], offset: usize) -> Option<f32> {
+    read_u32(data, offset).map(|value| value as i32 as f32 / 65536f32)
+}
+
+fn read_f2dot14(data: &[u8], offset: usize) -> Option<f32> {
+    read_i16(data, offset).map(|value| value as f32 / 16384f32)
+}
+
+/// `fvar` 中的一个轴
+#[derive(Debug, Clone)]
+pub struct Axis {
+    pub tag: [u8; 4],
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+}
+
+/// 可变字体的轴与各变体表的位置，轮廓只支持 `glyf`
+#[derive(Debug, Clone)]
+pub struct VariableFont {
+    pub axes: Vec<Axis>,
+    avar: Option<usize>,
+    gvar: Option<usize>,
+    hvar: Option<usize>,
+    glyf: Option<(usize, usize, bool)>,
+}
+
+/// 字形的点，`ends` 为每个轮廓最后一个点的序号
+#[derive(Debug, Clone, Default)]
+struct Outline {
+    points: Vec<(f32, f32)>,
+    on_curve: Vec<bool>,
+    ends: Vec<usize>,
+}
+
+struct Component {
+    glyph_id: u32,
+    offset: (f32, f32),
+    matrix: (f32, f32, f32, f32),
+}
+
+impl VariableFont {
+    /// 没有 `fvar` 表或没有轴时返回 `None`
+    pub fn new(data: &[u8], face_offset: usize) -> Option<Self> {
+        let (fvar, _) = find_table(data, face_offset, b"fvar")?;
+        let axes_offset = fvar + read_u16(data, fvar + 4)? as usize;
+        let axis_count = read_u16(data, fvar + 8)? as usize;
+        let axis_size = read_u16(data, fvar + 10)? as usize;
+        let axes = (0..axis_count).map(|i| {
+            let record = axes_offset + i * axis_size;
+            let tag = data.get(record..record + 4)?;
+            Some(Axis {
+                tag: [tag[0], tag[1], tag[2], tag[3]],
+                min: read_fixed(data, record + 4)?,
+                default: read_fixed(data, record + 8)?,
+                max: read_fixed(data, record + 12)?,
+            })
+        }).collect::<Option<Vec<Axis>>>()?;
+        if axes.is_empty() { return None; }
+        let glyf = match (find_table(data, face_offset, b"glyf"), find_table(data, face_offset, b"loca"), find_table(data, face_offset, b"head")) {
+            (Some((glyf, _)), Some((loca, _)), Some((head, _))) => Some((glyf, loca, read_i16(data, head + 50)? != 0)),
+            _ => None
+        };
+        Some(VariableFont {
+            axes,
+            avar: find_table(data, face_offset, b"avar").map(|(offset, _)| offset),
+            gvar: find_table(data, face_offset, b"gvar").map(|(offset, _)| offset),
+            hvar: find_table(data, face_offset, b"HVAR").map(|(offset, _)| offset),
+            glyf,
+        })
+    }
+
+    /// 只保留字体中存在的轴，超出范围的取值截断，等于默认值的轴去掉
+    pub fn filter(&self, variations: &Variations) -> Variations {
+        let values: Vec<([u8; 4], f32)> = self.axes.iter().filter_map(|axis| {
+            let value = variations.get(&axis.tag)?.max(axis.min).min(axis.max);
+            if value == axis.default { None } else { Some((axis.tag, value)) }
+        }).collect();
+        Variations::new(&values)
+    }
+
+    /// 归一化到 -1 到 1 并经过 `avar` 映射的坐标，按 `fvar` 中轴的顺序排列
+    pub fn get_coords(&self, data: &[u8], variations: &Variations) -> Vec<f32> {
+        let mut segment = self.avar.map(|avar| avar + 8);
+        self.axes.iter().enumerate().map(|(i, axis)| {
+            let value = variations.get(&axis.tag).unwrap_or(axis.default).max(axis.min).min(axis.max);
+            let mut coord = if value < axis.default {
+                (value - axis.default) / (axis.default - axis.min)
+            } else if value > axis.default {
+                (value - axis.default) / (axis.max - axis.default)
+            } else {
+                0f32
+            };
+            if let Some(offset) = segment {
+                let count = read_u16(data, offset).unwrap_or(0) as usize;
+                if self.avar.and_then(|avar| read_u16(data, avar + 6)).map(|n| i < n as usize).unwrap_or(false) {
+                    coord = map_segment(data, offset + 2, count, coord);
+                }
+                segment = Some(offset + 2 + count * 4);
+            }
+            (coord * 16384f32).round() / 16384f32
+        }).collect()
+    }
+
+    /// 应用 `gvar` 后的轮廓与前进宽度的变化量，优先使用 `HVAR` 中的前进宽度
+    pub fn get_glyph(&self, data: &[u8], glyph_id: u32, coords: &[f32]) -> Option<(PathData, f32)> {
+        let (outline, (left, right)) = self.get_outline(data, glyph_id, coords, 0)?;
+        let advance = self.get_advance_delta(data, glyph_id, coords).unwrap_or(right - left);
+        // 左侧幻点移动时原点随之移动
+        let points: Vec<(f32, f32)> = outline.points.iter().map(|(x, y)| (x - left, *y)).collect();
+        Some((to_path_data(&Outline { points, ..outline }), advance))
+    }
+
+    /// `HVAR` 中的前进宽度变化量，没有该表时返回 `None`
+    pub fn get_advance_delta(&self, data: &[u8], glyph_id: u32, coords: &[f32]) -> Option<f32> {
+        let hvar = self.hvar?;
+        let store = hvar + read_u32(data, hvar + 4)? as usize;
+        let mapping = read_u32(data, hvar + 8)? as usize;
+        let (outer, inner) = if mapping == 0 {
+            (0, glyph_id as usize)
+        } else {
+            get_delta_set_index(data, hvar + mapping, glyph_id as usize)?
+        };
+        get_item_delta(data, store, outer, inner, coords)
+    }
+
+    pub fn has_advance_variations(&self) -> bool {
+        self.hvar.is_some()
+    }
+
+    /// 两个幻点在 x 方向变化量之差，即没有 `HVAR` 时前进宽度的变化量
+    pub fn get_phantom_advance_delta(&self, data: &[u8], glyph_id: u32, coords: &[f32]) -> f32 {
+        self.get_outline(data, glyph_id, coords, 0).map(|(_, (left, right))| right - left).unwrap_or(0f32)
+    }
+
+    fn get_glyph_data<'a>(&self, data: &'a [u8], glyph_id: u32) -> Option<&'a [u8]> {
+        let (glyf, loca, long) = self.glyf?;
+        let index = glyph_id as usize;
+        let (start, end) = if long {
+            (read_u32(data, loca + index * 4)? as usize, read_u32(data, loca + index * 4 + 4)? as usize)
+        } else {
+            (read_u16(data, loca + index * 2)? as usize * 2, read_u16(data, loca + index * 2 + 2)? as usize * 2)
+        };
+        if end < start { return None; }
+        data.get(glyf + start..glyf + end)
+    }
+
+    /// 字形的点及左右两个幻点在 x 方向的变化量
+    fn get_outline(&self, data: &[u8], glyph_id: u32, coords: &[f32], depth: usize) -> Option<(Outline, (f32, f32))> {
+        if depth > MAX_COMPONENT_DEPTH { return None; }
+        let glyph = self.get_glyph_data(data, glyph_id)?;
+        if glyph.is_empty() {
+            let deltas = self.get_deltas(data, glyph_id, coords, &[(0f32, 0f32); 4], &[]);
+            return Some((Outline::default(), (deltas[0].0, deltas[1].0)));
+        }
+        let contour_count = read_i16(glyph, 0)?;
+        if contour_count >= 0 {
+            let mut outline = parse_simple_glyph(glyph, contour_count as usize)?;
+            let count = outline.points.len();
+            let mut points = outline.points.clone();
+            points.extend_from_slice(&[(0f32, 0f32); 4]);
+            let deltas = self.get_deltas(data, glyph_id, coords, &points, &outline.ends);
+            for (point, (dx, dy)) in outline.points.iter_mut().zip(deltas.iter()) {
+                point.0 += dx;
+                point.1 += dy;
+            }
+            Some((outline, (deltas[count].0, deltas[count + 1].0)))
+        } else {
+            let components = parse_composite_glyph(glyph)?;
+            let mut points: Vec<(f32, f32)> = components.iter().map(|c| c.offset).collect();
+            points.extend_from_slice(&[(0f32, 0f32); 4]);
+            let deltas = self.get_deltas(data, glyph_id, coords, &points, &[]);
+            let mut outline = Outline::default();
+            for (component, (dx, dy)) in components.iter().zip(deltas.iter()) {
+                let (child, _) = match self.get_outline(data, component.glyph_id, coords, depth + 1) {
+                    Some(child) => child,
+                    None => continue
+                };
+                let (a, b, c, d) = component.matrix;
+                let (ox, oy) = (component.offset.0 + dx, component.offset.1 + dy);
+                let base = outline.points.len();
+                outline.points.extend(child.points.iter().map(|(x, y)| (a * x + c * y + ox, b * x + d * y + oy)));
+                outline.on_curve.extend(child.on_curve.iter());
+                outline.ends.extend(child.ends.iter().map(|end| end + base));
+            }
+            let count = components.len();
+            Some((outline, (deltas[count].0, deltas[count + 1].0)))
+        }
+    }
+
+    /// `gvar` 中各元组按当前坐标加权后的点的变化量，`ends` 不为空时推算未指定的点
+    fn get_deltas(&self, data: &[u8], glyph_id: u32, coords: &[f32], points: &[(f32, f32)], ends: &[usize]) -> Vec<(f32, f32)> {
+        let mut deltas = vec![(0f32, 0f32); points.len()];
+        self.add_deltas(data, glyph_id, coords, points, ends, &mut deltas);
+        deltas
+    }
+
+    fn add_deltas(&self, data: &[u8], glyph_id: u32, coords: &[f32], points: &[(f32, f32)], ends: &[usize], deltas: &mut [(f32, f32)]) -> Option<()> {
+        let gvar = self.gvar?;
+        let axis_count = read_u16(data, gvar + 4)? as usize;
+        let shared_tuples = gvar + read_u32(data, gvar + 8)? as usize;
+        let glyph_count = read_u16(data, gvar + 12)? as u32;
+        let long_offsets = read_u16(data, gvar + 14)? & 1 != 0;
+        let array = gvar + read_u32(data, gvar + 16)? as usize;
+        if glyph_id >= glyph_count { return None; }
+        let index = glyph_id as usize;
+        let (start, end) = if long_offsets {
+            (read_u32(data, gvar + 20 + index * 4)? as usize, read_u32(data, gvar + 24 + index * 4)? as usize)
+        } else {
+            (read_u16(data, gvar + 20 + index * 2)? as usize * 2, read_u16(data, gvar + 22 + index * 2)? as usize * 2)
+        };
+        if start >= end { return None; }
+
+        let base = array + start;
+        let tuple_count = read_u16(data, base)?;
+        let mut serialized = base + read_u16(data, base + 2)? as usize;
+        let mut shared_points = None;
+        if tuple_count & 0x8000 != 0 {
+            let (numbers, next) = read_point_numbers(data, serialized)?;
+            shared_points = numbers;
+            serialized = next;
+        }
+        let mut header = base + 4;
+        for _ in 0..(tuple_count & 0x0FFF) {
+            let size = read_u16(data, header)? as usize;
+            let tuple_index = read_u16(data, header + 2)?;
+            header += 4;
+            let read_tuple = |offset: usize| (0..axis_count).map(|i| read_f2dot14(data, offset + i * 2)).collect::<Option<Vec<f32>>>();
+            let peak = if tuple_index & 0x8000 != 0 {
+                header += axis_count * 2;
+                read_tuple(header - axis_count * 2)?
+            } else {
+                read_tuple(shared_tuples + (tuple_index & 0x0FFF) as usize * axis_count * 2)?
+            };
+            let region = if tuple_index & 0x4000 != 0 {
+                header += axis_count * 4;
+                Some((read_tuple(header - axis_count * 4)?, read_tuple(header - axis_count * 2)?))
+            } else {
+                None
+            };
+            let tuple_data = serialized;
+            serialized += size;
+            let scalar = get_tuple_scalar(coords, &peak, region.as_ref());
+            if scalar == 0f32 { continue; }
+
+            let (numbers, cursor) = if tuple_index & 0x2000 != 0 {
+                read_point_numbers(data, tuple_data)?
+            } else {
+                (shared_points.clone(), tuple_data)
+            };
+            let count = numbers.as_ref().map(|numbers| numbers.len()).unwrap_or(points.len());
+            let (xs, cursor) = read_packed_deltas(data, cursor, count)?;
+            let (ys, _) = read_packed_deltas(data, cursor, count)?;
+            match numbers {
+                None => {
+                    for (i, delta) in deltas.iter_mut().enumerate() {
+                        delta.0 += xs[i] * scalar;
+                        delta.1 += ys[i] * scalar;
+                    }
+                }
+                Some(numbers) => {
+                    let mut tuple = vec![None; points.len()];
+                    for (k, number) in numbers.iter().enumerate() {
+                        if let Some(item) = tuple.get_mut(*number) { *item = Some((xs[k], ys[k])); }
+                    }
+                    infer_deltas(points, &mut tuple, ends);
+                    for (delta, item) in deltas.iter_mut().zip(tuple.iter()) {
+                        if let Some((dx, dy)) = item {
+                            delta.0 += dx * scalar;
+                            delta.1 += dy * scalar;
+                        }
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+/// `avar` 的分段线性映射
+fn map_segment(data: &[u8], offset: usize, count: usize, coord: f32) -> f32 {
+    let pairs: Vec<(f32, f32)> = (0..count).filter_map(|i| {
+        Some((read_f2dot14(data, offset + i * 4)?, read_f2dot14(data, offset + i * 4 + 2)?))
+    }).collect();
+    if pairs.is_empty() { return coord; }
+    if coord <= pairs[0].0 { return pairs[0].1; }
+    for k in 1..pairs.len() {
+        let (from1, to1) = pairs[k - 1];
+        let (from2, to2) = pairs[k];
+        if coord <= from2 {
+            if from2 == from1 { return to2; }
+            return to1 + (coord - from1) * (to2 - to1) / (from2 - from1);
+        }
+    }
+    pairs[pairs.len() - 1].1
+}
+
+/// 元组在当前坐标下的权重
+fn get_tuple_scalar(coords: &[f32], peak: &[f32], region: Option<&(Vec<f32>, Vec<f32>)>) -> f32 {
+    let mut scalar = 1f32;
+    for (i, p) in peak.iter().enumerate() {
+        let p = *p;
+        let v = coords.get(i).cloned().unwrap_or(0f32);
+        if p == 0f32 || v == p { continue; }
+        match region {
+            Some((start, end)) => {
+                let (s, e) = (start[i], end[i]);
+                if v < s || v > e { return 0f32; }
+                scalar *= if v < p { (v - s) / (p - s) } else { (e - v) / (e - p) };
+            }
+            None => {
+                if v == 0f32 || v < p.min(0f32) || v > p.max(0f32) { return 0f32; }
+                scalar *= v / p;
+            }
+        }
+    }
+    scalar
+}
+
+/// 压缩的点序号，`None` 表示所有点
+fn read_point_numbers(data: &[u8], offset: usize) -> Option<(Option<Vec<usize>>, usize)> {
+    let first = *data.get(offset)? as usize;
+    if first == 0 { return Some((None, offset + 1)); }
+    let (count, mut cursor) = if first & 0x80 != 0 {
+        (((first & 0x7F) << 8) | *data.get(offset + 1)? as usize, offset + 2)
+    } else {
+        (first, offset + 1)
+    };
+    let mut numbers = Vec::<usize>::with_capacity(count);
+    let mut last = 0usize;
+    while numbers.len() < count {
+        let control = *data.get(cursor)?;
+        cursor += 1;
+        let run = (control & 0x7F) as usize + 1;
+        for _ in 0..run.min(count - numbers.len()) {
+            if control & 0x80 != 0 {
+                last += read_u16(data, cursor)? as usize;
+                cursor += 2;
+            } else {
+                last += *data.get(cursor)? as usize;
+                cursor += 1;
+            }
+            numbers.push(last);
+        }
+    }
+    Some((Some(numbers), cursor))
+}
+
+fn read_packed_deltas(data: &[u8], offset: usize, count: usize) -> Option<(Vec<f32>, usize)> {
+    let mut deltas = Vec::<f32>::with_capacity(count);
+    let mut cursor = offset;
+    while deltas.len() < count {
+        let control = *data.get(cursor)?;
+        cursor += 1;
+        let run = (control & 0x3F) as usize + 1;
+        for _ in 0..run.min(count - deltas.len()) {
+            if control & 0x80 != 0 {
+                deltas.push(0f32);
+            } else if control & 0x40 != 0 {
+                deltas.push(read_i16(data, cursor)? as f32);
+                cursor += 2;
+            } else {
+                deltas.push(*data.get(cursor)? as i8 as f32);
+                cursor += 1;
+            }
+        }
+    }
+    Some((deltas, cursor))
+}
+
+/// 同一轮廓中未指定变化量的点按前后两个指定的点插值
+fn infer_deltas(points: &[(f32, f32)], deltas: &mut [Option<(f32, f32)>], ends: &[usize]) {
+    fn interpolate(c: f32, c1: f32, c2: f32, d1: f32, d2: f32) -> f32 {
+        if c1 == c2 { return if d1 == d2 { d1 } else { 0f32 }; }
+        let (c1, c2, d1, d2) = if c1 > c2 { (c2, c1, d2, d1) } else { (c1, c2, d1, d2) };
+        if c <= c1 { d1 } else if c >= c2 { d2 } else { d1 + (c - c1) * (d2 - d1) / (c2 - c1) }
+    }
+
+    let mut start = 0usize;
+    for end in ends.iter().cloned() {
+        if end >= deltas.len() || end < start { break; }
+        let touched: Vec<usize> = (start..=end).filter(|i| deltas[*i].is_some()).collect();
+        if !touched.is_empty() && touched.len() <= end - start {
+            for (k, t) in touched.iter().cloned().enumerate() {
+                let next = touched[(k + 1) % touched.len()];
+                let (d1, d2) = (deltas[t].unwrap(), deltas[next].unwrap());
+                let mut i = if t == end { start } else { t + 1 };
+                while i != next {
+                    deltas[i] = Some((
+                        interpolate(points[i].0, points[t].0, points[next].0, d1.0, d2.0),
+                        interpolate(points[i].1, points[t].1, points[next].1, d1.1, d2.1),
+                    ));
+                    i = if i == end { start } else { i + 1 };
+                }
+            }
+        }
+        start = end + 1;
+    }
+}
+
+/// `DeltaSetIndexMap` 中字形对应的 `(outer, inner)`
+fn get_delta_set_index(data: &[u8], offset: usize, glyph_id: usize) -> Option<(usize, usize)> {
+    let format = *data.get(offset)?;
+    let entry_format = *data.get(offset + 1)? as usize;
+    let (count, start) = if format == 0 {
+        (read_u16(data, offset + 2)? as usize, offset + 4)
+    } else {
+        (read_u32(data, offset + 2)? as usize, offset + 6)
+    };
+    if count == 0 { return None; }
+    let size = ((entry_format >> 4) & 3) + 1;
+    let inner_bits = (entry_format & 0x0F) + 1;
+    let entry_offset = start + glyph_id.min(count - 1) * size;
+    let entry = data.get(entry_offset..entry_offset + size)?.iter().fold(0usize, |entry, b| (entry << 8) | *b as usize);
+    Some((entry >> inner_bits, entry & ((1 << inner_bits) - 1)))
+}
+
+/// `ItemVariationStore` 中一项按当前坐标加权后的变化量
+fn get_item_delta(data: &[u8], store: usize, outer: usize, inner: usize, coords: &[f32]) -> Option<f32> {
+    let scalars = get_region_scalars(data, store, outer, coords)?;
+    let item_data = store + read_u32(data, store + 8 + outer * 4)? as usize;
+    let item_count = read_u16(data, item_data)? as usize;
+    if inner >= item_count { return None; }
+    let word_delta_count = read_u16(data, item_data + 2)? as usize;
+    let region_index_count = scalars.len();
+    let long_words = word_delta_count & 0x8000 != 0;
+    let word_count = word_delta_count & 0x7FFF;
+    let (word_size, short_size) = if long_words { (4, 2) } else { (2, 1) };
+    let row_size = word_count * word_size + (region_index_count - word_count.min(region_index_count)) * short_size;
+    let mut cursor = item_data + 6 + region_index_count * 2 + inner * row_size;
+
+    let mut delta = 0f32;
+    for (j, scalar) in scalars.iter().enumerate() {
+        let value = match (j < word_count, long_words) {
+            (true, true) => read_u32(data, cursor)? as i32 as f32,
+            (true, false) | (false, true) => read_i16(data, cursor)? as f32,
+            (false, false) => *data.get(cursor)? as i8 as f32,
+        };
+        cursor += if j < word_count { word_size } else { short_size };
+        delta += value * scalar;
+    }
+    Some(delta)
+}
+
+/// `ItemVariationStore` 中第 `outer` 组数据用到的各区域在当前坐标下的权重
+pub(crate) fn get_region_scalars(data: &[u8], store: usize, outer: usize, coords: &[f32]) -> Option<Vec<f32>> {
+    let regions = store + read_u32(data, store + 2)? as usize;
+    let data_count = read_u16(data, store + 6)? as usize;
+    if outer >= data_count { return None; }
+    let item_data = store + read_u32(data, store + 8 + outer * 4)? as usize;
+    let region_index_count = read_u16(data, item_data + 4)? as usize;
+    let axis_count = read_u16(data, regions)? as usize;
+    (0..region_index_count).map(|j| {
+        let region_index = read_u16(data, item_data + 6 + j * 2)? as usize;
+        let region = regions + 4 + region_index * axis_count * 6;
+        let mut scalar = 1f32;
+        for i in 0..axis_count {
+            let s = read_f2dot14(data, region + i * 6)?;
+            let p = read_f2dot14(data, region + i * 6 + 2)?;
+            let e = read_f2dot14(data, region + i * 6 + 4)?;
+            let v = coords.get(i).cloned().unwrap_or(0f32);
+            if s > p || p > e || (s < 0f32 && e > 0f32 && p != 0f32) || p == 0f32 || v == p { continue; }
+            if v < s || v > e { return Some(0f32); }
+            scalar *= if v < p { (v - s) / (p - s) } else { (e - v) / (e - p) };
+        }
+        Some(scalar)
+    }).collect()
+}
+
+fn parse_simple_glyph(glyph: &[u8], contour_count: usize) -> Option<Outline> {
+    let ends = (0..contour_count).map(|i| read_u16(glyph, 10 + i * 2).map(|end| end as usize)).collect::<Option<Vec<usize>>>()?;
+    let count = ends.last().map(|end| end + 1).unwrap_or(0);
+    let instruction_length = read_u16(glyph, 10 + contour_count * 2)? as usize;
+    let mut cursor = 12 + contour_count * 2 + instruction_length;
+
+    let mut flags = Vec::<u8>::with_capacity(count);
+    while flags.len() < count {
+        let flag = *glyph.get(cursor)?;
+        cursor += 1;
+        flags.push(flag);
+        if flag & 0x08 != 0 {
+            let repeat = *glyph.get(cursor)?;
+            cursor += 1;
+            for _ in 0..repeat { flags.push(flag); }
+        }
+    }
+    flags.truncate(count);
+
+    // x 与 y 依次存放，短格式时 same 位表示正负，长格式时表示与上一个点相同
+    let mut read_coords = |short: u8, same: u8| -> Option<Vec<f32>> {
+        let mut value = 0i32;
+        flags.iter().map(|flag| {
+            if flag & short != 0 {
+                let delta = *glyph.get(cursor)? as i32;
+                cursor += 1;
+                value += if flag & same != 0 { delta } else { -delta };
+            } else if flag & same == 0 {
+                value += read_i16(glyph, cursor)? as i32;
+                cursor += 2;
+            }
+            Some(value as f32)
+        }).collect()
+    };
+    let xs = read_coords(0x02, 0x10)?;
+    let ys = read_coords(0x04, 0x20)?;
+    Some(Outline {
+        points: xs.into_iter().zip(ys).collect(),
+        on_curve: flags.iter().map(|flag| flag & 0x01 != 0).collect(),
+        ends,
+    })
+}
+
+/// 组合字形的各部件，按点对齐的部件不支持，偏移视为 0
+fn parse_composite_glyph(glyph: &[u8]) -> Option<Vec<Component>> {
+    let mut components = Vec::<Component>::new();
+    let mut cursor = 10usize;
+    loop {
+        let flags = read_u16(glyph, cursor)?;
+        let glyph_id = read_u16(glyph, cursor + 2)? as u32;
+        cursor += 4;
+        let (arg1, arg2) = if flags & 0x0001 != 0 {
+            cursor += 4;
+            (read_i16(glyph, cursor - 4)? as f32, read_i16(glyph, cursor - 2)? as f32)
+        } else {
+            cursor += 2;
+            (*glyph.get(cursor - 2)? as i8 as f32, *glyph.get(cursor - 1)? as i8 as f32)
+        };
+        let offset = if flags & 0x0002 != 0 { (arg1, arg2) } else { (0f32, 0f32) };
+        let matrix = if flags & 0x0008 != 0 {
+            let scale = read_f2dot14(glyph, cursor)?;
+            cursor += 2;
+            (scale, 0f32, 0f32, scale)
+        } else if flags & 0x0040 != 0 {
+            cursor += 4;
+            (read_f2dot14(glyph, cursor - 4)?, 0f32, 0f32, read_f2dot14(glyph, cursor - 2)?)
+        } else if flags & 0x0080 != 0 {
+            cursor += 8;
+            (read_f2dot14(glyph, cursor - 8)?, read_f2dot14(glyph, cursor - 6)?, read_f2dot14(glyph, cursor - 4)?, read_f2dot14(glyph, cursor - 2)?)
+        } else {
+            (1f32, 0f32, 0f32, 1f32)
+        };
+        components.push(Component { glyph_id, offset, matrix });
+        if flags & 0x0020 == 0 { break; }
+    }
+    Some(components)
+}
+
+/// TrueType 二次曲线轮廓转为路径，相邻的两个控制点之间补上中点
+fn to_path_data(outline: &Outline) -> PathData {
+    let mut path_data = PathData::new();
+    let mid = |a: (f32, f32), b: (f32, f32)| ((a.0 + b.0) / 2f32, (a.1 + b.1) / 2f32);
+    let mut start = 0usize;
+    for end in outline.ends.iter().cloned() {
+        if end < start || end >= outline.points.len() { break; }
+        let points = &outline.points[start..=end];
+        let on_curve = &outline.on_curve[start..=end];
+        let count = points.len();
+        start = end + 1;
+        // 从第一个曲线上的点开始，全是控制点时从首尾两点的中点开始
+        let (first, order): ((f32, f32), Vec<usize>) = match on_curve.iter().position(|on| *on) {
+            Some(s) => (points[s], (1..=count).map(|k| (s + k) % count).collect()),
+            None => (mid(points[count - 1], points[0]), (0..count).collect())
+        };
+        path_data.move_to(first.0, first.1);
+        let mut control: Option<(f32, f32)> = None;
+        for i in order {
+            let point = points[i];
+            if on_curve[i] {
+                match control.take() {
+                    Some(c) => path_data.quad_to(point.0, point.1, c.0, c.1),
+                    None => path_data.line_to(point.0, point.1),
+                }
+            } else {
+                if let Some(c) = control {
+                    let m = mid(c, point);
+                    path_data.quad_to(m.0, m.1, c.0, c.1);
+                }
+                control = Some(point);
+            }
+        }
+        if let Some(c) = control {
+            path_data.quad_to(first.0, first.1, c.0, c.1);
+        }
+        path_data.close();
+    }
+    path_data
+}
@@ -0,0 +1,618 @@
+extern crate brotli_decompressor;
+
+use crate::kern::{read_u16, read_u32};
+
+const WOFF2_SIGNATURE: u32 = 0x774F4632;
+const TTC_TAG: u32 = 0x74746366;
+const HEADER_SIZE: usize = 48;
+/// 头部声明的大小只用于预分配，不可信，超过时按需增长
+const MAX_SIZE_HINT: usize = 32 << 20;
+
+// 表目录里用 6 位索引表示的常见表
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post",
+    b"cvt ", b"fpgm", b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT",
+    b"EBLC", b"gasp", b"hdmx", b"kern", b"LTSH", b"PCLT", b"VDMX", b"vhea",
+    b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC", b"JSTF", b"MATH",
+    b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar",
+    b"gvar", b"hsty", b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop",
+    b"trak", b"Zapf", b"Silf", b"Glat", b"Gloc", b"Feat", b"Sill",
+];
+
+// glyf 里的标志位
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const REPEAT_FLAG: u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+const OVERLAP_SIMPLE: u8 = 0x40;
+
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+struct Stream<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Stream<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Stream { data, offset: 0 }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let r = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        Some(r)
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        Some(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Option<u16> {
+        let r = self.read_bytes(2)?;
+        Some(u16::from_be_bytes([r[0], r[1]]))
+    }
+
+    fn read_i16(&mut self) -> Option<i16> {
+        Some(self.read_u16()? as i16)
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        let r = self.read_bytes(4)?;
+        Some(u32::from_be_bytes([r[0], r[1], r[2], r[3]]))
+    }
+
+    fn read_base128(&mut self) -> Option<u32> {
+        let mut result = 0u32;
+        for i in 0..5 {
+            let b = self.read_u8()?;
+            // 不允许前导 0
+            if i == 0 && b == 0x80 {
+                return None;
+            }
+            if result & 0xFE00_0000 != 0 {
+                return None;
+            }
+            result = (result << 7) | (b & 0x7F) as u32;
+            if b & 0x80 == 0 {
+                return Some(result);
+            }
+        }
+        None
+    }
+
+    fn read_255_u16(&mut self) -> Option<u16> {
+        let code = self.read_u8()?;
+        match code {
+            253 => self.read_u16(),
+            255 => Some(self.read_u8()? as u16 + 253),
+            254 => Some(self.read_u8()? as u16 + 506),
+            _ => Some(code as u16),
+        }
+    }
+
+    fn split(&mut self, len: usize) -> Option<Stream<'a>> {
+        Some(Stream::new(self.read_bytes(len)?))
+    }
+}
+
+struct TableEntry {
+    tag: [u8; 4],
+    orig_length: usize,
+    transform_length: Option<usize>,
+    src_offset: usize,
+}
+
+impl TableEntry {
+    fn src_length(&self) -> usize {
+        self.transform_length.unwrap_or(self.orig_length)
+    }
+}
+
+pub(crate) fn push_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+pub(crate) fn push_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+pub(crate) fn pad4(out: &mut Vec<u8>) {
+    while !out.len().is_multiple_of(4) {
+        out.push(0);
+    }
+}
+
+fn with_sign(flag: u8, base: i32) -> i32 {
+    if flag & 1 != 0 { base } else { -base }
+}
+
+/// 按 WOFF2 的三元组编码解出一个点的位移
+fn decode_triplet(flag: u8, glyph: &mut Stream) -> Option<(i32, i32)> {
+    let flag = flag & 0x7F;
+    let (dx, dy) = if flag < 10 {
+        let b0 = glyph.read_u8()? as i32;
+        (0, with_sign(flag, (((flag & 14) as i32) << 7) + b0))
+    } else if flag < 20 {
+        let b0 = glyph.read_u8()? as i32;
+        (with_sign(flag, ((((flag - 10) & 14) as i32) << 7) + b0), 0)
+    } else if flag < 84 {
+        let b0 = (flag - 20) as i32;
+        let b1 = glyph.read_u8()? as i32;
+        (with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+         with_sign(flag >> 1, 1 + ((b0 & 0x0C) << 2) + (b1 & 0x0F)))
+    } else if flag < 120 {
+        let b0 = (flag - 84) as i32;
+        let b1 = glyph.read_u8()? as i32;
+        let b2 = glyph.read_u8()? as i32;
+        (with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+         with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2))
+    } else if flag < 124 {
+        let b1 = glyph.read_u8()? as i32;
+        let b2 = glyph.read_u8()? as i32;
+        let b3 = glyph.read_u8()? as i32;
+        (with_sign(flag, (b1 << 4) + (b2 >> 4)),
+         with_sign(flag >> 1, ((b2 & 0x0F) << 8) + b3))
+    } else {
+        let b1 = glyph.read_u8()? as i32;
+        let b2 = glyph.read_u8()? as i32;
+        let b3 = glyph.read_u8()? as i32;
+        let b4 = glyph.read_u8()? as i32;
+        (with_sign(flag, (b1 << 8) + b2),
+         with_sign(flag >> 1, (b3 << 8) + b4))
+    };
+    Some((dx, dy))
+}
+
+/// 把点写成标准 glyf 的 flags 和坐标
+fn write_points(out: &mut Vec<u8>, points: &[(i32, i32, bool)], overlap: bool) -> Option<()> {
+    let mut flags = Vec::with_capacity(points.len());
+    let mut xs = vec![];
+    let mut ys = vec![];
+    let (mut last_x, mut last_y) = (0i32, 0i32);
+    for (i, &(x, y, on_curve)) in points.iter().enumerate() {
+        let mut flag = if on_curve { ON_CURVE_POINT } else { 0 };
+        if overlap && i == 0 {
+            flag |= OVERLAP_SIMPLE;
+        }
+        let dx = x - last_x;
+        let dy = y - last_y;
+        if dx == 0 {
+            flag |= X_IS_SAME_OR_POSITIVE;
+        } else if dx > -256 && dx < 256 {
+            flag |= X_SHORT_VECTOR;
+            if dx > 0 {
+                flag |= X_IS_SAME_OR_POSITIVE;
+            }
+            xs.push(dx.unsigned_abs() as u8);
+        } else {
+            if dx < i16::MIN as i32 || dx > i16::MAX as i32 {
+                return None;
+            }
+            xs.extend_from_slice(&(dx as i16).to_be_bytes());
+        }
+        if dy == 0 {
+            flag |= Y_IS_SAME_OR_POSITIVE;
+        } else if dy > -256 && dy < 256 {
+            flag |= Y_SHORT_VECTOR;
+            if dy > 0 {
+                flag |= Y_IS_SAME_OR_POSITIVE;
+            }
+            ys.push(dy.unsigned_abs() as u8);
+        } else {
+            if dy < i16::MIN as i32 || dy > i16::MAX as i32 {
+                return None;
+            }
+            ys.extend_from_slice(&(dy as i16).to_be_bytes());
+        }
+        flags.push(flag);
+        last_x = x;
+        last_y = y;
+    }
+    // 相同的 flag 用 REPEAT 合并
+    let mut i = 0;
+    while i < flags.len() {
+        let flag = flags[i];
+        let mut repeat = 0usize;
+        while i + repeat + 1 < flags.len() && flags[i + repeat + 1] == flag && repeat < 255 {
+            repeat += 1;
+        }
+        if repeat > 1 {
+            out.push(flag | REPEAT_FLAG);
+            out.push(repeat as u8);
+            i += repeat + 1;
+        } else {
+            out.push(flag);
+            i += 1;
+        }
+    }
+    out.extend_from_slice(&xs);
+    out.extend_from_slice(&ys);
+    Some(())
+}
+
+/// 还原变换过的 glyf，返回 (glyf, loca, 每个字形的 xMin)
+fn reconstruct_glyf(data: &[u8], index_format: &mut u16) -> Option<(Vec<u8>, Vec<u8>, Vec<i16>)> {
+    let mut rs = Stream::new(data);
+    let _reserved = rs.read_u16()?;
+    let option_flags = rs.read_u16()?;
+    let num_glyphs = rs.read_u16()? as usize;
+    *index_format = rs.read_u16()?;
+    let mut sizes = [0usize; 7];
+    for size in sizes.iter_mut() {
+        *size = rs.read_u32()? as usize;
+    }
+    let mut n_contour_stream = rs.split(sizes[0])?;
+    let mut n_points_stream = rs.split(sizes[1])?;
+    let mut flag_stream = rs.split(sizes[2])?;
+    let mut glyph_stream = rs.split(sizes[3])?;
+    let mut composite_stream = rs.split(sizes[4])?;
+    let mut bbox_stream = rs.split(sizes[5])?;
+    let mut instruction_stream = rs.split(sizes[6])?;
+    let overlap_bitmap = if option_flags & 1 != 0 {
+        Some(rs.read_bytes(num_glyphs.div_ceil(8))?)
+    } else {
+        None
+    };
+    let bbox_bitmap = bbox_stream.read_bytes(((num_glyphs + 31) >> 5) << 2)?;
+
+    let mut glyf = Vec::<u8>::new();
+    let mut offsets = Vec::with_capacity(num_glyphs + 1);
+    let mut x_mins = Vec::with_capacity(num_glyphs);
+
+    for i in 0..num_glyphs {
+        offsets.push(glyf.len());
+        let n_contours = n_contour_stream.read_i16()?;
+        let has_bbox = bbox_bitmap[i >> 3] & (0x80 >> (i & 7)) != 0;
+        let start = glyf.len();
+        if n_contours == 0 {
+            if has_bbox {
+                return None;
+            }
+            x_mins.push(0);
+            continue;
+        } else if n_contours < 0 {
+            // 组合字形必须带 bbox
+            if !has_bbox {
+                return None;
+            }
+            let composite_start = composite_stream.offset;
+            let mut have_instructions = false;
+            loop {
+                let flags = composite_stream.read_u16()?;
+                let mut size = 2;
+                size += if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+                if flags & WE_HAVE_A_SCALE != 0 {
+                    size += 2;
+                } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                    size += 4;
+                } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                    size += 8;
+                }
+                composite_stream.read_bytes(size)?;
+                have_instructions |= flags & WE_HAVE_INSTRUCTIONS != 0;
+                if flags & MORE_COMPONENTS == 0 {
+                    break;
+                }
+            }
+            let composite = &composite_stream.data[composite_start..composite_stream.offset];
+            push_u16(&mut glyf, n_contours as u16);
+            let bbox = bbox_stream.read_bytes(8)?;
+            x_mins.push(i16::from_be_bytes([bbox[0], bbox[1]]));
+            glyf.extend_from_slice(bbox);
+            glyf.extend_from_slice(composite);
+            if have_instructions {
+                let len = glyph_stream.read_255_u16()?;
+                push_u16(&mut glyf, len);
+                glyf.extend_from_slice(instruction_stream.read_bytes(len as usize)?);
+            }
+        } else {
+            let mut end_points = Vec::with_capacity(n_contours as usize);
+            let mut total = 0usize;
+            for _ in 0..n_contours {
+                total += n_points_stream.read_255_u16()? as usize;
+                if total == 0 || total > 0x10000 {
+                    return None;
+                }
+                end_points.push((total - 1) as u16);
+            }
+            let flags = flag_stream.read_bytes(total)?;
+            let mut points = Vec::with_capacity(total);
+            let (mut x, mut y) = (0i32, 0i32);
+            for &flag in flags {
+                let (dx, dy) = decode_triplet(flag, &mut glyph_stream)?;
+                x += dx;
+                y += dy;
+                points.push((x, y, flag & 0x80 == 0));
+            }
+            let instruction_len = glyph_stream.read_255_u16()?;
+            let bbox = if has_bbox {
+                let b = bbox_stream.read_bytes(8)?;
+                [
+                    i16::from_be_bytes([b[0], b[1]]),
+                    i16::from_be_bytes([b[2], b[3]]),
+                    i16::from_be_bytes([b[4], b[5]]),
+                    i16::from_be_bytes([b[6], b[7]]),
+                ]
+            } else {
+                let mut bbox = [i16::MAX, i16::MAX, i16::MIN, i16::MIN];
+                for &(x, y, _) in points.iter() {
+                    bbox[0] = bbox[0].min(x as i16);
+                    bbox[1] = bbox[1].min(y as i16);
+                    bbox[2] = bbox[2].max(x as i16);
+                    bbox[3] = bbox[3].max(y as i16);
+                }
+                bbox
+            };
+            x_mins.push(bbox[0]);
+            push_u16(&mut glyf, n_contours as u16);
+            for v in bbox.iter() {
+                push_u16(&mut glyf, *v as u16);
+            }
+            for end in end_points {
+                push_u16(&mut glyf, end);
+            }
+            push_u16(&mut glyf, instruction_len);
+            glyf.extend_from_slice(instruction_stream.read_bytes(instruction_len as usize)?);
+            let overlap = overlap_bitmap
+                .map(|b| b[i >> 3] & (0x80 >> (i & 7)) != 0)
+                .unwrap_or(false);
+            write_points(&mut glyf, &points, overlap)?;
+        }
+        // 短格式 loca 要求偏移是偶数，这里统一按 4 字节对齐
+        if glyf.len() > start {
+            pad4(&mut glyf);
+        }
+    }
+    offsets.push(glyf.len());
+
+    let mut loca = vec![];
+    if *index_format == 0 {
+        if glyf.len() > 0x1FFFE {
+            return None;
+        }
+        for offset in offsets {
+            push_u16(&mut loca, (offset >> 1) as u16);
+        }
+    } else {
+        for offset in offsets {
+            push_u32(&mut loca, offset as u32);
+        }
+    }
+    Some((glyf, loca, x_mins))
+}
+
+/// 还原变换过的 hmtx，缺省的 lsb 取字形的 xMin
+fn reconstruct_hmtx(data: &[u8], num_glyphs: usize, num_h_metrics: usize, x_mins: &[i16]) -> Option<Vec<u8>> {
+    let mut rs = Stream::new(data);
+    let flags = rs.read_u8()?;
+    if num_h_metrics == 0 || num_h_metrics > num_glyphs || x_mins.len() < num_glyphs {
+        return None;
+    }
+    let mut advances = Vec::with_capacity(num_h_metrics);
+    for _ in 0..num_h_metrics {
+        advances.push(rs.read_u16()?);
+    }
+    let mut lsbs = Vec::with_capacity(num_glyphs);
+    for (i, x_min) in x_mins.iter().take(num_glyphs).enumerate() {
+        let flag = if i < num_h_metrics { 1 } else { 2 };
+        lsbs.push(if flags & flag != 0 { *x_min } else { rs.read_i16()? });
+    }
+    let mut out = vec![];
+    for i in 0..num_glyphs {
+        if i < num_h_metrics {
+            push_u16(&mut out, advances[i]);
+        }
+        push_u16(&mut out, lsbs[i] as u16);
+    }
+    Some(out)
+}
+
+fn check_sum(data: &[u8]) -> u32 {
+    let mut sum = 0u32;
+    for chunk in data.chunks(4) {
+        let mut b = [0u8; 4];
+        b[..chunk.len()].copy_from_slice(chunk);
+        sum = sum.wrapping_add(u32::from_be_bytes(b));
+    }
+    sum
+}
+
+fn brotli_decompress(data: &[u8], size_hint: usize) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(size_hint.min(MAX_SIZE_HINT));
+    let mut input = data;
+    brotli_decompressor::BrotliDecompress(&mut input, &mut output).ok()?;
+    Some(output)
+}
+
+/// 把 WOFF2 还原成 ttf/otf，暂不支持字体集合
+pub fn decompress_woff2(input: &[u8]) -> Option<Vec<u8>> {
+    if read_u32(input, 0)? != WOFF2_SIGNATURE {
+        return None;
+    }
+    let flavor = read_u32(input, 4)?;
+    if flavor == TTC_TAG {
+        return None;
+    }
+    let num_tables = read_u16(input, 12)? as usize;
+    let total_sfnt_size = read_u32(input, 16)? as usize;
+    let total_compressed_size = read_u32(input, 20)? as usize;
+
+    let mut rs = Stream::new(input);
+    rs.offset = HEADER_SIZE;
+    let mut tables = Vec::with_capacity(num_tables);
+    let mut src_offset = 0usize;
+    for _ in 0..num_tables {
+        let flags = rs.read_u8()?;
+        let tag = if flags & 0x3F == 0x3F {
+            let t = rs.read_bytes(4)?;
+            [t[0], t[1], t[2], t[3]]
+        } else {
+            *KNOWN_TAGS[(flags & 0x3F) as usize]
+        };
+        let version = flags >> 6;
+        let orig_length = rs.read_base128()? as usize;
+        // glyf/loca 的 0 号变换才是真正变换过的，其他表则相反
+        let transformed = if &tag == b"glyf" || &tag == b"loca" {
+            version == 0
+        } else {
+            version != 0
+        };
+        let transform_length = if transformed {
+            Some(rs.read_base128()? as usize)
+        } else {
+            None
+        };
+        let entry = TableEntry { tag, orig_length, transform_length, src_offset };
+        src_offset = src_offset.checked_add(entry.src_length())?;
+        tables.push(entry);
+    }
+
+    let compressed = rs.read_bytes(total_compressed_size)?;
+    let stream = brotli_decompress(compressed, src_offset)?;
+    // 表的范围在累加时已检查不会溢出
+    let get_src = |entry: &TableEntry| stream.get(entry.src_offset..entry.src_offset + entry.src_length());
+    let find = |tag: &[u8; 4]| tables.iter().find(|t| &t.tag == tag);
+
+    let mut index_format = 0u16;
+    let mut glyf_loca: Option<(Vec<u8>, Vec<u8>, Vec<i16>)> = None;
+    if let Some(glyf) = find(b"glyf") {
+        if glyf.transform_length.is_some() {
+            glyf_loca = Some(reconstruct_glyf(get_src(glyf)?, &mut index_format)?);
+        }
+    }
+
+    let mut table_data: Vec<([u8; 4], Vec<u8>)> = Vec::with_capacity(num_tables);
+    for entry in tables.iter() {
+        let data = match (&entry.tag, entry.transform_length, &glyf_loca) {
+            (b"glyf", Some(_), Some((glyf, _, _))) => glyf.clone(),
+            (b"loca", Some(_), Some((_, loca, _))) => loca.clone(),
+            (b"loca", Some(_), None) => return None,
+            (b"hmtx", Some(_), _) => {
+                let x_mins = &glyf_loca.as_ref()?.2;
+                let hhea = get_src(find(b"hhea")?)?;
+                let maxp = get_src(find(b"maxp")?)?;
+                let num_h_metrics = read_u16(hhea, 34)? as usize;
+                let num_glyphs = read_u16(maxp, 4)? as usize;
+                reconstruct_hmtx(get_src(entry)?, num_glyphs, num_h_metrics, x_mins)?
+            }
+            (_, Some(_), _) => return None,
+            _ => get_src(entry)?.to_vec(),
+        };
+        table_data.push((entry.tag, data));
+    }
+    table_data.sort_by_key(|t| t.0);
+
+    // head 里的 checkSumAdjustment 先清零，loca 格式以变换数据为准
+    for (tag, data) in table_data.iter_mut() {
+        if tag == b"head" && data.len() >= 54 {
+            data[8..12].copy_from_slice(&[0, 0, 0, 0]);
+            if glyf_loca.is_some() {
+                data[50..52].copy_from_slice(&index_format.to_be_bytes());
+            }
+        }
+    }
+
+    Some(build_sfnt(flavor, &table_data, total_sfnt_size))
+}
+
+/// 按表标签排好序的表写成 sfnt，并计算 `head` 的 checkSumAdjustment
+pub(crate) fn build_sfnt(flavor: u32, table_data: &[([u8; 4], Vec<u8>)], size_hint: usize) -> Vec<u8> {
+    let num_tables = table_data.len();
+    let mut output = Vec::with_capacity(size_hint.min(MAX_SIZE_HINT));
+    let mut entry_selector = 0u16;
+    while (2usize << entry_selector) <= num_tables {
+        entry_selector += 1;
+    }
+    let search_range = 16u16 << entry_selector;
+    push_u32(&mut output, flavor);
+    push_u16(&mut output, num_tables as u16);
+    push_u16(&mut output, search_range);
+    push_u16(&mut output, entry_selector);
+    push_u16(&mut output, (num_tables as u16 * 16).saturating_sub(search_range));
+
+    let mut offset = 12 + 16 * num_tables;
+    for (tag, data) in table_data.iter() {
+        output.extend_from_slice(tag);
+        push_u32(&mut output, check_sum(data));
+        push_u32(&mut output, offset as u32);
+        push_u32(&mut output, data.len() as u32);
+        offset += (data.len() + 3) & !3;
+    }
+    let mut head_offset = None;
+    for (tag, data) in table_data.iter() {
+        if tag == b"head" {
+            head_offset = Some(output.len());
+        }
+        output.extend_from_slice(data);
+        pad4(&mut output);
+    }
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(check_sum(&output));
+        output[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_triplet, decompress_woff2, push_u16, push_u32, Stream, HEADER_SIZE, WOFF2_SIGNATURE};
+
+    /// 只有一个未变换的表的 WOFF2，`compressed` 为 brotli 数据
+    fn get_woff2(total_sfnt_size: u32, orig_length: &[u8], compressed: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        push_u32(&mut data, WOFF2_SIGNATURE);
+        push_u32(&mut data, 0x00010000);
+        push_u32(&mut data, 0);
+        push_u16(&mut data, 1);
+        push_u16(&mut data, 0);
+        push_u32(&mut data, total_sfnt_size);
+        push_u32(&mut data, compressed.len() as u32);
+        data.resize(HEADER_SIZE, 0);
+        // head 表的索引为 1
+        data.push(0x01);
+        data.extend_from_slice(orig_length);
+        data.extend_from_slice(compressed);
+        data
+    }
+
+    #[test]
+    fn test_read_numbers() {
+        let mut rs = Stream::new(&[0x3F, 0x87, 0x68, 0xFD, 0x01, 0x2C, 0xFF, 0x02, 0xFE, 0x00, 0x80]);
+        assert_eq!(rs.read_base128(), Some(63));
+        assert_eq!(rs.read_base128(), Some(1000));
+        assert_eq!(rs.read_255_u16(), Some(300));
+        assert_eq!(rs.read_255_u16(), Some(255));
+        assert_eq!(rs.read_255_u16(), Some(506));
+        // 前导 0 不合法
+        assert_eq!(rs.read_base128(), None);
+
+        let mut rs = Stream::new(&[0x05, 0x12, 0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(decode_triplet(0x01, &mut rs), Some((0, 5)));
+        assert_eq!(decode_triplet(0x16, &mut rs), Some((-2, 3)));
+        assert_eq!(decode_triplet(0x7F, &mut rs), Some((258, 772)));
+    }
+
+    #[test]
+    fn test_malformed() {
+        // 0x06 为空的 brotli 数据
+        let data = get_woff2(1024, &[0x36], &[0x06]);
+        assert_eq!(decompress_woff2(&data), None);
+        for len in [4, 20, HEADER_SIZE, HEADER_SIZE + 1, data.len() - 1].iter() {
+            assert_eq!(decompress_woff2(&data[..*len]), None);
+        }
+        // 头部声明的大小过大时不按声明预分配
+        let data = get_woff2(u32::MAX, &[0x8F, 0xFF, 0xFF, 0xFF, 0x7F], &[0x06]);
+        assert_eq!(decompress_woff2(&data), None);
+        let data = get_woff2(u32::MAX, &[0x8F, 0xFF, 0xFF, 0xFF, 0x7F], &[0xFF, 0xFF]);
+        assert_eq!(decompress_woff2(&data), None);
+    }
+}
@@ -1,6 +1,6 @@
 use super::svg_util::render::*;
 use super::svg_util::svg_methods::*;
-use core::open_type_like::command::{CommandSegment, CommandsList};
+use core::open_type_like::command::{ColorCommand, CommandSegment, CommandsList};
 use core::open_type_like::path::{PathSegment, PathData, BoundingBox};
 use core::open_type_like::transform::Transform;
 use core::data::text_data::{ArtTextOption, Gradient};
@@ -93,6 +93,21 @@ pub fn exec_text(commands: &CommandsList, width: f32, height: f32, scale: f32) -
                 }
                 ctx.set_transform(transform)
             }
+            CommandSegment::Color(ColorCommand::Layers(ref layers)) => {
+                let scale_transform = Transform::new(scale, 0.0, 0.0, scale, 0.0, 0.0);
+                for layer in layers.iter() {
+                    let mut path = layer.path.clone();
+                    path.transform(scale_transform);
+                    let mut paint = layer.paint.clone();
+                    paint.prepend_transform(&scale_transform);
+                    ctx.fill_style = ctx.create_paint_style(&paint);
+                    ctx.fill(Some(path));
+                }
+            }
+            CommandSegment::Color(ColorCommand::Image(ref format, ref data, (x, y, w, h))) => {
+                let href = into_str!["data:image/", format, ";base64,", base64::encode(data)];
+                ctx.draw_image(href, (x * scale, y * scale, w * scale, h * scale));
+            }
             CommandSegment::Clip => {}
             _ => {}
         }
@@ -131,6 +146,20 @@ pub fn simply_command(commands: &CommandsList) -> PathData {
                     }
                 }
             }
+            // 彩色字形只需要范围，图片按矩形计算
+            CommandSegment::Color(ColorCommand::Layers(ref layers)) => {
+                for layer in layers.iter() {
+                    let mut data = layer.path.clone();
+                    data.transform(transform);
+                    path.extend(data.iter().cloned());
+                }
+            }
+            CommandSegment::Color(ColorCommand::Image(_, _, (x, y, w, h))) => {
+                for (x, y) in [(*x, *y), (x + w, *y), (x + w, y + h), (*x, y + h)].iter() {
+                    let (x, y) = transform.apply(*x, *y);
+                    path.line_to(x, y);
+                }
+            }
             _ => {}
         }
     }
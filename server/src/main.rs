@@ -1,12 +1,17 @@
 use warp::Filter;
 use bytes::Bytes;
-use core::data::text_data::{TextData, WritingMode};
-use core::typesetting::compute_render_command;
-use core::open_type_like::bbox::BBoxes;
+use core::data::text_data::TextData;
+use core::typesetting::{compute_render_command, MergedFont, RenderResult};
 use core::open_type_like::command::{tran_commands_stream, CommandsList};
+use core::open_type_like::hyphenation::HYPHEN;
+use core::open_type_like::glyph::Variations;
+use core::open_type_like::script::split_script_runs;
+use core::Error;
 use font::ttf::FontCache;
+use font::subset::subset_text_data;
 use font::woff::decompress_woff;
-use font::check::check_type;
+use font::woff2::decompress_woff2;
+use font::check::{check_type, is_woff2};
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
@@ -44,6 +49,7 @@ async fn main() {
     let result = include_bytes!("./SourceHanSansSC-Regular.ttf") as &[u8];
     let font_cache = Arc::new(RwLock::new(FontCache::<Vec<u8>>::new()));
     font_cache.write().unwrap().load_font_bytes("default".to_string(), Cow::Borrowed(&result).to_vec());
+    load_fallback(&mut *font_cache.write().unwrap());
 
     let font_update_map = Arc::new(RwLock::new(FontUpdateMap::new()));
     let init_font_map = update_font_update_map();
@@ -102,22 +108,25 @@ async fn main() {
     });
 
 
+    // `?format=json` 时返回 `{"commands": [...], "missingGlyphs": [...]}`，否则只返回数组
     let convert_command = warp::path("convertCommand")
+        .and(warp::query::<HashMap<String, String>>())
         .and(warp::body::bytes())
         .and(font_cache.clone())
         .and(font_update_map_in_warp.clone())
-        .map(|json: Bytes, font_cache, font_update_map_in_warp| {
+        .map(|query: HashMap<String, String>, json: Bytes, font_cache, font_update_map_in_warp| {
             let start = SystemTime::now();
             let json = String::from_utf8(json.to_vec());
             if json.is_err() { return warp::http::Response::builder().status(500).body(String::from("解析字符串失败")).unwrap(); }
             let json = json.unwrap();
             let result = cc(&json, &font_cache, &font_update_map_in_warp);
-            if result.is_none() { return warp::http::Response::builder().status(500).body(String::from("解析文字数据失败")).unwrap(); }
-            let (min_width, b_boxes, commands, _, (_width, _height)) = result.unwrap();
-            let b_boxes: Vec<f32> = (&b_boxes).into();
+            if let Err(e) = &result { return warp::http::Response::builder().status(500).body(format!("解析文字数据失败: {}", e)).unwrap(); }
+            let (result, commands, _) = result.unwrap();
+            let b_boxes: Vec<f32> = (&result.b_boxes).into();
             let commands: Vec<f32> = (&commands).into();
+            let missing = result.missing;
             // todo 最新版应为 `[vec![-5.0, min_width, width, height], b_boxes, commands].concat();`
-            let typed_array: Vec<f32> = [vec![min_width], b_boxes, commands].concat();
+            let typed_array: Vec<f32> = [vec![result.min_width], b_boxes, commands].concat();
             let now = SystemTime::now();
             let diff = now.duration_since(start).unwrap_or(Duration::new(0, 0));
             let font_cache: &FontCache<Vec<u8>> = &*font_cache.read().unwrap();
@@ -127,6 +136,10 @@ async fn main() {
             if diff > Duration::from_secs_f32(0.5) {
                 println!("warning convert_command: {:?} 耗时: {:?}", diff, json);
             }
+            if query.get("format").map(|format| format == "json").unwrap_or(false) {
+                let body = serde_json::json!({ "commands": typed_array, "missingGlyphs": missing });
+                return warp::http::Response::builder().status(200).header("content-type", "application/json").body(body.to_string()).unwrap();
+            }
             warp::http::Response::builder().status(200).body(format!("{:?}", typed_array)).unwrap()
         });
     let compute_box = warp::path("computeBox")
@@ -139,11 +152,11 @@ async fn main() {
             if json.is_err() { return warp::http::Response::builder().status(500).body(String::from("解析字符串失败")).unwrap(); }
             let json = json.unwrap();
             let result = cc(&json, &font_cache, &font_update_map_in_warp);
-            if result.is_none() { return warp::http::Response::builder().status(500).body(String::from("解析文字数据失败")).unwrap(); }
-            let (min_width, b_boxes, _commands, _, (_width, _height)) = result.unwrap();
-            let b_boxes: Vec<f32> = (&b_boxes).into();
+            if let Err(e) = &result { return warp::http::Response::builder().status(500).body(format!("解析文字数据失败: {}", e)).unwrap(); }
+            let (result, _, _) = result.unwrap();
+            let b_boxes: Vec<f32> = (&result.b_boxes).into();
             // todo 最新版应为 `[vec![-5.0, min_width, width, height], b_boxes, commands].concat();`
-            let typed_array: Vec<f32> = [vec![min_width], b_boxes].concat();
+            let typed_array: Vec<f32> = [vec![result.min_width], b_boxes].concat();
             let now = SystemTime::now();
             let diff = now.duration_since(start).unwrap_or(Duration::new(0, 0));
             let font_cache: &FontCache<Vec<u8>> = &*font_cache.read().unwrap();
@@ -162,7 +175,7 @@ async fn main() {
             if json.is_err() { return Err(warp::reject::custom(ProcessError("解析字符串失败".to_string()))); }
             let json = json.unwrap();
             let text_data = TextData::parse(&json);
-            if text_data.is_none() { return Err(warp::reject::custom(ProcessError("解析文字数据失败".to_string()))); }
+            if let Err(e) = &text_data { return Err(warp::reject::custom(ProcessError(format!("解析文字数据失败: {}", e)))); }
             let text_data = text_data.unwrap();
             let texture_raw = if text_data.paragraph.art_text.is_some() {
                 let art_text = text_data.paragraph.art_text.unwrap();
@@ -180,8 +193,10 @@ async fn main() {
         .map(|result: (String, Option<Bytes>, SystemTime), font_cache: AF, font_update_map_in_warp| {
             let (json, texture_raw, start) = result;
             let result = cc(&json, &font_cache, &font_update_map_in_warp);
-            if result.is_none() { return warp::http::Response::builder().status(500).body(String::from("解析文字数据失败")).unwrap(); }
-            let (_min_width, _b_boxes, commands, text_data, (width, height)) = result.unwrap();
+            if let Err(e) = &result { return warp::http::Response::builder().status(500).body(format!("解析文字数据失败: {}", e)).unwrap(); }
+            let (result, commands, text_data) = result.unwrap();
+            let (width, height) = result.size;
+            let scale = result.scale;
             let ref_size = {
                 let mut size = 16f32;
                 if text_data.paragraph.paragraph_content.get(0).is_some() {
@@ -190,19 +205,9 @@ async fn main() {
                         size = blocks.get(0).unwrap().font_size.clone();
                     }
                 }
-                size
+                // shrink-to-fit 缩小后艺术字效果同比例缩小
+                size * scale
             };
-            let mut width = width;
-            let mut height = height;
-
-            match &text_data.paragraph.writing_mode {
-                &WritingMode::HorizontalTB => if text_data.width > width {
-                    width = text_data.width
-                },
-                _ => if text_data.height > height {
-                    height = text_data.height
-                },
-            }
 
             let svg = if text_data.paragraph.art_text.is_some() {
                 let art_text = text_data.paragraph.art_text.unwrap();
@@ -221,6 +226,32 @@ async fn main() {
             }
             warp::http::Response::builder().status(200).header("content-type", "image/svg+xml").body(svg).unwrap()
         });
+    // 只包含文字数据中用到的字符的字体，`fontFamily` 为要子集化的字体族，CFF 轮廓的字体为 OTF
+    let subset_font = warp::path("subsetFont")
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::body::bytes())
+        .and(font_cache.clone())
+        .and(font_update_map_in_warp.clone())
+        .map(|query: HashMap<String, String>, json: Bytes, font_cache: AF, font_update_map_in_warp| {
+            let json = String::from_utf8(json.to_vec());
+            if json.is_err() { return warp::http::Response::builder().status(500).body(String::from("解析字符串失败").into_bytes()).unwrap(); }
+            let text_data = TextData::parse(&json.unwrap());
+            if let Err(e) = &text_data { return warp::http::Response::builder().status(500).body(format!("解析文字数据失败: {}", e).into_bytes()).unwrap(); }
+            let text_data = text_data.unwrap();
+            let font_family = match query.get("fontFamily") {
+                Some(font_family) => font_family.to_string(),
+                None => return warp::http::Response::builder().status(400).body(String::from("缺少 fontFamily").into_bytes()).unwrap()
+            };
+            load_font(&font_family, &font_cache, &font_update_map_in_warp);
+            let font_cache: &FontCache<Vec<u8>> = &*font_cache.read().unwrap();
+            match subset_text_data(font_cache, &font_family, &text_data) {
+                Ok(data) => {
+                    let content_type = if data.starts_with(b"OTTO") { "font/otf" } else { "font/ttf" };
+                    warp::http::Response::builder().status(200).header("content-type", content_type).body(data).unwrap()
+                }
+                Err(e) => warp::http::Response::builder().status(500).body(e.to_string().into_bytes()).unwrap()
+            }
+        });
     let info = warp::path("info")
         .and(font_cache.clone())
         .map(|font_cache: AF| {
@@ -236,40 +267,30 @@ async fn main() {
             format!("延时1分钟")
         });
 
-    let routes = warp::post().and(convert_command.or(compute_box).or(convert_svg).or(info).or(test));
+    let routes = warp::post().and(convert_command.or(compute_box).or(convert_svg).or(subset_font).or(info).or(test));
 
     println!("text service on 8210");
     warp::serve(routes).run(([0, 0, 0, 0], 8210)).await;
 }
 
-fn cc(json: &String, font_cache: &AF, font_update_map: &Arc<RwLock<FontUpdateMap>>) -> Option<(f32, BBoxes, CommandsList, TextData, (f32, f32))> {
-    let text_data = TextData::parse(&json);
-    if text_data.is_none() { return None; }
-    let text_data = text_data.unwrap();
+fn cc(json: &String, font_cache: &AF, font_update_map: &Arc<RwLock<FontUpdateMap>>) -> Result<(RenderResult, CommandsList, TextData), Error> {
+    let text_data = TextData::parse(&json)?;
 
-    let (pre_font, pre_glyph) = {
+    let pre_font = {
         let font_cache_read = &font_cache.read().unwrap();
         let font_update_map_read: &FontUpdateMap = &font_update_map.read().unwrap();
         let mut pre_font = HashSet::<String>::new();
-        let mut pre_glyph = HashSet::<(String, u32)>::new();
-
+        let fallback_families = font_cache_read.get_fallback_families();
         for content in text_data.paragraph.paragraph_content.iter() {
-            let blocks = &content.blocks;
-            for block in blocks.iter() {
-                let text = block.text.clone();
-                let font_family = &block.font_family;
-                if !font_update_map_read.is_latest(font_family) {
-                    pre_font.insert(font_family.to_string());
-                }
-                let mut text_chars = text.chars();
-                while let Some(text) = text_chars.next() {
-                    if !font_cache_read.has_glyph(font_family.to_string(), text as u32) {
-                        pre_glyph.insert((font_family.to_string(), text as u32));
+            for block in content.blocks.iter() {
+                for font_family in font_cache_read.get_font_families(&block.font_family).iter().chain(fallback_families.iter()) {
+                    if !font_update_map_read.is_latest(font_family) {
+                        pre_font.insert(font_family.to_string());
                     }
                 }
             }
         }
-        (pre_font, pre_glyph)
+        pre_font
     };
 
     if pre_font.len() > 0 {
@@ -278,18 +299,53 @@ fn cc(json: &String, font_cache: &AF, font_update_map: &Arc<RwLock<FontUpdateMap
         }
     }
 
-    if pre_glyph.len() > 0 {
+    // 字体加载后才能按字重与样式选出实际使用的字体
+    let (pre_glyph, pre_text) = {
+        let font_cache_read = &font_cache.read().unwrap();
+        let mut pre_glyph = HashSet::<(String, Variations, u32)>::new();
+        let mut pre_text = HashSet::<(String, Variations, String)>::new();
+
+        for content in text_data.paragraph.paragraph_content.iter() {
+            let blocks = &content.blocks;
+            for block in blocks.iter() {
+                let text = block.text.clone();
+                let face_name = font_cache_read.get_face_name(&block.font_family, block.font_weight, block.is_italic());
+                let variations = block.get_variations();
+                // 与排版时一样按文字分段选择回退字体
+                for (start, end, script) in split_script_runs(&text) {
+                    let font_family = &font_cache_read.match_script_face(&face_name, script, &block.lang);
+                    for text in text[start..end].chars() {
+                        if !font_cache_read.has_glyph(font_family.to_string(), &variations, text as u32) {
+                            pre_glyph.insert((font_family.to_string(), variations.clone(), text as u32));
+                        }
+                    }
+                    if !font_cache_read.has_text(font_family.to_string(), &variations, &text[start..end]) {
+                        pre_text.insert((font_family.to_string(), variations.clone(), text[start..end].to_string()));
+                    }
+                }
+                if !font_cache_read.has_glyph(face_name.clone(), &variations, HYPHEN as u32) {
+                    pre_glyph.insert((face_name, variations, HYPHEN as u32));
+                }
+            }
+        }
+        (pre_glyph, pre_text)
+    };
+
+    if pre_glyph.len() > 0 || pre_text.len() > 0 {
         let font_cache = &mut *font_cache.write().unwrap();
-        for (font_family, text) in pre_glyph.iter() {
-            font_cache.check_glyph(font_family.to_string(), *text);
+        for (font_family, variations, text) in pre_glyph.iter() {
+            font_cache.check_glyph(font_family.to_string(), variations, *text);
+        }
+        for (font_family, variations, text) in pre_text.iter() {
+            font_cache.check_text(font_family.to_string(), variations, text);
         }
     }
 
     let font_cache_read = font_cache.read().unwrap();
-    let (b_boxes, result, min_width, rect) = compute_render_command(&text_data, &*font_cache_read).unwrap_or((BBoxes::new(), (HashMap::new(), Vec::new()), -1.0, (20.0, 20.0)));
-    let commands = tran_commands_stream(&result);
+    let result = compute_render_command(&text_data, &*font_cache_read)?;
+    let commands = tran_commands_stream(&result.commands);
 
-    Some((min_width, b_boxes, commands, text_data, rect))
+    Ok((result, commands, text_data))
 }
 
 fn load_font(font_name: &String, font_cache: &AF, font_update_map: &Arc<RwLock<FontUpdateMap>>) -> Option<()> {
@@ -311,10 +367,15 @@ fn load_font(font_name: &String, font_cache: &AF, font_update_map: &Arc<RwLock<F
             return None;
         }
         if let Some((typ, p)) = check_type(&font_buffer) {
-            if "ttf".to_string() == typ.clone() {
+            if typ == "ttf" || typ == "otf" {
                 if p {
                     println!("解压开始 {:?}", &font_name);
-                    if let Some(data1) = decompress_woff(&font_buffer) {
+                    let data1 = if is_woff2(&font_buffer) {
+                        decompress_woff2(&font_buffer)
+                    } else {
+                        decompress_woff(&font_buffer)
+                    };
+                    if let Some(data1) = data1 {
                         font_buffer = data1;
                         println!("解压成功 {:?}", &font_name);
                     } else {
@@ -340,6 +401,28 @@ fn load_font(font_name: &String, font_cache: &AF, font_update_map: &Arc<RwLock<F
     Some(())
 }
 
+/// 读取回退配置 `{"families": {"品牌字体": ["思源黑体"]}, "scripts": {"Zsye": ["NotoColorEmoji"]}}`，
+/// 回退字体与其他字体一样按需加载
+fn load_fallback(font_cache: &mut FontCache<Vec<u8>>) {
+    let read = File::open(&format!("{}fallback.json", FONT_DIR));
+    if read.is_err() { return; }
+    let mut fallback_data = String::from("");
+    if read.unwrap().read_to_string(&mut fallback_data).is_err() { return; }
+    let fallback: JsonValue = match serde_json::from_str(&fallback_data) {
+        Ok(fallback) => fallback,
+        Err(e) => return println!("回退配置格式错误 {:?}", e)
+    };
+    let get_list = |value: &JsonValue| -> Vec<String> {
+        value.as_array().map(|list| list.iter().filter_map(|v| v.as_str()).map(|v| v.to_string()).collect()).unwrap_or_default()
+    };
+    for (font_family, list) in fallback["families"].as_object().into_iter().flatten() {
+        font_cache.set_fallback(font_family.to_string(), get_list(list));
+    }
+    for (script, list) in fallback["scripts"].as_object().into_iter().flatten() {
+        font_cache.set_script_fallback(script.to_string(), get_list(list));
+    }
+}
+
 fn update_font_update_map() -> JsonValue {
     let read = File::open(&format!("{}data.json", FONT_DIR));
     if read.is_err() { return JsonValue::Null; }
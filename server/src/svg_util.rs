@@ -16,6 +16,8 @@ pub mod svg_methods {
     use svg::node::element::{Element, Style};
     use crate::draw::Color;
     use crate::draw::ToColorString;
+    use core::open_type_like::glyph::{GradientExtend, LayerColor, LayerPaint};
+    use core::open_type_like::transform::Transform;
 
     pub fn create_svg_tag(width: f32, height: f32) -> Document {
         let mut svg = Document::new();
@@ -90,6 +92,56 @@ pub mod svg_methods {
         linear_gradient
     }
 
+    pub fn get_layer_color(color: &LayerColor) -> (u8, u8, u8, f32) {
+        match color {
+            LayerColor::Rgba(r, g, b, a) => (*r, *g, *b, *a as f32 / 255.0),
+            LayerColor::Foreground(a) => (0, 0, 0, *a as f32 / 255.0),
+        }
+    }
+
+    /// 彩色字形图层的渐变，坐标为画布坐标，纯色时返回 `None`
+    pub fn create_paint_gradient(paint: &LayerPaint, key: String) -> Option<Element> {
+        let (mut gradient, stops, extend, transform) = match paint {
+            LayerPaint::Solid(_) => return None,
+            LayerPaint::LinearGradient { start, end, stops, extend, transform } => {
+                let mut gradient = Element::new("linearGradient");
+                gradient.assign("x1", start.0);
+                gradient.assign("y1", start.1);
+                gradient.assign("x2", end.0);
+                gradient.assign("y2", end.1);
+                (gradient, stops, extend, transform)
+            }
+            LayerPaint::RadialGradient { start, start_radius, end, end_radius, stops, extend, transform } => {
+                let mut gradient = Element::new("radialGradient");
+                gradient.assign("fx", start.0);
+                gradient.assign("fy", start.1);
+                gradient.assign("fr", *start_radius);
+                gradient.assign("cx", end.0);
+                gradient.assign("cy", end.1);
+                gradient.assign("r", *end_radius);
+                (gradient, stops, extend, transform)
+            }
+        };
+        let Transform { a, b, c, d, e, f } = transform;
+        gradient.assign("id", key);
+        gradient.assign("gradientUnits", "userSpaceOnUse");
+        gradient.assign("gradientTransform", format!("matrix({} {} {} {} {} {})", a, b, c, d, e, f));
+        gradient.assign("spreadMethod", match extend {
+            GradientExtend::Pad => "pad",
+            GradientExtend::Repeat => "repeat",
+            GradientExtend::Reflect => "reflect",
+        });
+        for (offset, color) in stops {
+            let color: Color = get_layer_color(color).into();
+            let mut stop = Element::new("stop");
+            stop.assign("offset", *offset);
+            stop.assign("stop-color", color.to_rgb());
+            stop.assign("stop-opacity", color.get_opacity());
+            gradient.append(stop);
+        }
+        Some(gradient)
+    }
+
     pub fn create_use_tag(id: String) -> Element {
         let mut use_tag = Element::new("use");
         use_tag.assign("xlink:href", into_str!["#", id]);
@@ -226,6 +278,7 @@ pub mod render {
     use super::svg_methods::*;
     use core::open_type_like::transform::Transform;
     use core::open_type_like::path::PathData;
+    use core::open_type_like::glyph::LayerPaint;
     use svg::Node;
     use svg::node::element::Element;
     use crate::draw::Color;
@@ -359,12 +412,45 @@ pub mod render {
             path.assign("stroke", String::from(&self.stroke_style));
             path.assign("stroke-width", "0");
             path.assign("fill", String::from(&self.fill_style));
+            if let Style::Color(_, _, _, a) = self.fill_style {
+                if a < 1.0 { path.assign("fill-opacity", a) }
+            }
             path.assign("stroke-linecap", String::from(&self.line_cap));
             path.assign("stroke-linejoin", String::from(&self.line_join));
             if self.shadow.is_some() { self.apply_shadow(&mut path) }
             self.document.append(path);
         }
 
+        /// 渐变加入 defs 后作为样式，渐变同样应用当前变换
+        pub fn create_paint_style(&mut self, paint: &LayerPaint) -> Style {
+            if let LayerPaint::Solid(color) = paint {
+                let (r, g, b, a) = get_layer_color(color);
+                return Style::Color(r, g, b, a);
+            }
+            let mut paint = paint.clone();
+            paint.prepend_transform(&self.transform);
+            let id = into_str!["gradient-", self.use_id];
+            self.use_id += 1;
+            if let Some(gradient) = create_paint_gradient(&paint, id.clone()) {
+                self.defs.append(gradient);
+            }
+            Style::Href(into_str!["url(#", id, ")"])
+        }
+
+        /// `rect` 为图片左上角与宽高，应用当前变换
+        pub fn draw_image(&mut self, href: String, rect: (f32, f32, f32, f32)) {
+            let (x, y, width, height) = rect;
+            let Transform { a, b, c, d, e, f } = self.transform;
+            let mut image = create_image_tag(href);
+            image.assign("x", x);
+            image.assign("y", y);
+            image.assign("width", width);
+            image.assign("height", height);
+            image.assign("preserveAspectRatio", "none");
+            image.assign("transform", format!("matrix({} {} {} {} {} {})", a, b, c, d, e, f));
+            self.document.append(image);
+        }
+
         fn apply_shadow(&mut self, source: &mut Element) {
             let (color, offset, blur) = self.shadow.unwrap();
             apply_shadow(&mut self.defs, source, color, offset, blur);
@@ -1,13 +1,14 @@
 extern crate skia_safe;
 
 use core::open_type_like::command::tran_commands_stream;
-use core::open_type_like::command::CommandSegment;
-use core::open_type_like::path::PathSegment;
+use core::open_type_like::command::{ColorCommand, CommandSegment};
+use core::open_type_like::glyph::{GradientExtend, LayerColor, LayerPaint};
+use core::open_type_like::path::{PathData, PathSegment};
 use core::open_type_like::transform::Transform;
 
 use std::collections::HashMap;
 
-use skia_safe::{Canvas, Path, Paint, Matrix, paint::Style, Color4f, Point, Color, Surface, EncodedImageFormat, Data};
+use skia_safe::{Canvas, Path, Paint, Matrix, paint::Style, Color4f, Point, Color, Surface, EncodedImageFormat, Data, Image, Rect, Shader, TileMode};
 
 
 
@@ -18,6 +19,72 @@ fn p(x: &f32, y: &f32) -> Point {
     }
 }
 
+fn get_path(data: &PathData) -> Path {
+    let mut path = Path::default();
+    for path_segment in data.iter() {
+        match path_segment {
+            PathSegment::MoveTo { ref x, ref y } => {
+                path.move_to(p(x, y));
+            }
+            PathSegment::LineTo { ref x, ref y } => {
+                path.line_to(p(x, y));
+            }
+            PathSegment::CurveTo { ref x, ref y, ref x1, ref y1, ref x2, ref y2, } => {
+                path.cubic_to(p(x1, y1), p(x2, y2), p(x, y));
+            }
+            PathSegment::ClosePath => {
+                path.close();
+            }
+        }
+    }
+    path
+}
+
+fn get_matrix(t: &Transform) -> Matrix {
+    Matrix::new_all(t.a, t.c, t.e, t.b, t.d, t.f, 0f32, 0f32, 1f32)
+}
+
+fn get_layer_color(color: &LayerColor) -> Color {
+    match color {
+        LayerColor::Rgba(r, g, b, a) => Color::from_argb(*a, *r, *g, *b),
+        LayerColor::Foreground(a) => Color::from_argb(*a, 0, 0, 0),
+    }
+}
+
+/// 彩色字形图层的纯色或渐变
+fn get_layer_paint(layer_paint: &LayerPaint) -> Paint {
+    let mut paint = Paint::default();
+    paint.set_style(Style::Fill);
+    let get_stops = |stops: &Vec<(f32, LayerColor)>| -> (Vec<Color>, Vec<f32>) {
+        (stops.iter().map(|(_, color)| get_layer_color(color)).collect(), stops.iter().map(|(offset, _)| *offset).collect())
+    };
+    let get_tile_mode = |extend: &GradientExtend| match extend {
+        GradientExtend::Pad => TileMode::Clamp,
+        GradientExtend::Repeat => TileMode::Repeat,
+        GradientExtend::Reflect => TileMode::Mirror,
+    };
+    match layer_paint {
+        LayerPaint::Solid(color) => {
+            paint.set_color(get_layer_color(color));
+        }
+        LayerPaint::LinearGradient { start, end, stops, extend, transform } => {
+            let (colors, offsets) = get_stops(stops);
+            let matrix = get_matrix(transform);
+            paint.set_shader(Shader::linear_gradient(
+                (p(&start.0, &start.1), p(&end.0, &end.1)), colors.as_slice(), offsets.as_slice(), get_tile_mode(extend), None, &matrix,
+            ));
+        }
+        LayerPaint::RadialGradient { start, start_radius, end, end_radius, stops, extend, transform } => {
+            let (colors, offsets) = get_stops(stops);
+            let matrix = get_matrix(transform);
+            paint.set_shader(Shader::two_point_conical_gradient(
+                p(&start.0, &start.1), *start_radius, p(&end.0, &end.1), *end_radius, colors.as_slice(), offsets.as_slice(), get_tile_mode(extend), None, &matrix,
+            ));
+        }
+    }
+    paint
+}
+
 pub fn exec_skia_command(source: &Vec<CommandSegment>, width: f32, height: f32, scale: f32) -> Option<Data> {
     let width_height = ((width * scale) as i32, (height * scale) as i32);
     let mut surface = Surface::new_raster_n32_premul(width_height).expect("no surface");
@@ -27,25 +94,9 @@ pub fn exec_skia_command(source: &Vec<CommandSegment>, width: f32, height: f32,
     for command in source {
         match command {
             CommandSegment::Path(ref data) => {
-                target_path = Path::default();
                 let mut data = data.clone();
                 data.transform(Transform::new(scale as f32, 0.0, 0.0, scale as f32, 0.0, 0.0));
-                for path_segment in data.iter() {
-                    match path_segment {
-                        PathSegment::MoveTo { ref x, ref y } => {
-                            target_path.move_to(p(x, y));
-                        }
-                        PathSegment::LineTo { ref x, ref y } => {
-                            target_path.line_to(p(x, y));
-                        }
-                        PathSegment::CurveTo { ref x, ref y, ref x1, ref y1, ref x2, ref y2, } => {
-                            target_path.cubic_to(p(x1, y1), p(x2, y2), p(x, y));
-                        }
-                        PathSegment::ClosePath => {
-                            target_path.close();
-                        }
-                    }
-                }
+                target_path = get_path(&data);
             }
             CommandSegment::Fill(ref color) => {
                 let mut paint = Paint::default();
@@ -73,6 +124,22 @@ pub fn exec_skia_command(source: &Vec<CommandSegment>, width: f32, height: f32,
                 }
                 canvas.set_matrix(&transform);
             }
+            CommandSegment::Color(ColorCommand::Layers(ref layers)) => {
+                let scale_transform = Transform::new(scale, 0.0, 0.0, scale, 0.0, 0.0);
+                for layer in layers.iter() {
+                    let mut data = layer.path.clone();
+                    data.transform(scale_transform);
+                    let mut paint = layer.paint.clone();
+                    paint.prepend_transform(&scale_transform);
+                    canvas.draw_path(&get_path(&data), &get_layer_paint(&paint));
+                }
+            }
+            CommandSegment::Color(ColorCommand::Image(_, ref data, (x, y, w, h))) => {
+                if let Some(image) = Image::from_encoded(Data::new_copy(data), None) {
+                    let rect = Rect::from_xywh(x * scale, y * scale, w * scale, h * scale);
+                    canvas.draw_image_rect(&image, None, &rect, &Paint::default());
+                }
+            }
             CommandSegment::Clip => {}
             _ => {}
         }
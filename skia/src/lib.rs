@@ -3,7 +3,7 @@ mod bind;
 pub use bind::exec_skia_command;
 use core::typesetting::MergedFont;
 use core::data::font_data::FontData;
-use core::open_type_like::glyph::Glyph;
+use core::open_type_like::glyph::{Glyph, Variations};
 use std::collections::HashMap;
 
 pub struct FontMap {
@@ -35,12 +35,12 @@ impl std::ops::DerefMut for FontMap {
 }
 
 impl MergedFont for FontMap {
-    fn char_to_glyph<'a>(&'a self, font_name: String, c: char) -> &'a Box<Glyph> {
+    fn char_to_glyph<'a>(&'a self, font_name: String, _variations: &Variations, c: char) -> Option<&'a Box<Glyph>> {
         let mut result = self.get(&font_name);
         if result.is_none() {
             result = self.get("default");
         }
-        let font = result.unwrap();
+        let font = result?;
         let index = c as u32;
 
         let pack_index = font.glyph_index_map.get(&index.to_string());
@@ -48,8 +48,8 @@ impl MergedFont for FontMap {
         if pack_index.is_some() {
             i = pack_index.unwrap();
         }
-        let (a, g) = &font.glyphs_pack[*i];
-        g
+        let (_, g) = font.glyphs_pack.get(*i)?;
+        Some(g)
     }
 }
 
@@ -91,14 +91,14 @@ mod tests {
             font_data_ref.insert(ff.clone(), font_data.clone());
         }
         let text_data = TextData::parse(&test_text_json).unwrap();
-        let (b_box, result, _, _) = compute_render_command(&text_data, &font_data_ref).unwrap();
-        let b_box = b_box.get_total_box();
+        let result = compute_render_command(&text_data, &font_data_ref).unwrap();
+        let b_box = result.b_boxes.get_total_box();
         let mut width = b_box.get_width().ceil() as f32;
         let height = b_box.get_height().ceil() as f32;
         if test_text_data.width > width {
             width = test_text_data.width
         }
-        let result1 = tran_commands_stream(&result);
+        let result1 = tran_commands_stream(&result.commands);
         let result = exec_skia_command(&result1, width, height, 2.0).unwrap();
         let time = std::time::SystemTime::now();
         let st = String::from(&result1);
@@ -36,7 +36,8 @@ const loadFontAll = async (textData: TextData) => {
   let fontFamilyList: string[] = []
   textData.paragraph.contents.forEach(content => {
     content.blocks.map(block => {
-      fontFamilyList.push(block.fontFamily)
+      const families = Array.isArray(block.fontFamily) ? block.fontFamily : block.fontFamily.split(',')
+      fontFamilyList.push(...families.map(ff => ff.trim().replace(/^['"]|['"]$/g, '')).filter(ff => ff))
     })
   })
   fontFamilyList = [...new Set(fontFamilyList)]
@@ -140,7 +141,7 @@ interface TextData {
       paragraphIndentation: number
       blocks: {
         text: string
-        fontFamily: string
+        fontFamily: string | string[]
         fontSize: number
         letterSpacing: number
         fill: string
@@ -5,19 +5,23 @@ extern crate js_sys;
 extern crate web_sys;
 extern crate wasm_bindgen;
 
-use std::collections::HashMap;
 use std::borrow::Cow;
 
-use crate::wasm_bindgen::prelude::wasm_bindgen;
+use crate::wasm_bindgen::prelude::{wasm_bindgen, JsValue};
 
 use core::open_type_like::command::{tran_commands_stream};
-use core::open_type_like::bbox::{BBoxes};
-use core::typesetting::{compute_render_command, MergedFont};
-use core::data::text_data::{TextData, WritingMode};
-use core::open_type_like::glyph::Glyph;
+use core::typesetting::{compute_render_command, find_missing_glyphs, FontFace, MergedFont};
+use core::data::text_data::TextData;
+use core::data::validate::json_schema;
+use core::open_type_like::glyph::{Glyph, ShapedGlyph, ShapeOption, Variations};
+use core::open_type_like::hyphenation::{set_patterns, Patterns, HYPHEN};
+use core::open_type_like::script::split_script_runs;
+use core::Error;
 use font::ttf::FontCache;
-use font::check::check_type;
+use font::check::{check_type, is_woff2};
+use font::subset::subset_text_data;
 use font::woff::decompress_woff;
+use font::woff2::decompress_woff2;
 
 #[wasm_bindgen]
 extern "C" {
@@ -37,6 +41,36 @@ fn now() -> f64 {
     performance()
 }
 
+fn to_js_error(error: Error) -> JsValue {
+    js_sys::Error::new(&error.to_string()).into()
+}
+
+/// 文字数据格式的 JSON Schema，供前端提交前校验
+#[wasm_bindgen(js_name = getTextDataSchema)]
+pub fn get_text_data_schema() -> String {
+    json_schema()
+}
+
+/// 加载语言的 Liang 断词模式（TeX `.pat` 格式），wasm 不内置断词模式，`hyphens: "auto"` 前需要先加载
+#[wasm_bindgen(js_name = setHyphenationPatterns)]
+pub fn set_hyphenation_patterns(lang: &str, source: &str, left_min: Option<u32>, right_min: Option<u32>) {
+    let patterns = Patterns::parse(source, left_min.unwrap_or(2) as usize, right_min.unwrap_or(3) as usize);
+    set_patterns(lang, patterns);
+}
+
+/// 严格模式校验，返回 `{ pointer, message }` 数组，没有问题时为空数组
+#[wasm_bindgen(js_name = validateTextData)]
+pub fn validate_text_data(text_data: &str) -> js_sys::Array {
+    let errors = TextData::parse_strict(text_data).err().unwrap_or_default();
+    errors.iter().map(|error| {
+        let item = js_sys::Object::new();
+        let pointer = error.pointer().map(JsValue::from).unwrap_or(JsValue::NULL);
+        js_sys::Reflect::set(&item, &"pointer".into(), &pointer).ok();
+        js_sys::Reflect::set(&item, &"message".into(), &error.to_string().into()).ok();
+        JsValue::from(item)
+    }).collect()
+}
+
 
 #[wasm_bindgen]
 impl Executor {
@@ -45,65 +79,92 @@ impl Executor {
         Executor(FontCache::new())
     }
 
+    /// 解析或排版失败时抛出带 JSON 路径的错误
     #[wasm_bindgen(js_name = exec)]
-    pub fn exec(&mut self, text_data: &str) -> Box<[f32]> {
+    pub fn exec(&mut self, text_data: &str) -> Result<Box<[f32]>, JsValue> {
 //        let start = now();
         let text_data = text_data.to_string();
-        let text_data = &TextData::parse(&text_data).expect(&format!("文字解析失败{}", text_data));
+        let text_data = &TextData::parse(&text_data).map_err(to_js_error)?;
+        self.prepare(text_data);
 
-        for content in text_data.paragraph.paragraph_content.iter() {
-            let blocks = &content.blocks;
-            for block in blocks.iter() {
-                let text = block.text.clone();
-                let font_family = &block.font_family;
-                let mut text_chars = text.chars();
-                while let Some(text) = text_chars.next() {
-                    self.check_glyph(font_family.to_string(), text as u32);
-                }
-            }
-        }
-
-        let (b_boxes, result, min_width, (width, height)) = compute_render_command(text_data, self).unwrap_or((BBoxes::new(), (HashMap::new(), Vec::new()), -1.0, (20.0, 20.0)));
-        let mut width = width;
-        let mut height = height;
-
-        match &text_data.paragraph.writing_mode {
-            &WritingMode::HorizontalTB => if text_data.width > width {
-                width = text_data.width
-            },
-            _ => if text_data.height > height {
-                height = text_data.height
-            },
-        }
-        let result = tran_commands_stream(&result);
+        let result = compute_render_command(text_data, self).map_err(to_js_error)?;
+        let commands = tran_commands_stream(&result.commands);
 
-        let b_boxes: Vec<f32> = (&b_boxes).into();
-        let commands: Vec<f32> = (&result).into();
-        let typed_array: Vec<f32> = [vec![-5.0, min_width, width, height], b_boxes, commands].concat();
+        let b_boxes: Vec<f32> = (&result.b_boxes).into();
+        let commands: Vec<f32> = (&commands).into();
+        let (width, height) = result.size;
+        let typed_array: Vec<f32> = [vec![-5.0, result.min_width, width, height], b_boxes, commands].concat();
         let boxed_array = typed_array.into_boxed_slice();
 
 //        js_console_log(&format!("缓存数量 {:?} 耗时 {:?}", self.get_cache_count(), now() - start));
-        boxed_array
+        Ok(boxed_array)
+    }
+
+    /// 已加载的字体都没有字形的字符，返回 `{ contentIndex, blockIndex, offset, charCode, fontFamily }` 数组，
+    /// `offset` 为字符在文字块中的序号
+    #[wasm_bindgen(js_name = findMissingGlyphs)]
+    pub fn find_missing_glyphs(&mut self, text_data: &str) -> Result<js_sys::Array, JsValue> {
+        let text_data = &TextData::parse(text_data).map_err(to_js_error)?;
+        self.prepare(text_data);
+        Ok(find_missing_glyphs(text_data, self).iter().map(|missing| {
+            let item = js_sys::Object::new();
+            js_sys::Reflect::set(&item, &"contentIndex".into(), &(missing.content_index as u32).into()).ok();
+            js_sys::Reflect::set(&item, &"blockIndex".into(), &(missing.block_index as u32).into()).ok();
+            js_sys::Reflect::set(&item, &"offset".into(), &(missing.offset as u32).into()).ok();
+            js_sys::Reflect::set(&item, &"charCode".into(), &missing.char_code.into()).ok();
+            js_sys::Reflect::set(&item, &"fontFamily".into(), &missing.font_family.as_str().into()).ok();
+            JsValue::from(item)
+        }).collect())
     }
 
+    /// 只包含文字数据中用到的字符的字体，用于导出时嵌入，CFF 轮廓的字体为 OTF
+    #[wasm_bindgen(js_name = subsetFont)]
+    pub fn subset_font(&self, font_family: &str, text_data: &str) -> Result<Box<[u8]>, JsValue> {
+        let text_data = &TextData::parse(text_data).map_err(to_js_error)?;
+        match subset_text_data(&self.0, font_family, text_data) {
+            Ok(data) => Ok(data.into_boxed_slice()),
+            Err(e) => Err(js_sys::Error::new(&e.to_string()).into())
+        }
+    }
+
+    /// 字体族缺字时依次使用的字体族
+    #[wasm_bindgen(js_name = setFallback)]
+    pub fn set_fallback(&mut self, font_family: String, fallback: &js_sys::Array) {
+        let fallback = fallback.iter().filter_map(|v| v.as_string()).collect();
+        self.0.set_fallback(font_family, fallback);
+    }
+
+    /// 按文字回退的字体族，`script` 为 ISO 15924 代码，如 `Hani`、`Zsye`
+    #[wasm_bindgen(js_name = setScriptFallback)]
+    pub fn set_script_fallback(&mut self, script: String, fallback: &js_sys::Array) {
+        let fallback = fallback.iter().filter_map(|v| v.as_string()).collect();
+        self.0.set_script_fallback(script, fallback);
+    }
+
+    /// 字体集合不指定 `index` 时加载其中所有字体
     #[wasm_bindgen(js_name = loadFontBuffer)]
-    pub fn load_font_buffer(&mut self, font_name: String, data: &[u8]) {
+    pub fn load_font_buffer(&mut self, font_name: String, data: &[u8], index: Option<u32>) {
         let data = Cow::Borrowed(data);
         let mut data = data.to_vec();
         if let Some((typ, p)) = check_type(&data) {
-            if "ttf".to_string() == typ.clone() {
+            if typ == "ttf" || typ == "otf" {
                 if p {
                     let start = now();
                     js_console_info(&format!("解压开始 {:?}", &font_name));
-                    if let Some(data1) = decompress_woff(&data) {
+                    let data1 = if is_woff2(&data) {
+                        decompress_woff2(&data)
+                    } else {
+                        decompress_woff(&data)
+                    };
+                    if let Some(data1) = data1 {
                         data = data1;
                     } else {
                         js_console_info(&format!("解压成功 {:?} 耗时 {:?}", &font_name, now() - start));
                     }
                 }
-                let result = self.load_font_bytes(font_name.clone(), data);
+                let result = self.load_font_face(font_name.clone(), data, index);
                 if result.is_none() {
-                    js_console_error(&format!("加载失败 {:?} 不标准的ttf", &font_name));
+                    js_console_error(&format!("加载失败 {:?} 不标准的{}", &font_name, typ));
                 }
             } else {
                 js_console_error(&format!("加载失败 {:?} {:?}", font_name, typ));
@@ -112,6 +173,28 @@ impl Executor {
     }
 }
 
+impl Executor {
+    /// 预先缓存排版用到的字形，与排版时一样按文字分段选择回退字体
+    fn prepare(&mut self, text_data: &TextData) {
+        for content in text_data.paragraph.paragraph_content.iter() {
+            let blocks = &content.blocks;
+            for block in blocks.iter() {
+                let text = block.text.clone();
+                let face_name = self.get_face_name(&block.font_family, block.font_weight, block.is_italic());
+                let variations = &block.get_variations();
+                for (start, end, script) in split_script_runs(&text) {
+                    let font_family = &self.0.match_script_face(&face_name, script, &block.lang);
+                    for text in text[start..end].chars() {
+                        self.check_glyph(font_family.to_string(), variations, text as u32);
+                    }
+                    self.check_text(font_family.to_string(), variations, &text[start..end]);
+                }
+                self.check_glyph(face_name, variations, HYPHEN as u32);
+            }
+        }
+    }
+}
+
 impl std::ops::Deref for Executor {
     type Target = FontCache<Vec<u8>>;
 
@@ -129,7 +212,27 @@ impl std::ops::DerefMut for Executor {
 }
 
 impl MergedFont for Executor {
-    fn char_to_glyph<'a>(&'a self, font_name: String, char: char) -> &'a Box<Glyph> {
-        self.0.char_to_glyph(font_name, char)
+    fn char_to_glyph<'a>(&'a self, font_name: String, variations: &Variations, char: char) -> Option<&'a Box<Glyph>> {
+        self.0.char_to_glyph(font_name, variations, char)
+    }
+
+    fn shape(&self, font_name: String, text: &str, option: &ShapeOption) -> Option<Vec<ShapedGlyph>> {
+        self.0.shape(font_name, text, option)
+    }
+
+    fn get_kerning(&self, font_name: String, left: u32, right: u32) -> i32 {
+        self.0.get_kerning(font_name, left, right)
+    }
+
+    fn id_to_glyph<'a>(&'a self, font_name: String, variations: &Variations, glyph_id: u32) -> Option<&'a Box<Glyph>> {
+        self.0.id_to_glyph(font_name, variations, glyph_id)
+    }
+
+    fn match_face(&self, font_family: &str, weight: u16, italic: bool) -> FontFace {
+        self.0.match_face(font_family, weight, italic)
+    }
+
+    fn match_script_face(&self, font_name: &str, script: &str, lang: &str) -> String {
+        self.0.match_script_face(font_name, script, lang)
     }
 }